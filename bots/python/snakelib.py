@@ -18,6 +18,34 @@ OSMODE = OsMode.LINUX
 PIPE_BASE_NAME = r'\\.\pipe\SnakePipe'
 SOCK_BASE_NAME = '/tmp/multisnake'
 
+# Set by the game for bots it starts, "en" or "de"
+LANGUAGE = os.environ.get("MULTISNAKE_LANGUAGE", "en")
+
+MESSAGES = {
+    "en": {
+        "waiting": "Waiting for game...",
+        "connected": "Connected as {name}",
+        "won": "Player with id {winner_id} won",
+        "override_update": "You have to override the update method",
+        "new_game": "New Game!",
+    },
+    "de": {
+        "waiting": "Warte auf das Spiel...",
+        "connected": "Verbunden als {name}",
+        "won": "Spieler mit der id {winner_id} hat gewonnen",
+        "override_update": "Du musst die update Methode überschreiben",
+        "new_game": "Neues Spiel!",
+    },
+}
+
+def tr(key, **kwargs):
+    messages = MESSAGES.get(LANGUAGE, MESSAGES["en"])
+    return messages.get(key, MESSAGES["en"][key]).format(**kwargs)
+
+def frame(packet):
+    # Every packet is sent with its length in front
+    return struct.pack("<I", len(packet)) + packet
+
 class GameEnd(Exception):
     pass
 
@@ -27,6 +55,15 @@ class Direction(enum.Enum):
     LEFT = 12
     RIGHT = 13
 
+class Feature(enum.Enum):
+    DEBUG_OVERLAY = 1  # send_marked_cells and send_log
+    JSON = 2
+    DELTAS = 3  # Not granted yet, gamestates stay complete
+
+PROTOCOL_VERSION = 1
+HELLO_PACKET_ID = 30
+WELCOME_PACKET_ID = 31
+
 class SnakeState(enum.Enum):
     ALIVE = 1
     DEAD = 0
@@ -66,12 +103,30 @@ class BufferReader:
         self.buff = self.buff[length:]
         return self
 
+class Welcome:
+    """
+    Answer of the game to the hello, the snake id is counted like the ids in the grid (10 = first snake).
+    """
+    def __init__(self, buffer):
+        reader = BufferReader(buffer)
+        self.version = reader.read_ushort()
+        self.snake_id = reader.read_ushort() + 10
+        self.width = reader.read_ushort()
+        self.height = reader.read_ushort()
+        self.tick_duration = reader.read_uint() / 1000  # Seconds
+        self.time_limit = reader.read_uint()  # Seconds
+        feature_count = reader.read_ubyte()
+        self.features = [Feature(reader.read_ubyte()) for _ in range(feature_count)]
+
+    def __repr__(self):
+        return f"<Welcome version={self.version} snake_id={self.snake_id} grid={self.width}x{self.height} features={self.features}>"
+
 class GameGrid:
     def __init__(self, grid_data, height, width):
         """
-        Ein Grid ist eine 2-Dimenstionale Array mit folgenden Werten:
+        A grid is a 2-dimensional array with the following values:
         -1 = Food
-        0  = Nichts
+        0  = Nothing
         10 = Snake 1 (id=10)
         11 = Snake 2 (id=11)
         ...
@@ -150,20 +205,35 @@ class BaseSnakeAi:
         """
         To use multiple ais give each of them different 'player_slot's.
         1-12 are possible.
+        A 'player_slot' like "192.168.0.2:7878" connects over TCP to a game
+        on another machine, as does the MULTISNAKE_TCP environment variable.
         """
         self.name = name
         self.player_slot = str(player_slot)
         self.current_markes_cells_packet = None
-        
+        self.log_packets = []
+        self.features = [Feature.DEBUG_OVERLAY]  # Asked for in the hello
+        self.welcome = None
+        self.tcp_address = os.environ.get("MULTISNAKE_TCP", self.player_slot if ":" in self.player_slot else None)
+        self.use_pipe = OSMODE == OsMode.WINDOWS and self.tcp_address is None
+
     def start(self):
-        print("Waiting for game...")
-        print(PIPE_BASE_NAME + self.player_slot)
+        # Bots started by the game get their socket path from the environment
+        if self.tcp_address is not None:
+            host, port = self.tcp_address.rsplit(":", 1)
+            address = (host, int(port))
+        elif OSMODE == OsMode.WINDOWS:
+            address = os.environ.get("MULTISNAKE_SOCKET", PIPE_BASE_NAME + self.player_slot)
+        else:
+            address = os.environ.get("MULTISNAKE_SOCKET", SOCK_BASE_NAME + self.player_slot + ".sock")
+        print(tr("waiting"))
+        print(address)
         while True:
             time.sleep(1)
             try:
-                if OSMODE == OsMode.WINDOWS:
+                if self.use_pipe:
                     self.pipe = win32file.CreateFile(
-                        PIPE_BASE_NAME + self.player_slot,
+                        address,
                         win32file.GENERIC_READ | win32file.GENERIC_WRITE,
                         0,
                         None,
@@ -172,9 +242,13 @@ class BaseSnakeAi:
                         None
                     )
                     break
+                elif self.tcp_address is not None:
+                    self.sock = socket.create_connection(address)
+                    self.sock.setsockopt(socket.IPPROTO_TCP, socket.TCP_NODELAY, 1)
+                    break
                 else:
                     self.sock = socket.socket(socket.AF_UNIX, socket.SOCK_STREAM)
-                    self.sock.connect(SOCK_BASE_NAME + self.player_slot + ".sock")
+                    self.sock.connect(address)
                     break
             except KeyboardInterrupt:
                 sys.exit()
@@ -182,65 +256,101 @@ class BaseSnakeAi:
                 time.sleep(1)
                 continue
 
-        print(f"Connected as {self.name}")
+        print(tr("connected", name=self.name))
 
-        if OSMODE == OsMode.WINDOWS:
-            win32file.WriteFile(self.pipe, self.name.encode())
-        else:
-            self.sock.sendall(self.name.encode())
+        self.pending = b''
+        self.write(frame(self.hello_packet()))
 
         while True:
             try:
-                if OSMODE == OsMode.WINDOWS:
-                    response = win32file.ReadFile(self.pipe, 64 * 1024)
-                else:
-                    response = (0, self.sock.recv(1024))
-                    print(response)
+                buffer = self.read_packet()
             except:
                 raise GameEnd
-            
-            if response[0] == 0:
-                buffer  = response[1]
-                if len(buffer) <= 0:
-                    break
 
-                if buffer[0] == 0:
-                    try:
-                        data = SnakeData(buffer[1:])
-                        self.me = data.me
-                        direction: Direction = self.update(data)
-                    except Exception as e:
-                        print("[ERROT]", e)
-                        raise e
-                        # continue
-                    
-                    try:
-                        packet = struct.pack("B", direction.value)
-                        if self.current_markes_cells_packet is not None:
-                            packet += self.current_markes_cells_packet
-                            self.current_markes_cells_packet = None
-
-                        if OSMODE == OsMode.WINDOWS:
-                            win32file.WriteFile(self.pipe, packet)
-                        else:
-                            self.sock.sendall(packet)
-                    except Exception as e:
-                        print(e)
-                        raise GameEnd
-                    
-                elif buffer[0] == 2:
-                    winner_id = struct.unpack("i", buffer[1:5])[0] + 10
-                    self.on_gameend(winner_id)
+            if len(buffer) <= 0:
+                break
+
+            if buffer[0] == 0:
+                try:
+                    data = SnakeData(buffer[1:])
+                    self.me = data.me
+                    direction: Direction = self.update(data)
+                except Exception as e:
+                    print("[ERROT]", e)
+                    raise e
+                    # continue
+                
+                try:
+                    packet = frame(struct.pack("B", direction.value))
+                    if self.current_markes_cells_packet is not None:
+                        packet += frame(self.current_markes_cells_packet)
+                        self.current_markes_cells_packet = None
+                    packet += b''.join(frame(x) for x in self.log_packets)
+                    self.log_packets = []
+
+                    self.write(packet)
+                except Exception as e:
+                    print(e)
+                    raise GameEnd
+                
+            elif buffer[0] == 2:
+                winner_id = struct.unpack("i", buffer[1:5])[0] + 10
+                self.on_gameend(winner_id)
+
+            elif buffer[0] == WELCOME_PACKET_ID:
+                self.welcome = Welcome(buffer[1:])
+                self.on_welcome(self.welcome)
+
+    def write(self, data):
+        if self.use_pipe:
+            win32file.WriteFile(self.pipe, data)
+        else:
+            self.sock.sendall(data)
+
+    def read_packet(self):
+        """
+        Returns the next packet without its length, b'' once the game closed the connection.
+        """
+        while True:
+            if len(self.pending) >= 4:
+                length = struct.unpack("<I", self.pending[:4])[0]
+                if len(self.pending) >= 4 + length:
+                    packet = self.pending[4:4 + length]
+                    self.pending = self.pending[4 + length:]
+                    return packet
+            if self.use_pipe:
+                data = win32file.ReadFile(self.pipe, 64 * 1024)[1]
+            else:
+                data = self.sock.recv(64 * 1024)
+            if len(data) == 0:
+                return b''
+            self.pending += data
+
+    def hello_packet(self):
+        name = self.name.encode()[:64]
+        packet = struct.pack("<BHH", HELLO_PACKET_ID, PROTOCOL_VERSION, len(name)) + name
+        return packet + struct.pack("B", len(self.features)) + bytes(x.value for x in self.features)
 
     def send_marked_cells(self, cells):
         packet = b'\x14' + struct.pack('H', len(cells)) + b''.join([struct.pack('H', x) for x in cells])
         self.current_markes_cells_packet = packet
 
+    def send_log(self, text):
+        """
+        Shows a line in the event log of this snake (Show Details in the scoreboard).
+        Sent together with the next direction.
+        """
+        data = str(text).encode()[:200]
+        self.log_packets.append(b'\x15' + struct.pack('H', len(data)) + data)
+
+    def on_welcome(self, welcome):
+        print(welcome)
+
     def on_gameend(self, winner_id):
-        print(f"Player with id {winner_id} won")
+        print(tr("won", winner_id=winner_id))
 
     def update(self, data: SnakeData) -> Direction:
-        raise NotImplementedError("Du musst die update methode überschreiben")
+        raise NotImplementedError(tr("override_update"))
 
 if __name__ == "__main__":
     while True:
@@ -248,6 +358,6 @@ if __name__ == "__main__":
             player = BaseSnakeAi("PyAi", player_slot='1')
             player.start()
         except GameEnd:
-            print("New Game!")
+            print(tr("new_game"))
             continue
 
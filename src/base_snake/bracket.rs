@@ -0,0 +1,502 @@
+use std::fmt::Write;
+
+use super::{locale::{tr, Text}, match_result::MatchResult, rating::RatingTable};
+
+// Elimination matches without a winner are replayed this often, then the better seed advances
+pub const MAX_REPLAYS: u32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BracketFormat {
+    Swiss { rounds: u32 },
+    SingleElimination,
+    DoubleElimination
+}
+
+impl BracketFormat {
+    pub fn swiss(entrant_count: usize) -> Self {
+        // Enough rounds to find a single undefeated entrant
+        let rounds = (entrant_count.max(2) as f32).log2().ceil() as u32;
+        BracketFormat::Swiss { rounds }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            BracketFormat::Swiss { .. } => "Swiss",
+            BracketFormat::SingleElimination => "Single Elimination",
+            BracketFormat::DoubleElimination => "Double Elimination",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        // Translated label for the ui
+        tr(match self {
+            BracketFormat::Swiss { .. } => Text::Swiss,
+            BracketFormat::SingleElimination => Text::SingleElimination,
+            BracketFormat::DoubleElimination => Text::DoubleElimination,
+        })
+    }
+
+    fn lives(&self) -> u32 {
+        match self {
+            BracketFormat::Swiss { .. } => u32::MAX,
+            BracketFormat::SingleElimination => 1,
+            BracketFormat::DoubleElimination => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BracketMatch {
+    pub round: u32,
+    pub tier: u32, // Losses of the entrants before the match, 0 is the winners bracket
+    pub is_final: bool, // Elimination match between entrants of different tiers
+    pub entrants: Vec<usize>, // Indices into the entrant list, the snake ids of the match are the positions in here
+    pub winner: Option<usize>,
+    pub draw: bool,
+    pub bye: bool, // Single entrant that advances without playing
+    pub replays: u32, // Games without a winner so far
+    pub by_seed: bool // Won by seed after MAX_REPLAYS games without a winner
+}
+
+impl BracketMatch {
+    pub fn is_pending(&self) -> bool {
+        !self.bye && !self.draw && self.winner.is_none()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Bracket {
+    format: BracketFormat,
+    names: Vec<String>,
+    seeds: Vec<usize>, // Seed of every entrant, 0 is the best
+    points: Vec<f32>, // Swiss only, a win is 1 and a draw 0.5
+    losses: Vec<u32>,
+    byes: Vec<u32>,
+    eliminated: Vec<Option<u32>>, // Round in which the entrant was knocked out
+    matches: Vec<BracketMatch>,
+    round: u32
+}
+
+impl Bracket {
+    pub fn new(format: BracketFormat, names: Vec<String>, ratings: Option<&RatingTable>) -> Self {
+        // Seeded by rating, entry order breaks ties
+        let count = names.len();
+        let mut order: Vec<usize> = (0..count).collect();
+        if let Some(ratings) = ratings {
+            order.sort_by(|a, b| ratings.get(&names[*b]).rating.total_cmp(&ratings.get(&names[*a]).rating));
+        }
+        let mut seeds = vec![0; count];
+        for (seed, entrant) in order.iter().enumerate() {
+            seeds[*entrant] = seed;
+        }
+
+        Self {
+            format,
+            names,
+            seeds,
+            points: vec![0.; count],
+            losses: vec![0; count],
+            byes: vec![0; count],
+            eliminated: vec![None; count],
+            matches: Vec::new(),
+            round: 0
+        }
+    }
+
+    pub fn get_format(&self) -> BracketFormat {
+        self.format
+    }
+
+    pub fn get_names(&self) -> &Vec<String> {
+        &self.names
+    }
+
+    pub fn get_matches(&self) -> &Vec<BracketMatch> {
+        &self.matches
+    }
+
+    pub fn get_round(&self) -> u32 {
+        self.round
+    }
+
+    pub fn get_points(&self, entrant: usize) -> f32 {
+        self.points[entrant]
+    }
+
+    pub fn get_losses(&self, entrant: usize) -> u32 {
+        self.losses[entrant]
+    }
+
+    fn active(&self) -> Vec<usize> {
+        let lives = self.format.lives();
+        let mut active: Vec<usize> = (0..self.names.len()).filter(|x| self.losses[*x] < lives).collect();
+        active.sort_by_key(|x| self.seeds[*x]);
+        active
+    }
+
+    pub fn is_finished(&self) -> bool {
+        if self.matches.iter().any(|x| x.is_pending()) {
+            return false;
+        }
+        match self.format {
+            BracketFormat::Swiss { rounds } => self.round >= rounds || self.names.len() < 2,
+            _ => self.active().len() <= 1
+        }
+    }
+
+    pub fn champion(&self) -> Option<usize> {
+        if !self.is_finished() {
+            return None;
+        }
+        self.ranking().first().copied()
+    }
+
+    pub fn next_match(&mut self) -> Option<usize> {
+        // Index of the next match to play, starts a new round once the current one is done
+        loop {
+            if let Some(index) = self.matches.iter().position(|x| x.is_pending()) {
+                return Some(index);
+            }
+            if self.is_finished() {
+                return None;
+            }
+            self.round += 1;
+            match self.format {
+                BracketFormat::Swiss { .. } => self.pair_swiss(),
+                _ => self.pair_elimination()
+            }
+        }
+    }
+
+    fn add_match(&mut self, tier: u32, is_final: bool, entrants: Vec<usize>) {
+        self.matches.push(BracketMatch { round: self.round, tier, is_final, entrants, winner: None, draw: false, bye: false, replays: 0, by_seed: false });
+    }
+
+    fn add_bye(&mut self, tier: u32, entrant: usize) {
+        self.byes[entrant] += 1;
+        self.matches.push(BracketMatch { round: self.round, tier, is_final: false, entrants: vec![entrant], winner: Some(entrant), draw: false, bye: true, replays: 0, by_seed: false });
+    }
+
+    fn have_played(&self, a: usize, b: usize) -> bool {
+        self.matches.iter().any(|x| !x.bye && x.entrants.contains(&a) && x.entrants.contains(&b))
+    }
+
+    fn pair_swiss(&mut self) {
+        let mut order = self.ranking();
+
+        // The lowest ranked entrant with the fewest byes sits out and scores a win
+        if order.len() % 2 == 1 {
+            let fewest = order.iter().map(|x| self.byes[*x]).min().unwrap_or(0);
+            let position = order.iter().rposition(|x| self.byes[*x] == fewest).unwrap();
+            let entrant = order.remove(position);
+            self.points[entrant] += 1.;
+            self.add_bye(0, entrant);
+        }
+
+        // Neighbours in the ranking play each other, rematches only if there is nobody else left
+        while !order.is_empty() {
+            let entrant = order.remove(0);
+            let opponent = order.iter().position(|x| !self.have_played(entrant, *x)).unwrap_or(0);
+            let opponent = order.remove(opponent);
+            self.add_match(0, false, vec![entrant, opponent]);
+        }
+    }
+
+    fn pair_elimination(&mut self) {
+        let active = self.active();
+        let tiers: Vec<Vec<usize>> = (0..self.format.lives())
+            .map(|tier| active.iter().copied().filter(|x| self.losses[*x] == tier).collect())
+            .collect();
+
+        // Once every tier is down to one entrant they meet in the final
+        if tiers.iter().all(|x| x.len() <= 1) {
+            self.add_match(self.losses[active[0]], true, active);
+            return;
+        }
+
+        // Entrants alone in their tier wait until the other tiers caught up
+        for (tier, mut entrants) in tiers.into_iter().enumerate() {
+            if entrants.len() < 2 {
+                continue;
+            }
+            if entrants.len() % 2 == 1 {
+                let fewest = entrants.iter().map(|x| self.byes[*x]).min().unwrap_or(0);
+                let position = entrants.iter().position(|x| self.byes[*x] == fewest).unwrap();
+                let entrant = entrants.remove(position);
+                self.add_bye(tier as u32, entrant);
+            }
+            // Best remaining seed against the worst
+            while entrants.len() >= 2 {
+                let best = entrants.remove(0);
+                let worst = entrants.pop().unwrap();
+                self.add_match(tier as u32, false, vec![best, worst]);
+            }
+        }
+    }
+
+    pub fn record(&mut self, index: usize, result: &MatchResult) -> bool {
+        // Returns false if the match has to be replayed because an elimination match ended without a winner.
+        // After MAX_REPLAYS replays the best seed of the snakes that shared the first place wins.
+        let entrants = self.matches[index].entrants.clone();
+        for standing in &result.standings {
+            if let Some(entrant) = entrants.get(standing.id as usize) {
+                self.names[*entrant] = standing.name.clone();
+            }
+        }
+
+        let first: Vec<usize> = result.standings.iter()
+            .filter(|x| x.placement == 1)
+            .filter_map(|x| entrants.get(x.id as usize).copied())
+            .collect();
+        let mut winner = if first.len() == 1 { Some(first[0]) } else { None };
+        if winner.is_none() && !matches!(self.format, BracketFormat::Swiss { .. }) {
+            if self.matches[index].replays < MAX_REPLAYS {
+                self.matches[index].replays += 1;
+                return false;
+            }
+            let candidates = if first.is_empty() { &entrants } else { &first };
+            winner = candidates.iter().min_by_key(|x| self.seeds[**x]).copied();
+            self.matches[index].by_seed = true;
+        }
+
+        match (self.format, winner) {
+            (BracketFormat::Swiss { .. }, Some(winner)) => {
+                self.points[winner] += 1.;
+                entrants.iter().filter(|x| **x != winner).for_each(|x| self.losses[*x] += 1);
+                self.matches[index].winner = Some(winner);
+            },
+            (BracketFormat::Swiss { .. }, None) => {
+                first.iter().for_each(|x| self.points[*x] += 0.5);
+                entrants.iter().filter(|x| !first.contains(x)).for_each(|x| self.losses[*x] += 1);
+                self.matches[index].draw = true;
+            },
+            (_, Some(winner)) => {
+                let lives = self.format.lives();
+                for entrant in entrants.iter().filter(|x| **x != winner) {
+                    self.losses[*entrant] += 1;
+                    if self.losses[*entrant] >= lives {
+                        self.eliminated[*entrant] = Some(self.round);
+                    }
+                }
+                self.matches[index].winner = Some(winner);
+            },
+            (_, None) => return false // Only possible without entrants
+        }
+        true
+    }
+
+    pub fn ranking(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.names.len()).collect();
+        match self.format {
+            BracketFormat::Swiss { .. } => {
+                // Points, then the points of the opponents (Buchholz), then the seed
+                let buchholz: Vec<f32> = order.iter().map(|entrant| self.matches.iter()
+                    .filter(|x| !x.bye && x.entrants.contains(entrant))
+                    .flat_map(|x| x.entrants.iter().filter(|y| *y != entrant))
+                    .map(|x| self.points[*x])
+                    .sum()
+                ).collect();
+                order.sort_by(|a, b| self.points[*b].total_cmp(&self.points[*a])
+                    .then(buchholz[*b].total_cmp(&buchholz[*a]))
+                    .then(self.seeds[*a].cmp(&self.seeds[*b])));
+            },
+            _ => {
+                // Still in the bracket first, then whoever got knocked out last
+                order.sort_by(|a, b| self.eliminated[*b].unwrap_or(u32::MAX).cmp(&self.eliminated[*a].unwrap_or(u32::MAX))
+                    .then(self.losses[*a].cmp(&self.losses[*b]))
+                    .then(self.seeds[*a].cmp(&self.seeds[*b])));
+            }
+        }
+        order
+    }
+
+    pub fn describe_match(&self, bracket_match: &BracketMatch) -> String {
+        let names: Vec<String> = bracket_match.entrants.iter().map(|x| self.names[*x].clone()).collect();
+        if bracket_match.bye {
+            return format!("{} (bye)", names[0]);
+        }
+        let outcome = match (bracket_match.winner, bracket_match.draw) {
+            (Some(winner), _) if bracket_match.by_seed => format!(" - {} advanced by seed after {} games without a winner", self.names[winner], bracket_match.replays + 1),
+            (Some(winner), _) => format!(" - {} won", self.names[winner]),
+            (None, true) => " - draw".to_string(),
+            _ => String::new()
+        };
+        format!("{}{}", names.join(" vs "), outcome)
+    }
+
+    pub fn format_results(&self) -> String {
+        let mut text = String::new();
+        for round in 1..=self.round {
+            let _ = writeln!(text, "Round {}", round);
+            for bracket_match in self.matches.iter().filter(|x| x.round == round) {
+                let _ = writeln!(text, "  {}", self.describe_match(bracket_match));
+            }
+        }
+        let _ = writeln!(text, "Final ranking ({})", self.format.label());
+        for (i, entrant) in self.ranking().iter().enumerate() {
+            let detail = match self.format {
+                BracketFormat::Swiss { .. } => format!("{} points", self.points[*entrant]),
+                _ => format!("{} losses", self.losses[*entrant])
+            };
+            let _ = writeln!(text, "{:>3}  {}  ({})", i + 1, self.names[*entrant], detail);
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::base_snake::{match_result::Standing, rating::Rating};
+
+    use super::*;
+
+    fn names(count: usize) -> Vec<String> {
+        (0..count).map(|x| format!("Bot {}", x)).collect()
+    }
+
+    fn result(entrants: usize, placements: &[u32]) -> MatchResult {
+        // The snake ids are the positions in the match
+        let standings: Vec<Standing> = (0..entrants).map(|id| Standing {
+            placement: placements[id],
+            id: id as i32,
+            name: format!("Snake {}", id),
+            size: 1,
+            alive: false,
+            death_tick: None,
+            death_cause: None,
+            missed_inputs: 0,
+            average_latency: None
+        }).collect();
+        let first: Vec<i32> = standings.iter().filter(|x| x.placement == 1).map(|x| x.id).collect();
+        MatchResult { seed: 0, ticks: 1, duration: 0., winner: if first.len() == 1 { Some(first[0]) } else { None }, standings }
+    }
+
+    fn win_for(bracket: &Bracket, index: usize, entrant: usize) -> MatchResult {
+        let entrants = &bracket.get_matches()[index].entrants;
+        let placements: Vec<u32> = entrants.iter().map(|x| if *x == entrant { 1 } else { 2 }).collect();
+        result(entrants.len(), &placements)
+    }
+
+    fn play_out(bracket: &mut Bracket, mut winner: impl FnMut(&[usize]) -> usize) -> usize {
+        // Plays every match, returns the number of matches played
+        let mut played = 0;
+        while let Some(index) = bracket.next_match() {
+            let entrant = winner(&bracket.get_matches()[index].entrants);
+            let result = win_for(bracket, index, entrant);
+            assert!(bracket.record(index, &result));
+            played += 1;
+            assert!(played < 100, "The bracket does not finish");
+        }
+        played
+    }
+
+    fn pairings(bracket: &Bracket, round: u32) -> Vec<Vec<usize>> {
+        bracket.get_matches().iter().filter(|x| x.round == round && !x.bye).map(|x| x.entrants.clone()).collect()
+    }
+
+    #[test]
+    fn single_elimination_pairs_best_against_worst_seed() {
+        let mut bracket = Bracket::new(BracketFormat::SingleElimination, names(4), None);
+        bracket.next_match();
+        assert_eq!(pairings(&bracket, 1), vec![vec![0, 3], vec![1, 2]]);
+    }
+
+    #[test]
+    fn seeds_follow_the_ratings() {
+        let mut ratings = RatingTable::default();
+        ratings.update(&result(2, &[2, 1]).standings, &["Snake 0".into(), "Snake 1".into()]); // "Snake 1" beats "Snake 0"
+        let mut bracket = Bracket::new(BracketFormat::SingleElimination, vec!["Snake 0".into(), "Snake 1".into(), "New".into(), "Snake 2".into()], Some(&ratings));
+        bracket.next_match();
+        assert!(ratings.get("Snake 1").rating > Rating::default().rating);
+        // Snake 1 is the best seed and Snake 0 the worst, unrated entrants keep their entry order
+        assert_eq!(pairings(&bracket, 1), vec![vec![1, 0], vec![2, 3]]);
+    }
+
+    #[test]
+    fn single_elimination_finds_a_champion() {
+        let mut bracket = Bracket::new(BracketFormat::SingleElimination, names(8), None);
+        // The lower index always wins
+        let played = play_out(&mut bracket, |x| *x.iter().min().unwrap());
+        assert_eq!(played, 7);
+        assert_eq!(bracket.champion(), Some(0));
+        assert_eq!(bracket.get_round(), 3);
+        assert_eq!(bracket.ranking()[0], 0);
+        assert!((1..8).all(|x| bracket.get_losses(x) == 1));
+    }
+
+    #[test]
+    fn odd_entrant_counts_get_byes() {
+        let mut bracket = Bracket::new(BracketFormat::SingleElimination, names(5), None);
+        bracket.next_match();
+        let byes: Vec<&BracketMatch> = bracket.get_matches().iter().filter(|x| x.bye).collect();
+        assert_eq!(byes.len(), 1);
+        assert_eq!(byes[0].winner, Some(byes[0].entrants[0]));
+        play_out(&mut bracket, |x| *x.iter().max().unwrap());
+        assert_eq!(bracket.champion(), Some(4));
+    }
+
+    #[test]
+    fn double_elimination_needs_two_losses() {
+        let mut bracket = Bracket::new(BracketFormat::DoubleElimination, names(4), None);
+        // The best seed loses its first match and still wins the tournament from the losers bracket
+        let mut first = true;
+        play_out(&mut bracket, |x| {
+            if first && x.contains(&0) {
+                first = false;
+                return *x.iter().find(|y| **y != 0).unwrap();
+            }
+            *x.iter().min().unwrap()
+        });
+        assert_eq!(bracket.champion(), Some(0));
+        assert_eq!(bracket.get_losses(0), 1);
+        assert!((1..4).all(|x| bracket.get_losses(x) == 2));
+        assert!(bracket.get_matches().iter().any(|x| x.is_final));
+    }
+
+    #[test]
+    fn swiss_avoids_rematches() {
+        let mut bracket = Bracket::new(BracketFormat::swiss(4), names(4), None);
+        assert_eq!(bracket.get_format(), BracketFormat::Swiss { rounds: 2 });
+        play_out(&mut bracket, |x| *x.iter().min().unwrap());
+        let first: Vec<Vec<usize>> = pairings(&bracket, 1);
+        for pair in pairings(&bracket, 2) {
+            assert!(!first.iter().any(|x| x.contains(&pair[0]) && x.contains(&pair[1])));
+        }
+        assert_eq!(bracket.champion(), Some(0));
+        assert_eq!(bracket.get_points(0), 2.);
+    }
+
+    #[test]
+    fn swiss_draws_share_points() {
+        let mut bracket = Bracket::new(BracketFormat::swiss(2), names(2), None);
+        let index = bracket.next_match().unwrap();
+        assert!(bracket.record(index, &result(2, &[1, 1])));
+        assert!(bracket.get_matches()[index].draw);
+        assert_eq!((bracket.get_points(0), bracket.get_points(1)), (0.5, 0.5));
+        assert!(bracket.is_finished());
+    }
+
+    #[test]
+    fn elimination_draws_are_replayed_then_decided_by_seed() {
+        let mut bracket = Bracket::new(BracketFormat::SingleElimination, names(2), None);
+        let index = bracket.next_match().unwrap();
+        for replay in 1..=MAX_REPLAYS {
+            assert!(!bracket.record(index, &result(2, &[1, 1])));
+            assert_eq!(bracket.get_matches()[index].replays, replay);
+            assert_eq!(bracket.next_match(), Some(index));
+        }
+        assert!(bracket.record(index, &result(2, &[1, 1])));
+        let decided = &bracket.get_matches()[index];
+        assert_eq!(decided.winner, Some(0));
+        assert!(decided.by_seed);
+        assert_eq!(bracket.champion(), Some(0));
+    }
+
+    #[test]
+    fn bracket_takes_the_names_of_the_connected_bots() {
+        let mut bracket = Bracket::new(BracketFormat::SingleElimination, names(2), None);
+        let index = bracket.next_match().unwrap();
+        bracket.record(index, &result(2, &[1, 2]));
+        assert_eq!(bracket.get_names(), &vec!["Snake 0".to_string(), "Snake 1".to_string()]);
+    }
+}
@@ -0,0 +1,372 @@
+use std::{path::PathBuf, str::FromStr};
+
+use super::{bracket::BracketFormat, config::{Config, PlayerKind}, consts, rules::GameRules, snake::SnakeController, snake_controller::bot_process::{split_command, BotCommand}, tournament::check_group_size};
+
+pub const USAGE: &str = "Usage: multisnake [options]
+Without options the lobby is opened. Defaults are read from multisnake.toml.
+
+Match:
+  --grid <width>x<height>   Grid size
+  --players <list>          Comma separated list of ai, tcp:<port>, ws:<port> and key profiles
+                            like arrows and wasd, the roster of the config file if not given
+  --ai <n>                  Adds n ai players
+  --bot <command>           Adds an ai player whose bot is started by the game, {slot} and
+                            {socket} are replaced, otherwise the slot is appended, quoted
+                            arguments may contain spaces
+  --tcp <port>              Adds an ai player whose bot connects over the network to the port
+  --websocket <port>        Adds an ai player whose bot connects with a websocket, e.g. from a browser
+  --restarts <n>            Crashes a started bot may have before it is disqualified (default 3)
+  --tick <seconds>          Time between two ticks
+  --time-limit <seconds>    Time until the longest alive snake wins
+  --seed <n>                Seed of the first game, the following games count up
+  --games <n>               Number of games to play (default: until closed, 1 when headless)
+  --sandbox                 Keep playing after all snakes died
+  --headless                Play without a window, only ai players
+  --output <dir>            Directory for replays, exports and statistics
+  --no-replays              Do not record replays
+
+Tournament:
+  --tournament [<n>]        Play a tournament, n adds n ai players
+  --format <format>         round-robin (headless only), swiss, single or double
+  --rounds <n>              Rounds of a swiss tournament
+  --group-size <n>          Snakes per round robin game (default 2 or the config file)
+
+Other:
+  --replay <file>           Watch a replay
+  --verify-replay <files>   Re-simulate replays and compare them
+  --help                    Show this message";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TournamentFormat {
+    RoundRobin,
+    Bracket(BracketFormat)
+}
+
+#[derive(Debug, Clone)]
+pub struct CliOptions {
+    pub grid_size: (i32, i32),
+    pub players: Vec<PlayerKind>,
+    pub rules: GameRules,
+    pub seed: Option<u64>,
+    pub games: Option<u32>,
+    pub sandbox: bool,
+    pub headless: bool,
+    pub output: Option<PathBuf>,
+    pub record_replays: bool,
+    pub tournament: Option<TournamentFormat>,
+    pub group_size: usize
+}
+
+#[derive(Debug, Clone)]
+pub enum Command {
+    Lobby,
+    Play(CliOptions),
+    Headless(CliOptions),
+    WatchReplay(String),
+    VerifyReplays(Vec<String>),
+    Help
+}
+
+impl CliOptions {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            grid_size: config.grid_size(),
+            players: Vec::new(),
+            rules: config.rules.clone(),
+            seed: None,
+            games: None,
+            sandbox: false,
+            headless: false,
+            output: None,
+            record_replays: true,
+            tournament: None,
+            group_size: config.tournament.group_size
+        }
+    }
+
+    pub fn replay_dir(&self) -> Option<PathBuf> {
+        if !self.record_replays || self.sandbox {
+            return None;
+        }
+        Some(match &self.output {
+            Some(output) => output.join(consts::REPLAY_DIR),
+            None => PathBuf::from(consts::REPLAY_DIR)
+        })
+    }
+
+    pub fn export_dir(&self) -> PathBuf {
+        self.output.clone().unwrap_or(PathBuf::from(consts::EXPORT_DIR))
+    }
+
+    pub fn create_controllers(&self, config: &Config) -> Vec<Box<dyn SnakeController>> {
+        // The key profiles were checked while parsing
+        config.create_controllers(&self.players).unwrap_or_default()
+    }
+}
+
+fn parse_value<T: FromStr>(args: &[String], index: usize) -> Result<T, String> {
+    let value = args.get(index + 1).ok_or(format!("Missing value for {}", args[index]))?;
+    value.parse().map_err(|_| format!("Invalid value {} for {}", value, args[index]))
+}
+
+fn parse_grid_size(value: &str) -> Result<(i32, i32), String> {
+    let (width, height) = value.split_once('x').ok_or(format!("Invalid grid size {}, expected <width>x<height>", value))?;
+    match (width.parse::<i32>(), height.parse::<i32>()) {
+        (Ok(width), Ok(height)) if width > 1 && height > 1 => Ok((width, height)),
+        _ => Err(format!("Invalid grid size {}", value))
+    }
+}
+
+pub fn parse_args(args: &[String], config: &Config) -> Result<Command, String> {
+    // Arguments without the program name
+    if args.is_empty() {
+        return Ok(Command::Lobby);
+    }
+
+    let mut options = CliOptions::new(config);
+    let mut format = None;
+    let mut rounds = None;
+    let mut restarts = None;
+    let mut index = 0;
+    while index < args.len() {
+        match args[index].as_str() {
+            "--help" | "-h" => return Ok(Command::Help),
+            "--verify-replay" => {
+                if args.len() <= index + 1 {
+                    return Err("Missing replay files for --verify-replay".to_string());
+                }
+                return Ok(Command::VerifyReplays(args[index + 1..].to_vec()));
+            },
+            "--replay" => return Ok(Command::WatchReplay(parse_value(args, index)?)),
+            "--grid" => {
+                options.grid_size = parse_grid_size(&parse_value::<String>(args, index)?)?;
+                index += 1;
+            },
+            "--players" => {
+                for player in parse_value::<String>(args, index)?.split(',') {
+                    options.players.push(player.parse()?);
+                }
+                index += 1;
+            },
+            "--ai" => {
+                let count: usize = parse_value(args, index)?;
+                options.players.extend(std::iter::repeat_n(PlayerKind::Ai, count));
+                index += 1;
+            },
+            "--bot" => {
+                let command = parse_value::<String>(args, index)?;
+                split_command(&command)?;
+                options.players.push(PlayerKind::Bot(BotCommand::new(command)));
+                index += 1;
+            },
+            "--tcp" => {
+                options.players.push(PlayerKind::Tcp(parse_value(args, index)?));
+                index += 1;
+            },
+            "--websocket" => {
+                options.players.push(PlayerKind::WebSocket(parse_value(args, index)?));
+                index += 1;
+            },
+            "--restarts" => {
+                restarts = Some(parse_value(args, index)?);
+                index += 1;
+            },
+            "--tick" => {
+                options.rules.tick_duration = parse_value(args, index)?;
+                index += 1;
+            },
+            "--time-limit" => {
+                options.rules.time_limit = parse_value(args, index)?;
+                index += 1;
+            },
+            "--seed" => {
+                options.seed = Some(parse_value(args, index)?);
+                index += 1;
+            },
+            "--games" => {
+                options.games = Some(parse_value(args, index)?);
+                index += 1;
+            },
+            "--output" => {
+                options.output = Some(PathBuf::from(parse_value::<String>(args, index)?));
+                index += 1;
+            },
+            "--sandbox" => options.sandbox = true,
+            "--headless" => options.headless = true,
+            "--no-replays" => options.record_replays = false,
+            "--tournament" => {
+                options.tournament = Some(TournamentFormat::RoundRobin);
+                // Optional number of ai players
+                if let Some(Ok(count)) = args.get(index + 1).map(|x| x.parse::<usize>()) {
+                    options.players.extend(std::iter::repeat_n(PlayerKind::Ai, count));
+                    index += 1;
+                }
+            },
+            "--format" => {
+                format = Some(parse_value::<String>(args, index)?);
+                index += 1;
+            },
+            "--rounds" => {
+                rounds = Some(parse_value(args, index)?);
+                index += 1;
+            },
+            "--group-size" => {
+                options.group_size = parse_value(args, index)?;
+                index += 1;
+            },
+            other => return Err(format!("Unknown argument {}", other))
+        }
+        index += 1;
+    }
+
+    if let Some(format) = format {
+        options.tournament = Some(match format.as_str() {
+            "round-robin" => TournamentFormat::RoundRobin,
+            "swiss" => TournamentFormat::Bracket(match rounds {
+                Some(rounds) => BracketFormat::Swiss { rounds },
+                None => BracketFormat::swiss(options.players.len())
+            }),
+            "single" => TournamentFormat::Bracket(BracketFormat::SingleElimination),
+            "double" => TournamentFormat::Bracket(BracketFormat::DoubleElimination),
+            other => return Err(format!("Unknown tournament format {}", other))
+        });
+    }
+
+    if options.players.is_empty() {
+        options.players = config.players.clone();
+    }
+    if let Some(restarts) = restarts {
+        options.players.iter_mut().for_each(|x| if let PlayerKind::Bot(command) = x {
+            command.restarts = restarts;
+        });
+    }
+    for player in &options.players {
+        if let PlayerKind::Keyboard(profile) = player {
+            config.key_profile(profile)?;
+        }
+    }
+    if options.players.len() < 2 {
+        return Err("At least two players are needed".to_string());
+    }
+    options.rules.validate()?;
+    if options.tournament == Some(TournamentFormat::RoundRobin) {
+        check_group_size(options.group_size, options.players.len())?;
+    }
+    if options.headless || options.tournament == Some(TournamentFormat::RoundRobin) {
+        if options.players.iter().any(|x| !x.is_ai()) {
+            return Err("Headless games only support ai players".to_string());
+        }
+        if options.sandbox {
+            return Err("Sandbox games need a window".to_string());
+        }
+        return Ok(Command::Headless(options));
+    }
+    Ok(Command::Play(options))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Command, String> {
+        let args: Vec<String> = args.split_whitespace().map(|x| x.to_string()).collect();
+        parse_args(&args, &Config::default())
+    }
+
+    fn play(args: &str) -> CliOptions {
+        match parse(args) {
+            Ok(Command::Play(options)) => options,
+            other => panic!("Expected a match for {}, got {:?}", args, other)
+        }
+    }
+
+    fn headless(args: &str) -> CliOptions {
+        match parse(args) {
+            Ok(Command::Headless(options)) => options,
+            other => panic!("Expected a headless match for {}, got {:?}", args, other)
+        }
+    }
+
+    #[test]
+    fn no_arguments_open_the_lobby() {
+        assert!(matches!(parse(""), Ok(Command::Lobby)));
+        assert!(matches!(parse("--ai 2 --help"), Ok(Command::Help)));
+    }
+
+    #[test]
+    fn match_options() {
+        let options = play("--grid 30x20 --ai 2 --players arrows --seed 5 --games 3 --tick 0.05 --time-limit 60 --sandbox");
+        assert_eq!(options.grid_size, (30, 20));
+        assert_eq!(options.players, vec![PlayerKind::Ai, PlayerKind::Ai, PlayerKind::Keyboard("arrows".to_string())]);
+        assert_eq!((options.seed, options.games), (Some(5), Some(3)));
+        assert_eq!(options.rules, GameRules { tick_duration: 0.05, time_limit: 60 });
+        assert!(options.sandbox);
+        assert!(options.replay_dir().is_none()); // Sandbox games are not recorded
+    }
+
+    #[test]
+    fn player_list() {
+        let options = headless("--headless --players ai,tcp:9000,ws:9001 --bot bot.py --restarts 1");
+        assert_eq!(options.players[..3], [PlayerKind::Ai, PlayerKind::Tcp(9000), PlayerKind::WebSocket(9001)]);
+        assert!(matches!(&options.players[3], PlayerKind::Bot(command) if command.command == "bot.py" && command.restarts == 1));
+        assert!(parse("--players ai,unknown").is_err());
+        assert!(parse("--players ai,tcp:port").is_err());
+    }
+
+    #[test]
+    fn invalid_grid_sizes() {
+        assert!(parse("--ai 2 --grid 30").is_err());
+        assert!(parse("--ai 2 --grid 1x20").is_err());
+        assert!(parse("--ai 2 --grid ax20").is_err());
+    }
+
+    #[test]
+    fn tick_duration_has_to_be_a_positive_number() {
+        for tick in ["0", "-1", "NaN", "inf", "-inf", "fast"] {
+            assert!(parse(&format!("--ai 2 --tick {}", tick)).is_err(), "--tick {} was accepted", tick);
+        }
+        assert_eq!(play("--ai 2 --tick 0.01").rules.tick_duration, 0.01);
+    }
+
+    #[test]
+    fn group_size_has_to_fit_the_players() {
+        assert!(parse("--ai 3 --tournament --group-size 0").is_err());
+        assert!(parse("--ai 3 --tournament --group-size 1").is_err());
+        assert!(parse("--ai 3 --tournament --group-size 4").is_err());
+        assert_eq!(headless("--ai 3 --tournament --group-size 3").group_size, 3);
+    }
+
+    #[test]
+    fn tournament_formats() {
+        let options = headless("--tournament 4");
+        assert_eq!(options.players.len(), 4);
+        assert_eq!(options.tournament, Some(TournamentFormat::RoundRobin));
+
+        assert_eq!(play("--tournament 8 --format swiss").tournament, Some(TournamentFormat::Bracket(BracketFormat::Swiss { rounds: 3 })));
+        assert_eq!(play("--ai 4 --format swiss --rounds 5").tournament, Some(TournamentFormat::Bracket(BracketFormat::Swiss { rounds: 5 })));
+        assert_eq!(play("--ai 4 --format double").tournament, Some(TournamentFormat::Bracket(BracketFormat::DoubleElimination)));
+        assert!(parse("--ai 4 --format league").is_err());
+    }
+
+    #[test]
+    fn headless_games_only_take_ai_players() {
+        assert!(parse("--headless --players ai,arrows").is_err());
+        assert!(parse("--headless --ai 2 --sandbox").is_err());
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        assert!(parse("--ai 1").is_err());
+        assert!(parse("--ai").is_err());
+        assert!(parse("--ai two").is_err());
+        assert!(parse("--ai 2 --fast").is_err());
+        assert!(parse("--ai 2 --players ai,nokeys").is_err());
+    }
+
+    #[test]
+    fn replay_commands() {
+        assert!(matches!(parse("--replay a.msreplay"), Ok(Command::WatchReplay(file)) if file == "a.msreplay"));
+        assert!(matches!(parse("--verify-replay a b"), Ok(Command::VerifyReplays(files)) if files == ["a", "b"]));
+        assert!(parse("--verify-replay").is_err());
+    }
+}
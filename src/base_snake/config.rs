@@ -0,0 +1,384 @@
+use std::{collections::BTreeMap, fs, path::Path, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use super::{consts, locale::Language, rules::GameRules, snake::SnakeController, snake_controller::{ai_controller::{create_ai_controller, create_bot_controller}, bot_process::{split_command, BotCommand}, keyboard_controller::{KeyBindings, KeyboardController}, tcp_controller::TcpController, websocket_controller::WebSocketController}};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "PlayerEntry", into = "PlayerEntry")]
+pub enum PlayerKind {
+    Ai, // Bot started by hand
+    Bot(BotCommand), // Bot started by the game
+    Tcp(u16), // Bot connecting over the network to this port
+    WebSocket(u16), // Bot connecting with a websocket, e.g. from a browser
+    Keyboard(String) // Name of a key profile
+}
+
+// How a player is written in the config file, "ai", "tcp:<port>", "ws:<port>", a key profile or a table with the bot command
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+enum PlayerEntry {
+    Name(String),
+    Bot(BotCommand)
+}
+
+impl FromStr for PlayerKind {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "" => Err("Empty player type".to_string()),
+            "ai" => Ok(PlayerKind::Ai),
+            tcp if tcp.starts_with("tcp:") => tcp[4..].parse().map(PlayerKind::Tcp).map_err(|_| format!("Invalid port in {}", value.trim())),
+            ws if ws.starts_with("ws:") => ws[3..].parse().map(PlayerKind::WebSocket).map_err(|_| format!("Invalid port in {}", value.trim())),
+            profile => Ok(PlayerKind::Keyboard(profile.to_string()))
+        }
+    }
+}
+
+impl TryFrom<PlayerEntry> for PlayerKind {
+    type Error = String;
+
+    fn try_from(value: PlayerEntry) -> Result<Self, Self::Error> {
+        match value {
+            PlayerEntry::Name(name) => name.parse(),
+            PlayerEntry::Bot(command) => Ok(PlayerKind::Bot(command))
+        }
+    }
+}
+
+impl From<PlayerKind> for PlayerEntry {
+    fn from(value: PlayerKind) -> Self {
+        match value {
+            PlayerKind::Bot(command) => PlayerEntry::Bot(command),
+            other => PlayerEntry::Name(other.label())
+        }
+    }
+}
+
+impl PlayerKind {
+    pub fn label(&self) -> String {
+        match self {
+            PlayerKind::Ai => "ai".to_string(),
+            PlayerKind::Bot(command) => command.command.clone(),
+            PlayerKind::Tcp(port) => format!("tcp:{}", port),
+            PlayerKind::WebSocket(port) => format!("ws:{}", port),
+            PlayerKind::Keyboard(profile) => profile.clone()
+        }
+    }
+
+    pub fn is_ai(&self) -> bool {
+        // Controlled by a bot
+        !matches!(self, PlayerKind::Keyboard(_))
+    }
+
+    pub fn uses_ai_slot(&self) -> bool {
+        // Connects to the socket or pipe of an ai player slot
+        matches!(self, PlayerKind::Ai | PlayerKind::Bot(_))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GridConfig {
+    pub width: i32,
+    pub height: i32
+}
+
+impl Default for GridConfig {
+    fn default() -> Self {
+        Self { width: 20, height: 18 }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowConfig {
+    pub width: i32,
+    pub height: i32,
+    pub fullscreen: bool
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self { width: 1200, height: 600, fullscreen: false }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebSocketConfig {
+    pub bind_address: String, // Only this machine by default
+    pub allowed_origins: Vec<String>, // Websites that may connect besides local pages, e.g. "https://example.com"
+    pub allow_file_origins: bool // Pages opened from a file send the origin null, which any sandboxed page can send too
+}
+
+impl Default for WebSocketConfig {
+    fn default() -> Self {
+        Self { bind_address: consts::WEBSOCKET_BIND_ADDRESS.to_string(), allowed_origins: Vec::new(), allow_file_origins: false }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TournamentConfig {
+    pub group_size: usize // Snakes per round robin game, --group-size overrides it
+}
+
+impl Default for TournamentConfig {
+    fn default() -> Self {
+        Self { group_size: 2 }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub grid: GridConfig,
+    pub rules: GameRules,
+    pub window: WindowConfig,
+    pub websocket: WebSocketConfig,
+    pub tournament: TournamentConfig,
+    pub volume: f32, // 0 to 1
+    pub language: Language,
+    pub players: Vec<PlayerKind>, // Added to the lobby on start, bots with a command are started by the game
+    pub keys: BTreeMap<String, KeyBindings>, // Key profiles, the default ones always exist. Profiles saved in the lobby take precedence.
+    pub colors: Vec<(u8, u8, u8)>, // Snake colors in join order, random ones are added if there are more snakes
+    #[serde(skip)]
+    lobby_keys: BTreeMap<String, Option<KeyBindings>> // Profiles changed in the lobby, None if deleted there
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            grid: GridConfig::default(),
+            rules: GameRules::default(),
+            window: WindowConfig::default(),
+            websocket: WebSocketConfig::default(),
+            tournament: TournamentConfig::default(),
+            volume: 0.2,
+            language: Language::default(),
+            players: Vec::new(),
+            keys: BTreeMap::from([
+                ("arrows".to_string(), KeyBindings::arrows()),
+                ("wasd".to_string(), KeyBindings::wasd()),
+                ("ijkl".to_string(), KeyBindings::ijkl()),
+                ("numpad".to_string(), KeyBindings::numpad()),
+            ]),
+            colors: vec![(171, 2, 168), (0, 134, 119), (143, 0, 255), (0, 121, 241), (253, 249, 0), (0, 228, 48), (255, 161, 0)],
+            lobby_keys: BTreeMap::new()
+        }
+    }
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Self {
+        // A missing or broken file uses the defaults
+        let config = match fs::read_to_string(path) {
+            Ok(text) => Config::parse(&text).map_err(|e| format!("Invalid config file {}: {}", path.display(), e)),
+            Err(_) => Ok(Config::default())
+        };
+        let mut config = config.unwrap_or_else(|e| {
+            println!("[ERROR] {}", e);
+            Config::default()
+        });
+        config.load_key_profiles(Path::new(consts::KEY_PROFILES_FILE));
+        config
+    }
+
+    fn load_key_profiles(&mut self, path: &Path) {
+        // Only holds the profiles changed in the lobby, so edits of the other ones in the config file still apply
+        let Ok(json) = fs::read_to_string(path) else {
+            return;
+        };
+        match serde_json::from_str::<BTreeMap<String, Option<KeyBindings>>>(&json) {
+            Ok(profiles) => profiles.into_iter().for_each(|(name, keys)| match keys {
+                Some(keys) => self.set_key_profile(&name, keys),
+                None => self.delete_key_profile(&name)
+            }),
+            Err(e) => println!("[ERROR] Invalid key profile file {}: {}", path.display(), e),
+        }
+    }
+
+    pub fn save_key_profiles(&self) -> Result<(), String> {
+        self.write_key_profiles(Path::new(consts::KEY_PROFILES_FILE))
+    }
+
+    fn write_key_profiles(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(&self.lobby_keys).map_err(|e| format!("Unable to serialize key profiles: {}", e))?;
+        fs::write(path, json).map_err(|e| format!("Unable to write {}: {}", path.display(), e))
+    }
+
+    pub fn set_key_profile(&mut self, name: &str, keys: KeyBindings) {
+        let name = name.to_lowercase();
+        self.keys.insert(name.clone(), keys);
+        self.lobby_keys.insert(name, Some(keys));
+    }
+
+    pub fn delete_key_profile(&mut self, name: &str) {
+        // The default profiles can not be deleted
+        let name = name.to_lowercase();
+        if !Config::default().keys.contains_key(&name) {
+            self.keys.remove(&name);
+            self.lobby_keys.insert(name, None);
+        }
+    }
+
+    fn parse(text: &str) -> Result<Self, String> {
+        toml::from_str::<Config>(text).map_err(|e| e.to_string())?.validate()
+    }
+
+    fn validate(mut self) -> Result<Self, String> {
+        if self.grid.width < 2 || self.grid.height < 2 {
+            return Err(format!("Invalid grid size {}x{}", self.grid.width, self.grid.height));
+        }
+        if self.tournament.group_size < 2 {
+            return Err(format!("Group size has to be at least 2, got {}", self.tournament.group_size));
+        }
+        self.rules.validate()?;
+        self.volume = self.volume.clamp(0., 1.);
+
+        self.keys = self.keys.into_iter().map(|(name, keys)| (name.to_lowercase(), keys)).collect();
+        for (name, keys) in Config::default().keys {
+            self.keys.entry(name).or_insert(keys);
+        }
+        for player in &self.players {
+            match player {
+                PlayerKind::Keyboard(profile) => { self.key_profile(profile)?; },
+                PlayerKind::Bot(command) => { split_command(&command.command)?; },
+                _ => {}
+            }
+        }
+        Ok(self)
+    }
+
+    pub fn grid_size(&self) -> (i32, i32) {
+        (self.grid.width, self.grid.height)
+    }
+
+    pub fn key_profile(&self, name: &str) -> Result<&KeyBindings, String> {
+        self.keys.get(&name.to_lowercase()).ok_or(format!("Unknown key profile {}", name))
+    }
+
+    pub fn create_controllers(&self, players: &[PlayerKind]) -> Result<Vec<Box<dyn SnakeController>>, String> {
+        // Ai slots are numbered in the order the ai players were given
+        let mut ai_index = 0;
+        players.iter().map(|player| -> Result<Box<dyn SnakeController>, String> {
+            match player {
+                PlayerKind::Ai => {
+                    ai_index += 1;
+                    Ok(create_ai_controller(ai_index - 1))
+                },
+                PlayerKind::Bot(command) => {
+                    ai_index += 1;
+                    Ok(create_bot_controller(ai_index - 1, command))
+                },
+                PlayerKind::Tcp(port) => Ok(Box::new(TcpController::new(*port))),
+                PlayerKind::WebSocket(port) => Ok(Box::new(WebSocketController::new(*port, self.websocket.clone()))),
+                PlayerKind::Keyboard(profile) => Ok(Box::new(KeyboardController::new(self.key_profile(profile)?)))
+            }
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use macroquad::input::KeyCode;
+
+    use super::*;
+
+    #[test]
+    fn parses_every_player_entry() {
+        let config = Config::parse(r#"
+            players = ["ai", "tcp:9000", "WS:9001", "Arrows", { command = "python3 bot.py" }, { command = "./bot", restarts = 0 }]
+        "#).unwrap();
+        assert_eq!(config.players, vec![
+            PlayerKind::Ai,
+            PlayerKind::Tcp(9000),
+            PlayerKind::WebSocket(9001),
+            PlayerKind::Keyboard("arrows".to_string()),
+            PlayerKind::Bot(BotCommand::new("python3 bot.py".to_string())),
+            PlayerKind::Bot(BotCommand { command: "./bot".to_string(), restarts: 0 })
+        ]);
+        assert_eq!(BotCommand::new(String::new()).restarts, 3);
+        assert!(Config::parse(r#"players = ["tcp:abc"]"#).is_err());
+        assert!(Config::parse(r#"players = ["ws:70000"]"#).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_configs() {
+        assert!(Config::parse("[grid]\nwidth = 1").is_err());
+        assert!(Config::parse("[grid]\nheight = 0").is_err());
+        assert!(Config::parse("[tournament]\ngroup_size = 1").is_err());
+        assert!(Config::parse("[rules]\ntick_duration = 0.0").is_err());
+        assert!(Config::parse(r#"players = ["qwer"]"#).is_err());
+        assert!(Config::parse(r#"players = [{ command = "python3 'bot.py" }]"#).is_err());
+        assert_eq!(Config::parse("[tournament]\ngroup_size = 3").unwrap().tournament.group_size, 3);
+    }
+
+    #[test]
+    fn lowercases_key_profiles() {
+        let config = Config::parse(r#"
+            players = ["tfgh"]
+
+            [keys.TFGH]
+            up = "T"
+            down = "g"
+            left = "F"
+            right = "H"
+        "#).unwrap();
+        assert!(config.keys.contains_key("tfgh"));
+        assert!(!config.keys.contains_key("TFGH"));
+        let keys = config.key_profile("TfGh").unwrap();
+        assert_eq!((keys.up, keys.down, keys.left, keys.right), (KeyCode::T, KeyCode::G, KeyCode::F, KeyCode::H));
+    }
+
+    #[test]
+    fn missing_sections_use_the_defaults() {
+        let config = Config::parse("").unwrap();
+        assert_eq!(config.grid_size(), (20, 18));
+        assert_eq!(config.volume, 0.2);
+        assert_eq!(config.tournament.group_size, 2);
+        assert_eq!(config.websocket.bind_address, consts::WEBSOCKET_BIND_ADDRESS);
+        assert!(config.players.is_empty());
+        assert_eq!(config.keys.keys().collect::<Vec<_>>(), ["arrows", "ijkl", "numpad", "wasd"]);
+
+        let config = Config::parse("volume = 3.0\n[grid]\nwidth = 30").unwrap();
+        assert_eq!(config.grid_size(), (30, 18));
+        assert_eq!(config.volume, 1.);
+        assert_eq!(config.window.width, 1200);
+    }
+
+    #[test]
+    fn saved_profiles_only_override_lobby_changes() {
+        let toml = r#"
+            [keys.tfgh]
+            up = "T"
+            down = "G"
+            left = "F"
+            right = "H"
+
+            [keys.zxcv]
+            up = "Z"
+            down = "X"
+            left = "C"
+            right = "V"
+        "#;
+        let path = std::env::temp_dir().join(format!("multisnake-key-profiles-test-{}.json", std::process::id()));
+        let mut lobby = Config::parse(toml).unwrap();
+        lobby.set_key_profile("WASD", KeyBindings::ijkl());
+        lobby.delete_key_profile("zxcv");
+        lobby.delete_key_profile("arrows");
+        lobby.write_key_profiles(&path).unwrap();
+
+        // The config file changed tfgh after the lobby saved its profiles
+        let mut config = Config::parse(&toml.replace(r#"up = "T""#, r#"up = "R""#)).unwrap();
+        config.load_key_profiles(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(config.key_profile("tfgh").unwrap().up, KeyCode::R);
+        assert_eq!(config.key_profile("wasd").unwrap(), &KeyBindings::ijkl());
+        assert!(config.key_profile("zxcv").is_err());
+        assert_eq!(config.key_profile("arrows").unwrap(), &KeyBindings::arrows());
+        assert_eq!(config.lobby_keys, BTreeMap::from([("wasd".to_string(), Some(KeyBindings::ijkl())), ("zxcv".to_string(), None)]));
+    }
+}
@@ -1,2 +1,13 @@
 pub const VERSION: &str = "Beta-0.4";
-pub const GRID_SIZE: (&str, &str) = ("20", "18");
\ No newline at end of file
+pub const CONFIG_FILE: &str = "multisnake.toml";
+pub const REPLAY_DIR: &str = "replays";
+pub const EXPORT_DIR: &str = "exports";
+pub const SCOREBOARD_FILE: &str = "scoreboard.json";
+pub const RATINGS_FILE: &str = "ratings.json";
+pub const BOT_LOG_DIR: &str = "bot_logs";
+pub const KEY_PROFILES_FILE: &str = "key_profiles.json";
+pub const DEFAULT_BOT_PORT: u16 = 7878; // Of tcp and websocket bots added in the lobby
+pub const TCP_BIND_ADDRESS: &str = "0.0.0.0"; // Tcp bots may connect from other machines
+pub const BOT_CONNECT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30); // Of bots that connect over the network
+pub const BOT_HELLO_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5); // For the whole hello of a connected bot
+pub const WEBSOCKET_BIND_ADDRESS: &str = "127.0.0.1"; // Browsers on other machines are not trusted
\ No newline at end of file
@@ -0,0 +1,111 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{events::{DeathCause, GameEvent}, locale::{direction_name, tr, trf, Text}, snake::{ControllerLog, Direction}, snakegrid::SnakeGrid};
+
+const MAX_ENTRIES: usize = 500; // Per snake, older entries are dropped
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogKind {
+    Food,
+    NearMiss,
+    Direction,
+    Rejected,
+    Death,
+    Bot
+}
+
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub tick: u32,
+    pub kind: LogKind,
+    pub message: String
+}
+
+#[derive(Debug, Default)]
+pub struct EventLog {
+    entries: HashMap<i32, Vec<LogEntry>>,
+    directions: HashMap<i32, Direction>, // Input of the previous tick
+    near_misses: HashSet<(i32, i32)> // Pairs of snakes whose heads touched in the previous tick
+}
+
+impl EventLog {
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.directions.clear();
+        self.near_misses.clear();
+    }
+
+    pub fn get(&self, snake_id: i32) -> &[LogEntry] {
+        self.entries.get(&snake_id).map(|x| x.as_slice()).unwrap_or(&[])
+    }
+
+    fn push(&mut self, snake_id: i32, tick: u32, kind: LogKind, message: String) {
+        let entries = self.entries.entry(snake_id).or_default();
+        entries.push(LogEntry { tick, kind, message });
+        if entries.len() > MAX_ENTRIES {
+            entries.remove(0);
+        }
+    }
+
+    pub fn record_tick(&mut self, game_grid: &mut SnakeGrid) {
+        // Called after every tick, also drains the log lines of the controllers
+        let tick = game_grid.get_tick_count();
+        let snakes = game_grid.get_all_snake_refs();
+        let name = |id: i32| snakes.iter().find(|x| x.id == id).map(|x| x.name.clone()).unwrap_or_default();
+
+        for (snake, direction) in snakes.iter().zip(game_grid.get_last_inputs().clone()) {
+            if !snake.alive {
+                continue;
+            }
+            if let Some(previous) = self.directions.insert(snake.id, direction) {
+                if previous != direction {
+                    self.push(snake.id, tick, LogKind::Direction, trf(Text::LogTurned, &[&direction_name(previous), &direction_name(direction)]));
+                }
+            }
+        }
+
+        for event in game_grid.get_last_events().clone() {
+            match event {
+                GameEvent::FoodEaten { snake_id, cell } => self.push(snake_id, tick, LogKind::Food, trf(Text::LogAteFood, &[&cell])),
+                GameEvent::Died { snake_id, cause } => {
+                    let message = match cause {
+                        DeathCause::Snake { id } if id == snake_id => tr(Text::LogRanIntoItself).to_string(),
+                        DeathCause::Snake { id } => trf(Text::LogRanInto, &[&name(id)]),
+                        cause => trf(Text::LogDied, &[&cause.name()])
+                    };
+                    self.push(snake_id, tick, LogKind::Death, message);
+                },
+                GameEvent::FoodPlaced { .. } => {}
+            }
+        }
+
+        // Heads next to each other, logged once when it starts
+        let width = game_grid.width;
+        let heads: Vec<(i32, (i32, i32))> = snakes.iter()
+            .filter(|x| x.alive && !x.tiles.is_empty())
+            .map(|x| (x.id, SnakeGrid::index_to_xy(x.tiles[0], width)))
+            .collect();
+        let mut near_misses = HashSet::new();
+        for (i, (a, (ax, ay))) in heads.iter().enumerate() {
+            for (b, (bx, by)) in heads.iter().skip(i + 1) {
+                if (ax - bx).abs() + (ay - by).abs() == 1 {
+                    near_misses.insert((*a, *b));
+                }
+            }
+        }
+        for (a, b) in near_misses.difference(&self.near_misses).copied().collect::<Vec<_>>() {
+            self.push(a, tick, LogKind::NearMiss, trf(Text::LogNearMiss, &[&name(b)]));
+            self.push(b, tick, LogKind::NearMiss, trf(Text::LogNearMiss, &[&name(a)]));
+        }
+        self.near_misses = near_misses;
+
+        for (snake_id, lines) in game_grid.take_controller_logs() {
+            for line in lines {
+                match line {
+                    ControllerLog::Rejected(message) => self.push(snake_id, tick, LogKind::Rejected, trf(Text::LogRejected, &[&message])),
+                    ControllerLog::Message(message) => self.push(snake_id, tick, LogKind::Bot, message),
+                }
+            }
+        }
+    }
+}
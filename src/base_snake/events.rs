@@ -0,0 +1,54 @@
+use serde::{Deserialize, Serialize};
+
+use super::locale::{tr, Text};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum DeathCause {
+    Wall,
+    Snake { id: i32 }, // Ran into the body of a snake (possibly itself)
+    HeadOn,
+    DeadSnake
+}
+
+impl DeathCause {
+    pub fn label(&self) -> &'static str {
+        match self {
+            DeathCause::Wall => "Wall",
+            DeathCause::Snake { .. } => "Snake",
+            DeathCause::HeadOn => "Head-on",
+            DeathCause::DeadSnake => "Dead Snake",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        // Translated label for the ui
+        tr(match self {
+            DeathCause::Wall => Text::DeathWall,
+            DeathCause::Snake { .. } => Text::DeathSnake,
+            DeathCause::HeadOn => Text::DeathHeadOn,
+            DeathCause::DeadSnake => Text::DeathDeadSnake,
+        })
+    }
+
+    pub fn name_of_label(label: &str) -> &str {
+        // Stats are keyed by the label, unknown labels are shown as they are
+        [DeathCause::Wall, DeathCause::Snake { id: 0 }, DeathCause::HeadOn, DeathCause::DeadSnake].iter()
+            .find(|x| x.label() == label)
+            .map(|x| x.name())
+            .unwrap_or(label)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum GameEvent {
+    FoodEaten { snake_id: i32, cell: i32 },
+    FoodPlaced { cell: i32 },
+    Died { snake_id: i32, cause: DeathCause }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeathRecord {
+    pub snake_id: i32,
+    pub tick: u32,
+    pub cause: DeathCause
+}
@@ -0,0 +1,91 @@
+use std::{fs::{self, File}, io::BufWriter, path::{Path, PathBuf}, time::Duration};
+
+use crate::base_snake::replay::Replay;
+
+use super::raster::{render_grid, Frame};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AnimationFormat {
+    Gif,
+    PngFrames // Numbered PNG files in a directory
+}
+
+impl AnimationFormat {
+    pub fn label(&self) -> &'static str {
+        match self {
+            AnimationFormat::Gif => "GIF",
+            AnimationFormat::PngFrames => "PNG Frames",
+        }
+    }
+}
+
+pub struct AnimationWriter {
+    path: PathBuf,
+    format: AnimationFormat,
+    frame_delay: Duration,
+    gif_encoder: Option<gif::Encoder<BufWriter<File>>>, // Created with the first frame, when the size is known
+    frame_count: usize
+}
+
+impl AnimationWriter {
+    pub fn create(path: &Path, format: AnimationFormat, frame_delay: Duration) -> Result<Self, String> {
+        let directory = match format {
+            AnimationFormat::Gif => path.parent().map(|x| x.to_path_buf()).unwrap_or_default(),
+            AnimationFormat::PngFrames => path.to_path_buf(),
+        };
+        fs::create_dir_all(&directory).map_err(|e| format!("Unable to create {}: {}", directory.display(), e))?;
+
+        Ok(Self { path: path.to_path_buf(), format, frame_delay, gif_encoder: None, frame_count: 0 })
+    }
+
+    pub fn add_frame(&mut self, frame: &Frame) -> Result<(), String> {
+        match self.format {
+            AnimationFormat::Gif => self.add_gif_frame(frame)?,
+            AnimationFormat::PngFrames => {
+                let path = self.path.join(format!("frame_{:05}.png", self.frame_count));
+                write_png(&path, frame)?;
+            }
+        }
+        self.frame_count += 1;
+        Ok(())
+    }
+
+    fn add_gif_frame(&mut self, frame: &Frame) -> Result<(), String> {
+        if self.gif_encoder.is_none() {
+            let file = File::create(&self.path).map_err(|e| format!("Unable to create {}: {}", self.path.display(), e))?;
+            let mut encoder = gif::Encoder::new(BufWriter::new(file), frame.width as u16, frame.height as u16, &[])
+                .map_err(|e| format!("Unable to write gif: {}", e))?;
+            encoder.set_repeat(gif::Repeat::Infinite).map_err(|e| format!("Unable to write gif: {}", e))?;
+            self.gif_encoder = Some(encoder);
+        }
+
+        let mut pixels = frame.pixels.clone();
+        let mut gif_frame = gif::Frame::from_rgba_speed(frame.width as u16, frame.height as u16, &mut pixels, 10);
+        gif_frame.delay = (self.frame_delay.as_millis() / 10) as u16; // In hundredths of a second
+        self.gif_encoder.as_mut().unwrap().write_frame(&gif_frame).map_err(|e| format!("Unable to write gif: {}", e))
+    }
+
+    pub fn finish(self) -> Result<(PathBuf, usize), String> {
+        // The gif trailer is written when the encoder is dropped
+        drop(self.gif_encoder);
+        Ok((self.path, self.frame_count))
+    }
+}
+
+pub fn write_png(path: &Path, frame: &Frame) -> Result<(), String> {
+    let file = File::create(path).map_err(|e| format!("Unable to create {}: {}", path.display(), e))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), frame.width, frame.height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()
+        .and_then(|mut writer| writer.write_image_data(&frame.pixels))
+        .map_err(|e| format!("Unable to write {}: {}", path.display(), e))
+}
+
+pub fn export_replay(replay: &Replay, path: &Path, format: AnimationFormat, total_square_size: u32) -> Result<(PathBuf, usize), String> {
+    let mut writer = AnimationWriter::create(path, format, Duration::from_secs_f32(replay.header.rules.tick_duration))?;
+    for (i, snapshot) in replay.simulate().iter().enumerate() {
+        writer.add_frame(&render_grid(snapshot, &replay.infos_after(i), total_square_size))?;
+    }
+    writer.finish()
+}
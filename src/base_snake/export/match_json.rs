@@ -0,0 +1,145 @@
+use std::{cell::Cell, fs, path::{Path, PathBuf}, rc::Rc, time::{SystemTime, UNIX_EPOCH}};
+
+use serde::{Deserialize, Serialize};
+
+use crate::base_snake::{events::GameEvent, match_result::{MatchResult, Standing}, object::Tile, replay::Replay, rules::GameRules, snake::SnakeRefData, snakegrid::SnakeGrid};
+
+pub const MATCH_EXPORT_VERSION: u32 = 1;
+
+// Tiles are exported as their protocol tile ids: 0 = Empty, -1 = Food, -2 = Dead snake, 10+ = Snake with id (tile-10)
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportedSnake {
+    pub id: i32,
+    pub name: String,
+    pub color: (u8, u8, u8)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportedTick {
+    pub tick: u32,
+    pub changes: Vec<(i32, i32)>, // (Cell index, new tile id) compared to the previous tick
+    pub snakes: Vec<SnakeRefData>,
+    pub events: Vec<GameEvent>
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatchExport {
+    pub version: u32,
+    pub seed: u64,
+    pub rules: GameRules,
+    pub width: i32,
+    pub height: i32,
+    pub snakes: Vec<ExportedSnake>,
+    pub initial_grid: Vec<i32>,
+    pub ticks: Vec<ExportedTick>,
+    pub ticks_played: u32,
+    pub duration: f32,
+    pub winner: Option<i32>,
+    pub standings: Vec<Standing>
+}
+
+pub struct MatchExporter {
+    export: MatchExport,
+    previous_grid: Vec<Tile>
+}
+
+impl MatchExporter {
+    pub fn new(game_grid: &SnakeGrid) -> Self {
+        // Has to be created after the game was started, like the ReplayRecorder
+        let grid = game_grid.clone_raw_grid();
+        let export = MatchExport {
+            version: MATCH_EXPORT_VERSION,
+            seed: game_grid.get_seed(),
+            rules: game_grid.rules.clone(),
+            width: game_grid.width,
+            height: game_grid.get_height(),
+            snakes: game_grid.get_all_snake_refs().into_iter().map(|x| ExportedSnake { id: x.id, name: x.name, color: x.color }).collect(),
+            initial_grid: grid.iter().map(|x| x.get_tile_id()).collect(),
+            ticks: Vec::new(),
+            ticks_played: 0,
+            duration: 0.,
+            winner: None,
+            standings: Vec::new()
+        };
+        Self { export, previous_grid: grid }
+    }
+
+    pub fn record_tick(&mut self, game_grid: &SnakeGrid) {
+        let grid = game_grid.clone_raw_grid();
+        let changes = grid.iter().zip(self.previous_grid.iter()).enumerate()
+            .filter(|(_, (new, old))| new != old)
+            .map(|(i, (new, _))| (i as i32, new.get_tile_id()))
+            .collect();
+
+        self.export.ticks.push(ExportedTick {
+            tick: game_grid.get_tick_count(),
+            changes,
+            snakes: game_grid.get_all_snake_refs(),
+            events: game_grid.get_last_events().clone()
+        });
+        self.previous_grid = grid;
+    }
+
+    pub fn finish(mut self, result: MatchResult) -> MatchExport {
+        self.export.ticks_played = result.ticks;
+        self.export.duration = result.duration;
+        self.export.winner = result.winner;
+        self.export.standings = result.standings;
+        self.export
+    }
+}
+
+impl MatchExport {
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Unable to create {}: {}", parent.display(), e))?;
+        }
+        let json = serde_json::to_string_pretty(self).map_err(|e| format!("Unable to serialize match: {}", e))?;
+        fs::write(path, json).map_err(|e| format!("Unable to write {}: {}", path.display(), e))
+    }
+}
+
+pub fn export_path(directory: &Path, seed: u64) -> PathBuf {
+    // Seeds are reused by --seed and bracket replays, so the name starts with the time and never overwrites an export
+    let created = SystemTime::now().duration_since(UNIX_EPOCH).map(|x| x.as_secs()).unwrap_or(0);
+    let mut path = directory.join(format!("match-{}-{}.json", created, seed));
+    let mut counter = 1;
+    while path.exists() {
+        counter += 1;
+        path = directory.join(format!("match-{}-{}-{}.json", created, seed, counter));
+    }
+    path
+}
+
+pub fn export_replay_json(replay: &Replay, path: &Path) -> Result<(), String> {
+    let cursor = Rc::new(Cell::new(0));
+    let mut controllers = replay.create_controllers(&cursor);
+    let mut game_grid = replay.create_grid(&mut controllers);
+
+    let mut exporter = MatchExporter::new(&game_grid);
+    for i in 0..replay.ticks.len() {
+        cursor.set(i);
+        game_grid.tick();
+        exporter.record_tick(&game_grid);
+    }
+    let duration = replay.ticks.len() as f32 * replay.header.rules.tick_duration;
+    exporter.finish(MatchResult::from_grid(&game_grid, duration)).save(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn export_paths_are_not_reused() {
+        let directory = std::env::temp_dir().join(format!("multisnake-match-test-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let first = export_path(&directory, 7);
+        fs::write(&first, "{}").unwrap();
+        let second = export_path(&directory, 7);
+        assert_ne!(first, second);
+        assert!(second.file_name().unwrap().to_string_lossy().starts_with("match-"));
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
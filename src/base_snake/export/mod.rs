@@ -0,0 +1,5 @@
+pub mod raster;
+pub mod animation;
+pub mod match_json;
+
+pub mod stats;
\ No newline at end of file
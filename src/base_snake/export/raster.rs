@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+
+use macroquad::color::{Color, BLACK, DARKGRAY, GRAY, RED};
+
+use crate::base_snake::{object::Tile, snake::PlayerInfo, snakegrid::{GRID_OFFSET_X, GRID_OFFSET_Y}, snapshot::GridSnapshot};
+
+pub const DEFAULT_SQUARE_SIZE: u32 = 20;
+
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8> // RGBA, row by row
+}
+
+fn to_rgb(color: Color) -> [u8; 3] {
+    [(color.r * 255.) as u8, (color.g * 255.) as u8, (color.b * 255.) as u8]
+}
+
+impl Frame {
+    pub fn new(width: u32, height: u32, background: Color) -> Self {
+        let [r, g, b] = to_rgb(background);
+        Self { width, height, pixels: [r, g, b, 255].repeat((width * height) as usize) }
+    }
+
+    pub fn fill_rect(&mut self, x: u32, y: u32, width: u32, height: u32, color: [u8; 3], alpha: u8) {
+        // Blends the color over the current content like macroquad does with transparent colors
+        for py in y..(y + height).min(self.height) {
+            for px in x..(x + width).min(self.width) {
+                let index = ((py * self.width + px) * 4) as usize;
+                for (channel, value) in color.iter().enumerate() {
+                    let old = self.pixels[index + channel] as u32;
+                    self.pixels[index + channel] = ((*value as u32 * alpha as u32 + old * (255 - alpha as u32)) / 255) as u8;
+                }
+            }
+        }
+    }
+}
+
+pub fn render_grid(snapshot: &GridSnapshot, infos: &HashMap<i32, PlayerInfo>, total_square_size: u32) -> Frame {
+    // Renders the grid the same way SnakeGrid::draw does, without needing a window
+    let square_size = (total_square_size as f32 * 0.7) as u32;
+    let (offset_x, offset_y) = (GRID_OFFSET_X as u32, GRID_OFFSET_Y as u32);
+    let mut frame = Frame::new(
+        snapshot.width as u32 * total_square_size + 2 * offset_x,
+        snapshot.height as u32 * total_square_size + 2 * offset_y,
+        BLACK
+    );
+    let snake_color = |id: i32| snapshot.snakes.iter().find(|x| x.id == id).map(|x| [x.color.0, x.color.1, x.color.2]);
+
+    for (i, tile) in snapshot.grid.iter().enumerate() {
+        let x = i as u32 % snapshot.width as u32;
+        let y = i as u32 / snapshot.width as u32;
+
+        let color = match tile {
+            Tile::Snake { id } => snake_color(*id).unwrap_or(to_rgb(GRAY)),
+            Tile::DeadSnake => to_rgb(GRAY),
+            Tile::EMPTY => to_rgb(DARKGRAY),
+            Tile::FOOD => to_rgb(RED),
+        };
+        frame.fill_rect(x * total_square_size + offset_x, y * total_square_size + offset_y, square_size, square_size, color, 255);
+    }
+
+    for snake in &snapshot.snakes {
+        let Some(info) = infos.get(&snake.id) else { continue };
+        let color = [snake.color.0, snake.color.1, snake.color.2];
+        for cell_index in &info.marked_cells {
+            let x = *cell_index as u32 % snapshot.width as u32;
+            let y = *cell_index as u32 / snapshot.width as u32;
+            frame.fill_rect(x * total_square_size + offset_x, y * total_square_size + offset_y, square_size, square_size, color, 40);
+        }
+    }
+    frame
+}
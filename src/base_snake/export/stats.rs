@@ -0,0 +1,150 @@
+use std::{fmt::Write, fs, path::{Path, PathBuf}, time::{SystemTime, UNIX_EPOCH}};
+
+use serde::Serialize;
+
+use crate::base_snake::{consts, events::DeathCause, match_result::MatchResult, stats::BotStats};
+
+const DEATH_CAUSES: [DeathCause; 4] = [DeathCause::Wall, DeathCause::Snake { id: 0 }, DeathCause::HeadOn, DeathCause::DeadSnake];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScoreRow {
+    pub name: String,
+    pub games: u32,
+    pub wins: u32,
+    pub rating: Option<f32>,
+    pub stats: BotStats
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StatsExport {
+    pub version: String,
+    pub created: u64,
+    pub scoreboard: Vec<ScoreRow>,
+    pub matches: Vec<MatchResult>
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map(|x| x.to_string()).unwrap_or_default()
+}
+
+impl StatsExport {
+    pub fn new(scoreboard: Vec<ScoreRow>, matches: Vec<MatchResult>) -> Self {
+        Self {
+            version: consts::VERSION.to_string(),
+            created: SystemTime::now().duration_since(UNIX_EPOCH).map(|x| x.as_secs()).unwrap_or(0),
+            scoreboard,
+            matches
+        }
+    }
+
+    pub fn scoreboard_csv(&self) -> String {
+        let mut csv = String::from("name,games,wins,rating,kills,deaths,food_eaten,average_length,max_length,average_survival_ticks,average_latency_ms,missed_inputs");
+        for cause in DEATH_CAUSES {
+            let _ = write!(csv, ",deaths_{}", cause.label().to_lowercase().replace([' ', '-'], "_"));
+        }
+        csv.push('\n');
+
+        for row in &self.scoreboard {
+            let stats = &row.stats;
+            let _ = write!(csv, "{},{},{},{},{},{},{},{:.2},{},{:.2},{},{}",
+                csv_field(&row.name), row.games, row.wins, optional(row.rating.map(|x| format!("{:.1}", x))),
+                stats.kills, stats.total_deaths(), stats.food_eaten, stats.average_length(), stats.max_length,
+                stats.average_survival(), optional(stats.average_latency().map(|x| format!("{:.2}", x))), stats.missed_inputs);
+            for cause in DEATH_CAUSES {
+                let _ = write!(csv, ",{}", stats.deaths.get(cause.label()).unwrap_or(&0));
+            }
+            csv.push('\n');
+        }
+        csv
+    }
+
+    pub fn matches_csv(&self) -> String {
+        // One line per snake and match
+        let mut csv = String::from("match,seed,ticks,duration,winner,placement,id,name,size,alive,death_tick,death_cause,missed_inputs,average_latency_ms\n");
+        for (i, result) in self.matches.iter().enumerate() {
+            let winner = result.winner.and_then(|id| result.standings.iter().find(|x| x.id == id)).map(|x| x.name.clone());
+            for standing in &result.standings {
+                let _ = writeln!(csv, "{},{},{},{:.2},{},{},{},{},{},{},{},{},{},{}",
+                    i + 1, result.seed, result.ticks, result.duration, csv_field(&winner.clone().unwrap_or_default()),
+                    standing.placement, standing.id, csv_field(&standing.name), standing.size, standing.alive,
+                    optional(standing.death_tick), optional(standing.death_cause.map(|x| x.label())),
+                    standing.missed_inputs, optional(standing.average_latency.map(|x| format!("{:.2}", x))));
+            }
+        }
+        csv
+    }
+
+    pub fn save(&self, directory: &Path, prefix: &str) -> Result<Vec<PathBuf>, String> {
+        // Writes <prefix>.json, <prefix>-scoreboard.csv and <prefix>-matches.csv
+        fs::create_dir_all(directory).map_err(|e| format!("Unable to create {}: {}", directory.display(), e))?;
+        let json = serde_json::to_string_pretty(self).map_err(|e| format!("Unable to serialize statistics: {}", e))?;
+
+        let files = [
+            (directory.join(format!("{}.json", prefix)), json),
+            (directory.join(format!("{}-scoreboard.csv", prefix)), self.scoreboard_csv()),
+            (directory.join(format!("{}-matches.csv", prefix)), self.matches_csv()),
+        ];
+        for (path, content) in &files {
+            fs::write(path, content).map_err(|e| format!("Unable to write {}: {}", path.display(), e))?;
+        }
+        Ok(files.into_iter().map(|x| x.0).collect())
+    }
+
+    pub fn save_session(&self, directory: &Path, prefix: &str) {
+        // Named after the creation time
+        match self.save(directory, &format!("{}-{}", prefix, self.created)) {
+            Ok(paths) => paths.iter().for_each(|x| println!("Exported statistics to {}", x.display())),
+            Err(e) => println!("[ERROR] {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::base_snake::match_result::Standing;
+
+    use super::*;
+
+    fn standing(id: i32, name: &str, placement: u32) -> Standing {
+        Standing { placement, id, name: name.to_string(), size: 3, alive: placement == 1, death_tick: None, death_cause: None, missed_inputs: 0, average_latency: None }
+    }
+
+    #[test]
+    fn csv_fields_are_quoted_when_needed() {
+        assert_eq!(csv_field("Bot"), "Bot");
+        assert_eq!(csv_field("Bot, the second"), "\"Bot, the second\"");
+        assert_eq!(csv_field("The \"Bot\""), "\"The \"\"Bot\"\"\"");
+        assert_eq!(csv_field("Line\nbreak"), "\"Line\nbreak\"");
+        assert_eq!(csv_field("Carriage\rreturn"), "\"Carriage\rreturn\"");
+        assert_eq!(csv_field(""), "");
+    }
+
+    #[test]
+    fn scoreboard_csv_has_a_column_for_every_header() {
+        let row = ScoreRow { name: "A, B".to_string(), games: 2, wins: 1, rating: Some(1512.34), stats: BotStats::default() };
+        let csv = StatsExport::new(vec![row], Vec::new()).scoreboard_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].starts_with("\"A, B\",2,1,1512.3,"));
+        // The quoted comma is not a separator
+        assert_eq!(lines[0].split(',').count(), lines[1].replace("\"A, B\"", "AB").split(',').count());
+    }
+
+    #[test]
+    fn matches_csv_has_a_line_per_snake() {
+        let result = MatchResult { seed: 7, ticks: 12, duration: 1.5, winner: Some(1), standings: vec![standing(1, "Quote \"Bot\"", 1), standing(0, "Plain", 2)] };
+        let csv = StatsExport::new(Vec::new(), vec![result]).matches_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[1], "1,7,12,1.50,\"Quote \"\"Bot\"\"\",1,1,\"Quote \"\"Bot\"\"\",3,true,,,0,");
+        assert!(lines[2].starts_with("1,7,12,1.50,\"Quote \"\"Bot\"\"\",2,0,Plain,3,false,"));
+    }
+}
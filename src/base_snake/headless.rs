@@ -0,0 +1,79 @@
+use std::{path::PathBuf, thread::sleep, time::{Duration, Instant}};
+
+use super::{match_result::MatchResult, replay::ReplayRecorder, rules::GameRules, snake::SnakeController, snake_controller::handshake::MatchInfo, snakegrid::SnakeGrid};
+
+#[derive(Debug, Clone)]
+pub struct MatchSettings {
+    pub grid_size: (i32, i32),
+    pub rules: GameRules,
+    pub seed: Option<u64>, // Random if None
+    pub replay_dir: Option<PathBuf>,
+    pub colors: Vec<(u8, u8, u8)>
+}
+
+pub fn play_headless_match(controllers: &mut [Box<dyn SnakeController>], settings: &MatchSettings) -> MatchResult {
+    // Plays a full match without a window, the same way the main loop does. Only works with ai controllers.
+    for (i, controller) in controllers.iter_mut().enumerate() {
+        // Snakes get their ids in this order
+        if !controller.connect(&MatchInfo { snake_id: i as i32, grid_size: settings.grid_size, rules: settings.rules.clone() }) {
+            println!("Unable to connect {}", controller.get_name());
+        }
+    }
+    sleep(Duration::from_secs_f32(0.5));
+
+    let result = {
+        let mut game_grid = SnakeGrid::new(settings.grid_size.0, settings.grid_size.1);
+        game_grid.rules = settings.rules.clone();
+        game_grid.set_colors(&settings.colors);
+        if let Some(seed) = settings.seed {
+            game_grid.reseed(seed);
+        }
+        controllers.iter_mut().for_each(|x| { game_grid.add_snake(x.as_mut()); });
+
+        game_grid.do_place_food();
+        game_grid.start_game();
+
+        let mut recorder = settings.replay_dir.as_ref().and_then(|dir| ReplayRecorder::create(dir, &game_grid)
+            .map_err(|e| println!("[ERROR] {}", e))
+            .ok());
+
+        let start_time = Instant::now();
+        loop {
+            game_grid.update_input();
+            game_grid.tick();
+
+            if let Some(Err(e)) = recorder.as_mut().map(|x| x.record_tick(&game_grid)) {
+                println!("[ERROR] {}", e);
+                recorder = None;
+            }
+
+            match game_grid.check_end() {
+                Err(1) if start_time.elapsed() < Duration::from_secs(settings.rules.time_limit) => {} // Ongoing
+                _ => break
+            }
+
+            game_grid.send_gamestate();
+            let now = Instant::now();
+            while now.elapsed() < Duration::from_secs_f32(settings.rules.tick_duration) {
+                game_grid.update_input(); // Read answers as they arrive, the direction is only used on the next tick
+                sleep(Duration::from_millis(1));
+            }
+        }
+
+        let result = MatchResult::from_grid(&game_grid, start_time.elapsed().as_secs_f32());
+        if let Some(recorder) = recorder {
+            if let Err(e) = recorder.finish(result.winner) {
+                println!("[ERROR] {}", e);
+            }
+        }
+        result
+    };
+
+    if let Some(winner) = result.winner {
+        controllers.iter_mut().for_each(|x| x.send_winner(winner));
+    }
+    sleep(Duration::from_secs_f32(0.3));
+    controllers.iter_mut().for_each(|x| x.disconnect());
+
+    result
+}
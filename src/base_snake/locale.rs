@@ -0,0 +1,464 @@
+use std::{fmt::Display, sync::atomic::{AtomicU8, Ordering}};
+
+use serde::{Deserialize, Serialize};
+
+use super::snake::Direction;
+
+// UI texts only, console output and exported files stay English
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum Language {
+    #[default]
+    #[serde(rename = "en")]
+    English,
+    #[serde(rename = "de")]
+    German
+}
+
+impl Language {
+    pub const ALL: [Language; 2] = [Language::English, Language::German];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Language::English => "English",
+            Language::German => "Deutsch",
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        // Passed to bots started by the game
+        match self {
+            Language::English => "en",
+            Language::German => "de",
+        }
+    }
+}
+
+static LANGUAGE: AtomicU8 = AtomicU8::new(0);
+
+pub fn set_language(language: Language) {
+    LANGUAGE.store(Language::ALL.iter().position(|x| *x == language).unwrap_or(0) as u8, Ordering::Relaxed);
+}
+
+pub fn get_language() -> Language {
+    Language::ALL.get(LANGUAGE.load(Ordering::Relaxed) as usize).copied().unwrap_or_default()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Text {
+    // Lobby
+    Connecting,
+    Connected,
+    ConnectionError,
+    LobbyAddPlayer,
+    LobbyAddAi,
+    LobbyStart,
+    Settings,
+    Language,
+    GridSize,
+    GridWidth,
+    GridHeight,
+    Players,
+    AddPlayerProfile,
+    KeyBindings,
+    BotPort,
+    AddTcpBot,
+    AddWebSocketBot,
+    RecordReplays,
+    ReplayDir,
+    LiveExport,
+    Off,
+    ExportMatchesJson,
+    Bracket,
+    StartBracket,
+    ScoreboardFile,
+    LoadScoreboard,
+    ExportScoreboard,
+    ResetScoreboard,
+    ReplayFile,
+    WatchReplay,
+    Debug,
+    Sandbox,
+    PositionFile,
+    EvalTest,
+    // Game
+    Won,
+    Tie,
+    Up,
+    Down,
+    Left,
+    Right,
+    NoDirection,
+    // Scoreboard
+    Scoreboard,
+    ExportStats,
+    Rating,
+    ShowDetails,
+    DetailsFor,
+    MissedInputs,
+    CurrentDirection,
+    RemoteAddress,
+    BotCrashes,
+    BotDisqualified,
+    StatsGames,
+    StatsDeaths,
+    StatsLength,
+    StatsSurvival,
+    StatsLatency,
+    DeathWall,
+    DeathSnake,
+    DeathHeadOn,
+    DeathDeadSnake,
+    // Event log
+    LogTurned,
+    LogAteFood,
+    LogRanIntoItself,
+    LogRanInto,
+    LogDied,
+    LogNearMiss,
+    LogRejected,
+    LogBotCrashed,
+    // Bracket
+    BracketTitle,
+    Swiss,
+    SingleElimination,
+    DoubleElimination,
+    Round,
+    Final,
+    LosersRound,
+    WinnersRound,
+    Bye,
+    ReplayCount,
+    BySeed,
+    Ranking,
+    Points,
+    Losses,
+    NextMatchIn,
+    PlayNextMatch,
+    WonTournament,
+    Finished,
+    BracketHelp,
+    On,
+    // Replay viewer
+    Replay,
+    ReplayStatus,
+    Playing,
+    Paused,
+    Timeline,
+    ReplayHelp,
+    // Key bindings
+    KeyBindingsHelp,
+    PressKeyFor,
+    RemapCancelled,
+    KeyNotBindable,
+    KeyAlreadyUsed,
+    ProfileSaved,
+    InvalidProfileName,
+    ProfileExists,
+    DefaultProfile,
+    ProfileDeleted,
+    Profiles,
+    Name,
+    AddProfile,
+    DeleteSelected,
+    Back,
+}
+
+fn english(text: Text) -> &'static str {
+    match text {
+        Text::Connecting => "Connecting",
+        Text::Connected => "{}: Connected!",
+        Text::ConnectionError => "<Connection Error>",
+        Text::LobbyAddPlayer => "P - Add Player",
+        Text::LobbyAddAi => "O - Add Ai",
+        Text::LobbyStart => "Press <Enter> to start",
+        Text::Settings => "Settings",
+        Text::Language => "Language",
+        Text::GridSize => "Grid Size",
+        Text::GridWidth => "Grid X Size",
+        Text::GridHeight => "Grid Y Size",
+        Text::Players => "Players",
+        Text::AddPlayerProfile => "Add Player ({})",
+        Text::KeyBindings => "Key Bindings",
+        Text::BotPort => "Bot Port",
+        Text::AddTcpBot => "Add TCP Bot",
+        Text::AddWebSocketBot => "Add WebSocket Bot",
+        Text::RecordReplays => "Record Replays",
+        Text::ReplayDir => "Replay Dir",
+        Text::LiveExport => "Live Export",
+        Text::Off => "Off",
+        Text::ExportMatchesJson => "Export Matches as JSON",
+        Text::Bracket => "Bracket",
+        Text::StartBracket => "Start Bracket",
+        Text::ScoreboardFile => "Scoreboard File",
+        Text::LoadScoreboard => "Load Scoreboard",
+        Text::ExportScoreboard => "Export Scoreboard",
+        Text::ResetScoreboard => "Reset Scoreboard",
+        Text::ReplayFile => "Replay File",
+        Text::WatchReplay => "Watch Replay",
+        Text::Debug => "Debug",
+        Text::Sandbox => "Sandbox",
+        Text::PositionFile => "Position File",
+        Text::EvalTest => "Eval Test",
+        Text::Won => "{} Won!",
+        Text::Tie => "Tie!",
+        Text::Up => "Up",
+        Text::Down => "Down",
+        Text::Left => "Left",
+        Text::Right => "Right",
+        Text::NoDirection => "None",
+        Text::Scoreboard => "Scoreboard",
+        Text::ExportStats => "Export Stats",
+        Text::Rating => "Rating {}",
+        Text::ShowDetails => "Show Details",
+        Text::DetailsFor => "Details for {}",
+        Text::MissedInputs => "Missed Inputs: {}",
+        Text::CurrentDirection => "Current Direction: {}",
+        Text::RemoteAddress => "Address: {}",
+        Text::BotCrashes => "Bot Crashes: {}",
+        Text::BotDisqualified => "Bot disqualified after {} crashes",
+        Text::StatsGames => "Games: {}  Kills: {}  Food: {}",
+        Text::StatsDeaths => "Deaths: {}{}",
+        Text::StatsLength => "Length: {} avg, {} max",
+        Text::StatsSurvival => "Survival: {} ticks avg",
+        Text::StatsLatency => "Latency: {}  Missed: {}",
+        Text::DeathWall => "Wall",
+        Text::DeathSnake => "Snake",
+        Text::DeathHeadOn => "Head-on",
+        Text::DeathDeadSnake => "Dead Snake",
+        Text::LogTurned => "Turned {} -> {}",
+        Text::LogAteFood => "Ate food at {}",
+        Text::LogRanIntoItself => "Died: ran into itself",
+        Text::LogRanInto => "Died: ran into {}",
+        Text::LogDied => "Died: {}",
+        Text::LogNearMiss => "Near miss with {}",
+        Text::LogRejected => "Rejected: {}",
+        Text::LogBotCrashed => "Bot process crashed",
+        Text::BracketTitle => "{} Bracket",
+        Text::Swiss => "Swiss",
+        Text::SingleElimination => "Single Elimination",
+        Text::DoubleElimination => "Double Elimination",
+        Text::Round => "Round {}",
+        Text::Final => "Final",
+        Text::LosersRound => "Losers R{}",
+        Text::WinnersRound => "Winners R{}",
+        Text::Bye => "{} (bye)",
+        Text::ReplayCount => "No winner, replay {} of {}",
+        Text::BySeed => "{} - by seed",
+        Text::Ranking => "Ranking",
+        Text::Points => "{} pts",
+        Text::Losses => "{} L",
+        Text::NextMatchIn => "Next match in {}s",
+        Text::PlayNextMatch => "<Enter> Play next match",
+        Text::WonTournament => "{} won the tournament!",
+        Text::Finished => "Finished",
+        Text::BracketHelp => "<A> Auto advance ({})  <Left/Right> Scroll  <Esc> Exit",
+        Text::On => "On",
+        Text::Replay => "Replay",
+        Text::ReplayStatus => "Tick {}/{}   Speed x{}   {}",
+        Text::Playing => "Playing",
+        Text::Paused => "Paused",
+        Text::Timeline => "Timeline",
+        Text::ReplayHelp => "<Space> Pause  <Left/Right> Step  <Up/Down> Speed  <G/P/J> Export GIF/PNG/JSON  <Esc> Exit",
+        Text::KeyBindingsHelp => "<Up/Down> Select  <Enter> Remap  <Esc> Back",
+        Text::PressKeyFor => "{}: press the key for {} (<Esc> cancels)",
+        Text::RemapCancelled => "Remapping {} cancelled",
+        Text::KeyNotBindable => "{} can not be bound",
+        Text::KeyAlreadyUsed => "{} is already used",
+        Text::ProfileSaved => "Saved {}",
+        Text::InvalidProfileName => "Invalid profile name {}",
+        Text::ProfileExists => "{} already exists",
+        Text::DefaultProfile => "{} is a default profile",
+        Text::ProfileDeleted => "Deleted {}",
+        Text::Profiles => "Profiles",
+        Text::Name => "Name",
+        Text::AddProfile => "Add Profile",
+        Text::DeleteSelected => "Delete Selected",
+        Text::Back => "Back",
+    }
+}
+
+fn german(text: Text) -> &'static str {
+    match text {
+        Text::Connecting => "Verbinden",
+        Text::Connected => "{}: Verbunden!",
+        Text::ConnectionError => "<Verbindungsfehler>",
+        Text::LobbyAddPlayer => "P - Spieler hinzufügen",
+        Text::LobbyAddAi => "O - KI hinzufügen",
+        Text::LobbyStart => "Drücke <Enter> zum Starten",
+        Text::Settings => "Einstellungen",
+        Text::Language => "Sprache",
+        Text::GridSize => "Spielfeldgröße",
+        Text::GridWidth => "Breite",
+        Text::GridHeight => "Höhe",
+        Text::Players => "Spieler",
+        Text::AddPlayerProfile => "Spieler hinzufügen ({})",
+        Text::KeyBindings => "Tastenbelegung",
+        Text::BotPort => "Bot-Port",
+        Text::AddTcpBot => "TCP-Bot hinzufügen",
+        Text::AddWebSocketBot => "WebSocket-Bot hinzufügen",
+        Text::RecordReplays => "Replays aufnehmen",
+        Text::ReplayDir => "Replay-Ordner",
+        Text::LiveExport => "Live-Export",
+        Text::Off => "Aus",
+        Text::ExportMatchesJson => "Spiele als JSON exportieren",
+        Text::Bracket => "Turnier",
+        Text::StartBracket => "Turnier starten",
+        Text::ScoreboardFile => "Punktetabelle",
+        Text::LoadScoreboard => "Punktetabelle laden",
+        Text::ExportScoreboard => "Punktetabelle exportieren",
+        Text::ResetScoreboard => "Punktetabelle zurücksetzen",
+        Text::ReplayFile => "Replay-Datei",
+        Text::WatchReplay => "Replay ansehen",
+        Text::Debug => "Debug",
+        Text::Sandbox => "Sandkasten",
+        Text::PositionFile => "Stellungsdatei",
+        Text::EvalTest => "Bewertungstest",
+        Text::Won => "{} hat gewonnen!",
+        Text::Tie => "Unentschieden!",
+        Text::Up => "Hoch",
+        Text::Down => "Runter",
+        Text::Left => "Links",
+        Text::Right => "Rechts",
+        Text::NoDirection => "Keine",
+        Text::Scoreboard => "Punktetabelle",
+        Text::ExportStats => "Statistik exportieren",
+        Text::Rating => "Wertung {}",
+        Text::ShowDetails => "Details anzeigen",
+        Text::DetailsFor => "Details für {}",
+        Text::MissedInputs => "Verpasste Eingaben: {}",
+        Text::CurrentDirection => "Aktuelle Richtung: {}",
+        Text::RemoteAddress => "Adresse: {}",
+        Text::BotCrashes => "Bot-Abstürze: {}",
+        Text::BotDisqualified => "Bot nach {} Abstürzen disqualifiziert",
+        Text::StatsGames => "Spiele: {}  Kills: {}  Futter: {}",
+        Text::StatsDeaths => "Tode: {}{}",
+        Text::StatsLength => "Länge: {} im Schnitt, {} max",
+        Text::StatsSurvival => "Überlebt: {} Ticks im Schnitt",
+        Text::StatsLatency => "Latenz: {}  Verpasst: {}",
+        Text::DeathWall => "Wand",
+        Text::DeathSnake => "Schlange",
+        Text::DeathHeadOn => "Frontal",
+        Text::DeathDeadSnake => "Tote Schlange",
+        Text::LogTurned => "Gedreht {} -> {}",
+        Text::LogAteFood => "Futter gefressen bei {}",
+        Text::LogRanIntoItself => "Gestorben: in sich selbst gefahren",
+        Text::LogRanInto => "Gestorben: in {} gefahren",
+        Text::LogDied => "Gestorben: {}",
+        Text::LogNearMiss => "Knapp an {} vorbei",
+        Text::LogRejected => "Abgelehnt: {}",
+        Text::LogBotCrashed => "Bot-Prozess abgestürzt",
+        Text::BracketTitle => "Turnier: {}",
+        Text::Swiss => "Schweizer System",
+        Text::SingleElimination => "K.-o.-System",
+        Text::DoubleElimination => "Doppel-K.-o.-System",
+        Text::Round => "Runde {}",
+        Text::Final => "Finale",
+        Text::LosersRound => "Verlierer R{}",
+        Text::WinnersRound => "Gewinner R{}",
+        Text::Bye => "{} (Freilos)",
+        Text::ReplayCount => "Kein Sieger, Wiederholung {} von {}",
+        Text::BySeed => "{} - per Setzliste",
+        Text::Ranking => "Rangliste",
+        Text::Points => "{} Pkt.",
+        Text::Losses => "{} N",
+        Text::NextMatchIn => "Nächstes Spiel in {}s",
+        Text::PlayNextMatch => "<Enter> Nächstes Spiel",
+        Text::WonTournament => "{} hat das Turnier gewonnen!",
+        Text::Finished => "Beendet",
+        Text::BracketHelp => "<A> Automatisch weiter ({})  <Links/Rechts> Scrollen  <Esc> Beenden",
+        Text::On => "An",
+        Text::Replay => "Replay",
+        Text::ReplayStatus => "Tick {}/{}   Tempo x{}   {}",
+        Text::Playing => "Läuft",
+        Text::Paused => "Pausiert",
+        Text::Timeline => "Zeitleiste",
+        Text::ReplayHelp => "<Leertaste> Pause  <Links/Rechts> Schritt  <Hoch/Runter> Tempo  <G/P/J> Export GIF/PNG/JSON  <Esc> Beenden",
+        Text::KeyBindingsHelp => "<Hoch/Runter> Auswählen  <Enter> Neu belegen  <Esc> Zurück",
+        Text::PressKeyFor => "{}: Taste für {} drücken (<Esc> bricht ab)",
+        Text::RemapCancelled => "Belegung von {} abgebrochen",
+        Text::KeyNotBindable => "{} kann nicht belegt werden",
+        Text::KeyAlreadyUsed => "{} ist schon belegt",
+        Text::ProfileSaved => "{} gespeichert",
+        Text::InvalidProfileName => "Ungültiger Profilname {}",
+        Text::ProfileExists => "{} existiert bereits",
+        Text::DefaultProfile => "{} ist ein Standardprofil",
+        Text::ProfileDeleted => "{} gelöscht",
+        Text::Profiles => "Profile",
+        Text::Name => "Name",
+        Text::AddProfile => "Profil hinzufügen",
+        Text::DeleteSelected => "Auswahl löschen",
+        Text::Back => "Zurück",
+    }
+}
+
+pub fn direction_name(direction: Direction) -> &'static str {
+    tr(match direction {
+        Direction::UP => Text::Up,
+        Direction::DOWN => Text::Down,
+        Direction::LEFT => Text::Left,
+        Direction::RIGHT => Text::Right,
+        Direction::NONE => Text::NoDirection,
+    })
+}
+
+pub fn tr(text: Text) -> &'static str {
+    match get_language() {
+        Language::English => english(text),
+        Language::German => german(text),
+    }
+}
+
+pub fn trf(text: Text, arguments: &[&dyn Display]) -> String {
+    // Replaces the {} of the text in order
+    let mut parts = tr(text).split("{}");
+    let mut result = parts.next().unwrap_or_default().to_string();
+    for (i, part) in parts.enumerate() {
+        if let Some(argument) = arguments.get(i) {
+            result.push_str(&argument.to_string());
+        }
+        result.push_str(part);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL_TEXTS: [Text; 109] = [
+        Text::Connecting, Text::Connected, Text::ConnectionError, Text::LobbyAddPlayer, Text::LobbyAddAi, Text::LobbyStart,
+        Text::Settings, Text::Language, Text::GridSize, Text::GridWidth, Text::GridHeight, Text::Players,
+        Text::AddPlayerProfile, Text::KeyBindings, Text::BotPort, Text::AddTcpBot, Text::AddWebSocketBot,
+        Text::RecordReplays, Text::ReplayDir, Text::LiveExport, Text::Off, Text::ExportMatchesJson, Text::Bracket,
+        Text::StartBracket, Text::ScoreboardFile, Text::LoadScoreboard, Text::ExportScoreboard, Text::ResetScoreboard,
+        Text::ReplayFile, Text::WatchReplay, Text::Debug, Text::Sandbox, Text::PositionFile, Text::EvalTest, Text::Won,
+        Text::Tie, Text::Up, Text::Down, Text::Left, Text::Right, Text::NoDirection, Text::Scoreboard, Text::ExportStats,
+        Text::Rating, Text::ShowDetails, Text::DetailsFor, Text::MissedInputs, Text::CurrentDirection, Text::RemoteAddress,
+        Text::BotCrashes, Text::BotDisqualified, Text::StatsGames, Text::StatsDeaths, Text::StatsLength,
+        Text::StatsSurvival, Text::StatsLatency, Text::DeathWall, Text::DeathSnake, Text::DeathHeadOn, Text::DeathDeadSnake,
+        Text::LogTurned, Text::LogAteFood, Text::LogRanIntoItself, Text::LogRanInto, Text::LogDied, Text::LogNearMiss,
+        Text::LogRejected, Text::LogBotCrashed, Text::BracketTitle, Text::Swiss, Text::SingleElimination,
+        Text::DoubleElimination, Text::Round, Text::Final, Text::LosersRound, Text::WinnersRound, Text::Bye,
+        Text::ReplayCount, Text::BySeed, Text::Ranking, Text::Points, Text::Losses, Text::NextMatchIn, Text::PlayNextMatch,
+        Text::WonTournament, Text::Finished, Text::BracketHelp, Text::On, Text::Replay, Text::ReplayStatus, Text::Playing,
+        Text::Paused, Text::Timeline, Text::ReplayHelp, Text::KeyBindingsHelp, Text::PressKeyFor, Text::RemapCancelled,
+        Text::KeyNotBindable, Text::KeyAlreadyUsed, Text::ProfileSaved, Text::InvalidProfileName, Text::ProfileExists,
+        Text::DefaultProfile, Text::ProfileDeleted, Text::Profiles, Text::Name, Text::AddProfile, Text::DeleteSelected,
+        Text::Back
+    ];
+
+    #[test]
+    fn all_texts_are_listed() {
+        // Back is the last variant, new texts have to be added to the list above
+        assert_eq!(ALL_TEXTS.len(), Text::Back as usize + 1);
+        assert!(ALL_TEXTS.iter().enumerate().all(|(i, text)| *text as usize == i));
+    }
+
+    #[test]
+    fn translations_have_the_same_placeholders() {
+        for text in ALL_TEXTS {
+            assert_eq!(english(text).matches("{}").count(), german(text).matches("{}").count(), "{:?}", text);
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use serde::{Deserialize, Serialize};
+
+use super::{events::DeathCause, snakegrid::SnakeGrid};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Standing {
+    pub placement: u32, // 1 is the winner, snakes that died in the same tick with the same size share a placement
+    pub id: i32,
+    pub name: String,
+    pub size: i32,
+    pub alive: bool,
+    pub death_tick: Option<u32>,
+    pub death_cause: Option<DeathCause>,
+    #[serde(default)]
+    pub missed_inputs: u32,
+    #[serde(default)]
+    pub average_latency: Option<f32> // Milliseconds, None if the controller does not answer gamestates
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatchResult {
+    pub seed: u64,
+    pub ticks: u32,
+    pub duration: f32, // Seconds
+    pub winner: Option<i32>,
+    pub standings: Vec<Standing>
+}
+
+impl MatchResult {
+    pub fn from_grid(game_grid: &SnakeGrid, duration: f32) -> Self {
+        Self {
+            seed: game_grid.get_seed(),
+            ticks: game_grid.get_tick_count(),
+            duration,
+            winner: game_grid.get_winner().map(|x| x.id),
+            standings: compute_standings(game_grid)
+        }
+    }
+}
+
+pub fn compute_standings(game_grid: &SnakeGrid) -> Vec<Standing> {
+    let winner = game_grid.get_winner().map(|x| x.id);
+    let controller_stats = game_grid.get_stats_dict();
+    let mut standings: Vec<Standing> = game_grid.get_all_snake_refs().into_iter().map(|snake| {
+        let death = game_grid.get_deaths().iter().find(|x| x.snake_id == snake.id);
+        let stats = controller_stats.get(&snake.id);
+        Standing {
+            placement: 0,
+            id: snake.id,
+            name: snake.name,
+            size: snake.size,
+            alive: snake.alive,
+            death_tick: death.map(|x| x.tick),
+            death_cause: death.map(|x| x.cause),
+            missed_inputs: stats.map(|x| x.missed_inputs).unwrap_or(0),
+            average_latency: stats.and_then(|x| x.average_latency()).map(|x| x.as_secs_f32() * 1000.)
+        }
+    }).collect();
+
+    // Winner first, then the survivors, then the snakes that died last. Longer snakes win ties.
+    let rank = |x: &Standing| (Some(x.id) != winner, !x.alive, u32::MAX - x.death_tick.unwrap_or(u32::MAX), -x.size);
+    standings.sort_by_key(rank);
+    let ranks: Vec<_> = standings.iter().map(rank).collect();
+    for (i, standing) in standings.iter_mut().enumerate() {
+        standing.placement = ranks.iter().position(|x| *x == ranks[i]).unwrap() as u32 + 1;
+    }
+    standings
+}
+
+// The key of a bot in the scoreboard and the ratings, by roster slot (index in the list of players).
+// Names are only known once a bot connected, so every slot gets its key the first time it plays and keeps it.
+// A name that is already taken by another slot becomes "Name (2)", "Name (3)", ... skipping keys that exist.
+#[derive(Debug, Clone, Default)]
+pub struct RosterKeys {
+    keys: Vec<Option<String>>
+}
+
+impl RosterKeys {
+    pub fn assign(&mut self, slot: usize, name: &str) -> String {
+        if let Some(Some(key)) = self.keys.get(slot) {
+            return key.clone();
+        }
+        let taken = |key: &str| self.keys.iter().flatten().any(|x| x == key);
+        let mut key = name.to_string();
+        let mut count = 1;
+        while taken(&key) {
+            count += 1;
+            key = format!("{} ({})", name, count);
+        }
+        if self.keys.len() <= slot {
+            self.keys.resize(slot + 1, None);
+        }
+        self.keys[slot] = Some(key.clone());
+        key
+    }
+
+    pub fn match_keys(&mut self, slots: &[usize], result: &MatchResult) -> Vec<String> {
+        // Indexed by snake id, slots[id] is the roster slot of the snake. New slots are numbered in id order.
+        slots.iter().enumerate().map(|(id, slot)| {
+            let name = result.standings.iter().find(|x| x.id == id as i32).map(|x| x.name.as_str()).unwrap_or_default();
+            self.assign(*slot, name)
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roster_keys_stay_with_their_slot() {
+        let mut keys = RosterKeys::default();
+        assert_eq!(keys.assign(3, "Bot"), "Bot");
+        assert_eq!(keys.assign(0, "Bot"), "Bot (2)");
+        assert_eq!(keys.assign(3, "Renamed"), "Bot");
+        // Numbering skips keys that are taken by other bots
+        assert_eq!(keys.assign(1, "Bot (3)"), "Bot (3)");
+        assert_eq!(keys.assign(2, "Bot"), "Bot (4)");
+    }
+
+    #[test]
+    fn match_keys_follow_the_snake_ids() {
+        let standing = |id: i32, placement: u32| Standing { placement, id, name: "Bot".to_string(), size: 1, alive: false, death_tick: None, death_cause: None, missed_inputs: 0, average_latency: None };
+        let result = MatchResult { seed: 0, ticks: 1, duration: 0., winner: None, standings: vec![standing(2, 1), standing(0, 2), standing(1, 3)] };
+        assert_eq!(RosterKeys::default().match_keys(&[4, 5, 6], &result), vec!["Bot", "Bot (2)", "Bot (3)"]);
+    }
+}
@@ -4,4 +4,19 @@ pub mod snakegrid;
 pub mod snake_controller;
 pub mod scenes;
 pub mod consts;
-pub mod scoreboard;
\ No newline at end of file
+pub mod scoreboard;
+pub mod snapshot;
+pub mod rules;
+pub mod events;
+pub mod replay;
+pub mod export;
+pub mod match_result;
+pub mod headless;
+pub mod tournament;
+pub mod rating;
+pub mod bracket;
+pub mod stats;
+pub mod event_log;
+pub mod cli;
+pub mod config;
+pub mod locale;
\ No newline at end of file
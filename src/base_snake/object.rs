@@ -1,7 +1,9 @@
 use std::fmt::Debug;
 use std::hash::Hash;
 
-#[derive(Clone, Debug, PartialEq)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Tile {
     EMPTY,
     FOOD,
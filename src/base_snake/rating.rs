@@ -0,0 +1,168 @@
+use std::{collections::HashMap, fs, path::{Path, PathBuf}};
+
+use serde::{Deserialize, Serialize};
+
+use super::match_result::Standing;
+
+pub const DEFAULT_RATING: f32 = 1500.;
+const K_FACTOR: f32 = 32.;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rating {
+    pub rating: f32,
+    pub games: u32
+}
+
+impl Default for Rating {
+    fn default() -> Self {
+        Self { rating: DEFAULT_RATING, games: 0 }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RatingTable {
+    // Elo ratings keyed like the scoreboard, see RosterKeys. Matches with more than two snakes are rated as every pair of snakes playing each other.
+    ratings: HashMap<String, Rating>,
+    #[serde(skip)]
+    path: Option<PathBuf>
+}
+
+fn expected_score(rating: f32, opponent: f32) -> f32 {
+    1. / (1. + 10f32.powf((opponent - rating) / 400.))
+}
+
+impl RatingTable {
+    pub fn load(path: &Path) -> Self {
+        // A missing or broken file starts a new table at the same path
+        let mut table = match fs::read_to_string(path) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                println!("[ERROR] Invalid rating file {}: {}", path.display(), e);
+                RatingTable::default()
+            }),
+            Err(_) => RatingTable::default()
+        };
+        table.path = Some(path.to_path_buf());
+        table
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let json = serde_json::to_string_pretty(self).map_err(|e| format!("Unable to serialize ratings: {}", e))?;
+        fs::write(path, json).map_err(|e| format!("Unable to write {}: {}", path.display(), e))
+    }
+
+    pub fn get(&self, name: &str) -> Rating {
+        self.ratings.get(name).cloned().unwrap_or_default()
+    }
+
+    pub fn get_all(&self) -> &HashMap<String, Rating> {
+        &self.ratings
+    }
+
+    pub fn update(&mut self, standings: &[Standing], keys: &[String]) {
+        // The keys are indexed by snake id, like RosterKeys::match_keys
+        if standings.len() < 2 {
+            return;
+        }
+        let keys: Vec<String> = standings.iter().map(|x| keys.get(x.id as usize).cloned().unwrap_or_else(|| x.name.clone())).collect();
+        let current: Vec<f32> = keys.iter().map(|x| self.get(x).rating).collect();
+        let k = K_FACTOR / (standings.len() - 1) as f32;
+
+        for (i, standing) in standings.iter().enumerate() {
+            let delta: f32 = standings.iter().enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(j, opponent)| {
+                    let score = match standing.placement.cmp(&opponent.placement) {
+                        std::cmp::Ordering::Less => 1.,
+                        std::cmp::Ordering::Equal => 0.5,
+                        std::cmp::Ordering::Greater => 0.,
+                    };
+                    score - expected_score(current[i], current[j])
+                })
+                .sum();
+
+            let rating = self.ratings.entry(keys[i].clone()).or_default();
+            rating.rating += k * delta;
+            rating.games += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn standing(id: i32, name: &str, placement: u32) -> Standing {
+        Standing { placement, id, name: name.to_string(), size: 1, alive: placement == 1, death_tick: None, death_cause: None, missed_inputs: 0, average_latency: None }
+    }
+
+    fn keys(keys: &[&str]) -> Vec<String> {
+        keys.iter().map(|x| x.to_string()).collect()
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 0.01, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn expected_score_is_symmetric() {
+        assert_close(expected_score(1500., 1500.), 0.5);
+        assert_close(expected_score(1900., 1500.), 10. / 11.);
+        assert_close(expected_score(1700., 1500.) + expected_score(1500., 1700.), 1.);
+    }
+
+    #[test]
+    fn win_between_equal_ratings() {
+        let mut table = RatingTable::default();
+        table.update(&[standing(1, "B", 1), standing(0, "A", 2)], &keys(&["A", "B"]));
+        assert_close(table.get("B").rating, DEFAULT_RATING + K_FACTOR / 2.);
+        assert_close(table.get("A").rating, DEFAULT_RATING - K_FACTOR / 2.);
+        assert_eq!(table.get("A").games, 1);
+    }
+
+    #[test]
+    fn draw_between_equal_ratings_keeps_them() {
+        let mut table = RatingTable::default();
+        table.update(&[standing(0, "A", 1), standing(1, "B", 1)], &keys(&["A", "B"]));
+        assert_close(table.get("A").rating, DEFAULT_RATING);
+        assert_close(table.get("B").rating, DEFAULT_RATING);
+    }
+
+    #[test]
+    fn multiplayer_matches_are_rated_pairwise() {
+        let mut table = RatingTable::default();
+        table.update(&[standing(2, "C", 1), standing(0, "A", 2), standing(1, "B", 3)], &keys(&["A", "B", "C"]));
+        // Every pair counts with half the k factor, the total stays the same
+        assert_close(table.get("C").rating, DEFAULT_RATING + K_FACTOR / 2.);
+        assert_close(table.get("A").rating, DEFAULT_RATING);
+        assert_close(table.get("B").rating, DEFAULT_RATING - K_FACTOR / 2.);
+        let total: f32 = table.get_all().values().map(|x| x.rating).sum();
+        assert_close(total, DEFAULT_RATING * 3.);
+    }
+
+    #[test]
+    fn upset_moves_more_points() {
+        let mut table = RatingTable::default();
+        table.ratings.insert("Strong".to_string(), Rating { rating: 1800., games: 10 });
+        table.update(&[standing(1, "Weak", 1), standing(0, "Strong", 2)], &keys(&["Strong", "Weak"]));
+        assert!(table.get("Weak").rating - DEFAULT_RATING > K_FACTOR * 0.8);
+    }
+
+    #[test]
+    fn same_names_are_rated_by_their_keys() {
+        let mut table = RatingTable::default();
+        // Keys follow the snake ids, not the placements
+        table.update(&[standing(1, "Bot", 1), standing(0, "Bot", 2)], &keys(&["Bot", "Bot (2)"]));
+        assert_close(table.get("Bot (2)").rating, DEFAULT_RATING + K_FACTOR / 2.);
+        assert_close(table.get("Bot").rating, DEFAULT_RATING - K_FACTOR / 2.);
+    }
+
+    #[test]
+    fn single_snake_matches_are_not_rated() {
+        let mut table = RatingTable::default();
+        table.update(&[standing(0, "A", 1)], &keys(&["A"]));
+        assert!(table.get_all().is_empty());
+    }
+}
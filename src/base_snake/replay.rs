@@ -0,0 +1,330 @@
+use std::{cell::Cell, collections::HashMap, fmt::Display, fs::{self, File}, io::{BufRead, BufReader, BufWriter, Write}, path::{Path, PathBuf}, rc::Rc, time::{SystemTime, UNIX_EPOCH}};
+
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+
+use super::{events::GameEvent, rules::GameRules, snake_controller::replay_controller::ReplayController, snake::{Direction, PlayerInfo}, snakegrid::SnakeGrid, snapshot::{GameState, GridSnapshot}};
+
+pub const REPLAY_FORMAT_VERSION: u32 = 1;
+pub const REPLAY_EXTENSION: &str = "msreplay";
+
+// A replay file is a gzip compressed stream of JSON lines: first the header, then one entry per line
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayHeader {
+    pub version: u32,
+    pub seed: u64,
+    pub created: u64, // Unix timestamp
+    pub rules: GameRules,
+    pub initial_state: GameState
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TickRecord {
+    pub tick: u32,
+    pub inputs: Vec<Direction>, // Indexed by snake id
+    pub events: Vec<GameEvent>,
+    pub infos: HashMap<i32, PlayerInfo>, // Marked cells and info lines of the bots after the tick
+    #[serde(default)]
+    pub checksum: Option<u64> // GridSnapshot::checksum after the tick
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ReplayEntry {
+    Tick(TickRecord),
+    End { winner: Option<i32> }
+}
+
+#[derive(Debug, Clone)]
+pub struct Replay {
+    pub header: ReplayHeader,
+    pub ticks: Vec<TickRecord>,
+    pub winner: Option<i32>,
+    pub complete: bool // False if the recording stopped before the match ended
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence {
+    pub tick: u32,
+    pub reason: String
+}
+impl Display for Divergence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Diverged at tick {}: {}", self.tick, self.reason)
+    }
+}
+
+pub struct ReplayRecorder {
+    writer: GzEncoder<BufWriter<File>>,
+    path: PathBuf
+}
+
+fn write_line<T: Serialize>(writer: &mut impl Write, value: &T) -> Result<(), String> {
+    let line = serde_json::to_string(value).map_err(|e| format!("Unable to serialize replay entry: {}", e))?;
+    writeln!(writer, "{}", line).map_err(|e| format!("Unable to write replay: {}", e))
+}
+
+impl ReplayRecorder {
+    pub fn create(directory: &Path, game_grid: &SnakeGrid) -> Result<Self, String> {
+        // Has to be called after the game was started so the spawn positions are part of the initial state
+        fs::create_dir_all(directory).map_err(|e| format!("Unable to create {}: {}", directory.display(), e))?;
+
+        let created = SystemTime::now().duration_since(UNIX_EPOCH).map(|x| x.as_secs()).unwrap_or(0);
+        let path = directory.join(format!("{}-{}.{}", created, game_grid.get_seed(), REPLAY_EXTENSION));
+        let file = File::create(&path).map_err(|e| format!("Unable to create {}: {}", path.display(), e))?;
+
+        let mut recorder = Self { writer: GzEncoder::new(BufWriter::new(file), Compression::default()), path };
+        let header = ReplayHeader {
+            version: REPLAY_FORMAT_VERSION,
+            seed: game_grid.get_seed(),
+            created,
+            rules: game_grid.rules.clone(),
+            initial_state: game_grid.save_state()
+        };
+        write_line(&mut recorder.writer, &header)?;
+        Ok(recorder)
+    }
+
+    pub fn record_tick(&mut self, game_grid: &SnakeGrid) -> Result<(), String> {
+        let record = TickRecord {
+            tick: game_grid.get_tick_count(),
+            inputs: game_grid.get_last_inputs().clone(),
+            events: game_grid.get_last_events().clone(),
+            infos: game_grid.get_info_dict(),
+            checksum: Some(game_grid.snapshot().checksum())
+        };
+        write_line(&mut self.writer, &ReplayEntry::Tick(record))
+    }
+
+    pub fn finish(mut self, winner: Option<i32>) -> Result<PathBuf, String> {
+        write_line(&mut self.writer, &ReplayEntry::End { winner })?;
+        self.writer.finish()
+            .and_then(|mut x| x.flush())
+            .map_err(|e| format!("Unable to finish replay: {}", e))?;
+        Ok(self.path)
+    }
+}
+
+impl Replay {
+    pub fn load(path: &Path) -> Result<Replay, String> {
+        let file = File::open(path).map_err(|e| format!("Unable to open {}: {}", path.display(), e))?;
+        let mut lines = BufReader::new(GzDecoder::new(file)).lines();
+
+        let header_line = lines.next()
+            .ok_or("Replay is empty")?
+            .map_err(|e| format!("Unable to read replay: {}", e))?;
+        let header: ReplayHeader = serde_json::from_str(&header_line).map_err(|e| format!("Invalid replay header: {}", e))?;
+        if header.version > REPLAY_FORMAT_VERSION {
+            return Err(format!("Replay version {} is newer than the supported version {}", header.version, REPLAY_FORMAT_VERSION));
+        }
+        header.rules.validate().and(header.initial_state.rules.validate()).and(header.initial_state.grid.validate()).map_err(|e| format!("Invalid replay: {}", e))?;
+
+        let mut replay = Replay { header, ticks: Vec::new(), winner: None, complete: false };
+        for line in lines {
+            let Ok(line) = line else {
+                println!("[WARNING] Replay {} is truncated", path.display());
+                break;
+            };
+            match serde_json::from_str(&line).map_err(|e| format!("Invalid replay entry: {}", e))? {
+                ReplayEntry::Tick(record) => replay.ticks.push(record),
+                ReplayEntry::End { winner } => {
+                    replay.winner = winner;
+                    replay.complete = true;
+                    break;
+                }
+            }
+        }
+        Ok(replay)
+    }
+
+    pub fn create_controllers(&self, cursor: &Rc<Cell<usize>>) -> Vec<ReplayController> {
+        let ticks = Rc::new(self.ticks.clone());
+        self.header.initial_state.grid.snakes.iter()
+            .map(|x| ReplayController::new(x.id, x.name.clone(), Rc::clone(&ticks), Rc::clone(cursor)))
+            .collect()
+    }
+
+    pub fn create_grid<'a>(&self, controllers: &'a mut [ReplayController]) -> SnakeGrid<'a> {
+        // Sets up a grid in the recorded initial state, driven by the controllers from create_controllers
+        let state = &self.header.initial_state;
+        let mut game_grid = SnakeGrid::new(state.grid.width, state.grid.height);
+        controllers.iter_mut().for_each(|x| { game_grid.add_snake(x); });
+        game_grid.reseed(self.header.seed);
+        if let Err(e) = game_grid.load_state(state) {
+            println!("[ERROR] {}", e); // Not possible for loaded replays, the controllers are created from the same snakes
+        }
+        game_grid
+    }
+
+    pub fn simulate(&self) -> Vec<GridSnapshot> {
+        // The state before the first tick followed by the state after every tick
+        let cursor = Rc::new(Cell::new(0));
+        let mut controllers = self.create_controllers(&cursor);
+        let mut game_grid = self.create_grid(&mut controllers);
+
+        let mut frames = vec![game_grid.snapshot()];
+        for i in 0..self.ticks.len() {
+            cursor.set(i);
+            game_grid.tick();
+            frames.push(game_grid.snapshot());
+        }
+        frames
+    }
+
+    pub fn infos_after(&self, ticks_applied: usize) -> HashMap<i32, PlayerInfo> {
+        ticks_applied.checked_sub(1)
+            .and_then(|x| self.ticks.get(x))
+            .map(|x| x.infos.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn verify(&self) -> Result<usize, Divergence> {
+        // Re-simulates the match headlessly from the seed and the recorded inputs. Returns the number of verified ticks.
+        // The inputs drive the simulation, so only their results are compared: events, checksums and the winner.
+        let cursor = Rc::new(Cell::new(0));
+        let mut controllers = self.create_controllers(&cursor);
+        let mut game_grid = self.create_grid(&mut controllers);
+
+        for (i, record) in self.ticks.iter().enumerate() {
+            cursor.set(i);
+            game_grid.tick();
+
+            let diverged = |reason: String| Divergence { tick: record.tick, reason };
+            if game_grid.get_tick_count() != record.tick {
+                return Err(diverged(format!("Expected tick number {}, got {}", record.tick, game_grid.get_tick_count())));
+            }
+            if game_grid.get_last_events() != &record.events {
+                return Err(diverged(format!("Events {:?} were recorded, the engine produced {:?}", record.events, game_grid.get_last_events())));
+            }
+            if let Some(checksum) = record.checksum {
+                let actual = game_grid.snapshot().checksum();
+                if actual != checksum {
+                    return Err(diverged(format!("State checksum {:016x} was recorded, the engine produced {:016x}", checksum, actual)));
+                }
+            }
+        }
+
+        if self.complete {
+            let winner = game_grid.get_winner().map(|x| x.id);
+            if winner != self.winner {
+                return Err(Divergence { tick: game_grid.get_tick_count(), reason: format!("Winner {:?} was recorded, the engine ended with {:?}", self.winner, winner) });
+            }
+        }
+        Ok(self.ticks.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::base_snake::snake::SnakeController;
+
+    use super::*;
+
+    #[derive(Debug)]
+    struct FixedController(Direction);
+
+    impl SnakeController for FixedController {
+        fn get_name(&self) -> String {
+            "Fixed".to_string()
+        }
+
+        fn next_direction(&self) -> Direction {
+            self.0
+        }
+
+        fn clone_weak(&self) -> Box<dyn SnakeController> {
+            Box::new(FixedController(self.0))
+        }
+    }
+
+    fn record_match(directions: &[Direction]) -> Replay {
+        // Plays a match to the end and records it like ReplayRecorder does
+        let mut controllers: Vec<FixedController> = directions.iter().map(|x| FixedController(*x)).collect();
+        let mut game_grid = SnakeGrid::new(10, 8);
+        controllers.iter_mut().for_each(|x| game_grid.add_snake(x));
+        game_grid.reseed(3);
+        game_grid.start_game();
+        game_grid.do_place_food();
+
+        let header = ReplayHeader { version: REPLAY_FORMAT_VERSION, seed: game_grid.get_seed(), created: 0, rules: game_grid.rules.clone(), initial_state: game_grid.save_state() };
+        let mut ticks = Vec::new();
+        while game_grid.check_end() == Err(1) {
+            game_grid.tick();
+            ticks.push(TickRecord {
+                tick: game_grid.get_tick_count(),
+                inputs: game_grid.get_last_inputs().clone(),
+                events: game_grid.get_last_events().clone(),
+                infos: HashMap::new(),
+                checksum: Some(game_grid.snapshot().checksum())
+            });
+        }
+        let winner = game_grid.get_winner().map(|x| x.id);
+        Replay { header, ticks, winner, complete: true }
+    }
+
+    #[test]
+    fn recorded_match_verifies() {
+        let replay = record_match(&[Direction::RIGHT, Direction::UP]);
+        assert!(!replay.ticks.is_empty());
+        assert_eq!(replay.verify(), Ok(replay.ticks.len()));
+    }
+
+    #[test]
+    fn simulation_is_deterministic() {
+        let replay = record_match(&[Direction::RIGHT, Direction::UP, Direction::LEFT]);
+        let first: Vec<u64> = replay.simulate().iter().map(|x| x.checksum()).collect();
+        let second: Vec<u64> = replay.simulate().iter().map(|x| x.checksum()).collect();
+        assert_eq!(first, second);
+        assert_eq!(first.len(), replay.ticks.len() + 1);
+    }
+
+    #[test]
+    fn changed_input_diverges() {
+        let mut replay = record_match(&[Direction::RIGHT, Direction::UP]);
+        replay.ticks[1].inputs[0] = Direction::DOWN;
+        // The replay controllers play the changed input, so the recorded checksum no longer matches
+        assert_eq!(replay.verify().map_err(|x| x.tick), Err(replay.ticks[1].tick));
+    }
+
+    #[test]
+    fn changed_checksum_diverges() {
+        let mut replay = record_match(&[Direction::RIGHT, Direction::UP]);
+        let last = replay.ticks.len() - 1;
+        replay.ticks[last].checksum = Some(0);
+        assert_eq!(replay.verify().map_err(|x| x.tick), Err(replay.ticks[last].tick));
+    }
+
+    #[test]
+    fn saved_replay_loads_and_verifies() {
+        let mut controllers = [FixedController(Direction::RIGHT), FixedController(Direction::UP)];
+        let mut game_grid = SnakeGrid::new(10, 8);
+        controllers.iter_mut().for_each(|x| game_grid.add_snake(x));
+        game_grid.reseed(5);
+        game_grid.start_game();
+
+        let directory = std::env::temp_dir().join(format!("multisnake-replay-test-{}", std::process::id()));
+        let mut recorder = ReplayRecorder::create(&directory, &game_grid).unwrap();
+        while game_grid.check_end() == Err(1) {
+            game_grid.tick();
+            recorder.record_tick(&game_grid).unwrap();
+        }
+        let path = recorder.finish(game_grid.get_winner().map(|x| x.id)).unwrap();
+
+        let replay = Replay::load(&path);
+        fs::remove_dir_all(&directory).ok();
+        let replay = replay.unwrap();
+        assert!(replay.complete);
+        assert_eq!(replay.verify(), Ok(game_grid.get_tick_count() as usize));
+    }
+
+    #[test]
+    fn changed_winner_diverges() {
+        let mut replay = record_match(&[Direction::RIGHT, Direction::UP]);
+        replay.winner = replay.winner.map(|x| 1 - x).or(Some(0));
+        assert!(replay.verify().is_err());
+
+        // Incomplete recordings have no winner to compare
+        replay.complete = false;
+        assert!(replay.verify().is_ok());
+    }
+}
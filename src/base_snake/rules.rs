@@ -0,0 +1,24 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameRules {
+    pub tick_duration: f32, // Seconds between two ticks
+    pub time_limit: u64, // Seconds until the longest alive snake wins
+}
+
+impl Default for GameRules {
+    fn default() -> Self {
+        Self { tick_duration: 0.15, time_limit: 600 }
+    }
+}
+
+impl GameRules {
+    pub fn validate(&self) -> Result<(), String> {
+        // NaN and infinite durations would panic once they are turned into a Duration
+        if !(self.tick_duration.is_finite() && self.tick_duration > 0.) {
+            return Err(format!("The tick duration has to be a positive number of seconds, got {}", self.tick_duration));
+        }
+        Ok(())
+    }
+}
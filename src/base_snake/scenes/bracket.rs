@@ -0,0 +1,164 @@
+use std::{thread::sleep, time::{Duration, Instant}};
+
+use macroquad::prelude::*;
+
+use crate::base_snake::{bracket::{Bracket, BracketFormat, BracketMatch, MAX_REPLAYS}, locale::{tr, trf, Text}, rating::RatingTable, rules::GameRules, scoreboard::Scoreboard, snake::SnakeController};
+
+use super::{connect::{connection_screen, draw_version_hud}, game::{create_grid, play_match, GameOptions, GameSounds}};
+
+const COLUMN_WIDTH: f32 = 230.;
+const AUTO_ADVANCE_DELAY: f32 = 5.; // Seconds
+
+pub struct BracketSettings {
+    pub format: BracketFormat,
+    pub grid_size: (i32, i32),
+    pub rules: GameRules,
+    pub colors: Vec<(u8, u8, u8)>,
+    pub seed: Option<u64>, // Every game adds its number, random if None
+    pub options: GameOptions
+}
+
+fn match_title(bracket: &Bracket, bracket_match: &BracketMatch) -> String {
+    match bracket.get_format() {
+        BracketFormat::Swiss { .. } => trf(Text::Round, &[&bracket_match.round]),
+        _ if bracket_match.is_final => tr(Text::Final).to_string(),
+        BracketFormat::DoubleElimination if bracket_match.tier > 0 => trf(Text::LosersRound, &[&bracket_match.round]),
+        BracketFormat::DoubleElimination => trf(Text::WinnersRound, &[&bracket_match.round]),
+        BracketFormat::SingleElimination => trf(Text::Round, &[&bracket_match.round]),
+    }
+}
+
+fn draw_bracket(bracket: &Bracket, next: Option<usize>, scroll: u32) {
+    clear_background(DARKGRAY);
+    draw_text(&trf(Text::BracketTitle, &[&bracket.get_format().name()]), 20.0, 40.0, 40.0, WHITE);
+
+    let names = bracket.get_names();
+    let matches = bracket.get_matches();
+    for round in (scroll + 1)..=bracket.get_round() {
+        let x = 20. + (round - scroll - 1) as f32 * COLUMN_WIDTH;
+        if x + COLUMN_WIDTH > screen_width() - 260. {
+            break;
+        }
+        let mut y = 80.;
+        for (index, bracket_match) in matches.iter().enumerate().filter(|(_, x)| x.round == round) {
+            let height = 22. + 20. * bracket_match.entrants.len() as f32;
+            let border = if Some(index) == next { YELLOW } else { GRAY };
+            draw_rectangle(x, y, COLUMN_WIDTH - 20., height, BLACK);
+            draw_rectangle_lines(x, y, COLUMN_WIDTH - 20., height, 2., border);
+            let (title, title_color) = match bracket_match.winner {
+                Some(_) if bracket_match.by_seed => (trf(Text::BySeed, &[&match_title(bracket, bracket_match)]), ORANGE),
+                None if bracket_match.replays > 0 => (trf(Text::ReplayCount, &[&bracket_match.replays, &MAX_REPLAYS]), ORANGE),
+                _ => (match_title(bracket, bracket_match), LIGHTGRAY)
+            };
+            draw_text(&title, x + 6., y + 16., 18., title_color);
+
+            for (i, entrant) in bracket_match.entrants.iter().enumerate() {
+                let color = match bracket_match.winner {
+                    Some(winner) if winner == *entrant => GREEN,
+                    Some(_) => RED,
+                    None if bracket_match.draw => ORANGE,
+                    None => WHITE
+                };
+                let name = if bracket_match.bye { trf(Text::Bye, &[&names[*entrant]]) } else { names[*entrant].clone() };
+                draw_text(&name, x + 10., y + 36. + 20. * i as f32, 20., color);
+            }
+            y += height + 10.;
+        }
+    }
+
+    // Current ranking on the right
+    let x = screen_width() - 240.;
+    draw_text(tr(Text::Ranking), x, 80., 30., WHITE);
+    for (i, entrant) in bracket.ranking().iter().enumerate() {
+        let detail = match bracket.get_format() {
+            BracketFormat::Swiss { .. } => trf(Text::Points, &[&bracket.get_points(*entrant)]),
+            _ => trf(Text::Losses, &[&bracket.get_losses(*entrant)])
+        };
+        draw_text(&format!("{}. {} ({})", i + 1, names[*entrant], detail), x, 110. + 22. * i as f32, 20., WHITE);
+    }
+}
+
+pub async fn bracket_view(entrants: &[Box<dyn SnakeController>], settings: &BracketSettings, sounds: &GameSounds, scoreboard: &mut Scoreboard, ratings: &mut RatingTable) {
+    // Entrants are templates, every match gets a fresh clone_weak like the main loop
+    let names = entrants.iter().map(|x| x.get_name()).collect();
+    let mut bracket = Bracket::new(settings.format, names, Some(&*ratings));
+    let mut auto_advance = false;
+    let mut scroll = 0;
+    let mut shown_round = 0;
+    let mut shown_since = Instant::now();
+    let mut games_played: u64 = 0;
+
+    loop {
+        let next = bracket.next_match();
+        if bracket.get_round() != shown_round {
+            // Keep the newest round in view
+            let visible = ((screen_width() - 280.) / COLUMN_WIDTH).max(1.) as u32;
+            scroll = bracket.get_round().saturating_sub(visible);
+            shown_round = bracket.get_round();
+        }
+
+        if is_key_pressed(KeyCode::Escape) {
+            break;
+        }
+        if is_key_pressed(KeyCode::A) {
+            auto_advance = !auto_advance;
+            shown_since = Instant::now();
+        }
+        if is_key_pressed(KeyCode::Right) {
+            scroll = (scroll + 1).min(bracket.get_round().saturating_sub(1));
+        }
+        if is_key_pressed(KeyCode::Left) {
+            scroll = scroll.saturating_sub(1);
+        }
+
+        draw_bracket(&bracket, next, scroll);
+        let status = match (next, bracket.champion()) {
+            (Some(_), _) if auto_advance => trf(Text::NextMatchIn, &[&format!("{:.0}", (AUTO_ADVANCE_DELAY - shown_since.elapsed().as_secs_f32()).max(0.))]),
+            (Some(_), _) => tr(Text::PlayNextMatch).to_string(),
+            (None, Some(champion)) => trf(Text::WonTournament, &[&bracket.get_names()[champion]]),
+            (None, None) => tr(Text::Finished).to_string()
+        };
+        draw_text(&status, 20., screen_height() - 50., 30., WHITE);
+        draw_text(&trf(Text::BracketHelp, &[&if auto_advance { tr(Text::On) } else { tr(Text::Off) }]), 20., screen_height() - 20., 20., LIGHTGRAY);
+        draw_version_hud();
+        next_frame().await;
+
+        let Some(index) = next else {
+            continue;
+        };
+        let start = is_key_pressed(KeyCode::Enter) || (auto_advance && shown_since.elapsed().as_secs_f32() >= AUTO_ADVANCE_DELAY);
+        if !start {
+            continue;
+        }
+
+        let group = bracket.get_matches()[index].entrants.clone();
+        let mut snake_controllers: Vec<Box<dyn SnakeController>> = group.iter().map(|x| entrants[*x].clone_weak()).collect();
+        connection_screen(&mut snake_controllers, settings.grid_size, &settings.rules).await;
+        sleep(Duration::from_secs_f32(0.5));
+
+        let result = {
+            let seed = settings.seed.map(|x| x.wrapping_add(games_played)); // Replays get a new seed too
+            games_played += 1;
+            let mut game_grid = create_grid(&mut snake_controllers, settings.grid_size, &settings.rules, seed, &settings.colors, sounds);
+            scoreboard.set_ratings(ratings);
+            play_match(&mut game_grid, &group, &settings.options, scoreboard).await
+        };
+
+        ratings.update(&result.standings, &scoreboard.current_keys());
+        if let Err(e) = ratings.save().and(scoreboard.save()) {
+            println!("[ERROR] {}", e);
+        }
+        if !bracket.record(index, &result) {
+            println!("No winner, replay {} of {}", bracket.get_matches()[index].replays, MAX_REPLAYS);
+        }
+
+        if let Some(winner) = result.winner {
+            snake_controllers.iter_mut().for_each(|x| x.send_winner(winner));
+        }
+        sleep(Duration::from_secs_f32(0.3));
+        snake_controllers.iter_mut().for_each(|x| x.disconnect());
+        sleep(Duration::from_secs_f32(0.05));
+
+        shown_since = Instant::now();
+    }
+}
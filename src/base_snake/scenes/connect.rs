@@ -1,41 +1,54 @@
+use std::path::Path;
+
 use macroquad::{prelude::*, ui::{hash, root_ui, widgets::{self, Group}}};
 
-use crate::base_snake::{consts, snake::SnakeController, snake_controller::{keyboard_controller::KeyboardController}};
+use crate::base_snake::{bracket::BracketFormat, config::Config, consts, export::animation::AnimationFormat, rules::GameRules, scoreboard::Scoreboard, snake::SnakeController, snake_controller::{keyboard_controller::{KeyBindings, KeyboardController}, handshake::MatchInfo, tcp_controller::TcpController, websocket_controller::WebSocketController}};
+
+use crate::base_snake::snake_controller::ai_controller::create_ai_controller;
 
-#[cfg(target_os = "linux")]
-use crate::base_snake::snake_controller::ai_controller::UnixSocketController;
+use crate::base_snake::locale::{set_language, tr, trf, Language, Text};
 
-#[cfg(target_os = "windows")]
-use crate::base_snake::snake_controller::ai_controller::PipeController;
+use super::key_bindings::key_bindings_screen;
 
 pub struct GameConfig {
     pub snake_controller_list: Vec<Box<dyn SnakeController>>,
     pub grid_size: (i32, i32),
     pub sandbox: bool,
-    pub snake_draw_mode: bool
-
+    pub snake_draw_mode: bool,
+    pub rules: GameRules,
+    pub position_file: Option<String>, // Saved position to start the sandbox from
+    pub replay_dir: Option<String>, // None if recording is disabled
+    pub replay_file: Option<String>, // Replay to watch instead of playing
+    pub live_export: Option<AnimationFormat>,
+    pub json_export: bool,
+    pub bracket: Option<BracketFormat>, // Play a bracket tournament between the players instead of single games
+    pub scoreboard: Scoreboard,
+    pub seed: Option<u64>, // Of the first game, random if None
+    pub games: Option<u32>, // Until the window is closed if None
+    pub export_dir: String
 }
 
 pub fn draw_player_names(names: &Vec<String>) {
     clear_background(RED);
-    draw_text("Connecting", 20.0, 40.0, 40.0, WHITE);
+    draw_text(tr(Text::Connecting), 20.0, 40.0, 40.0, WHITE);
     names.iter().enumerate().for_each(|(i, x)| {
-            draw_text(&format!("{}: Connected!", x), 60.0, 70.0+30.*i as f32, 30.0, WHITE);
+            draw_text(&trf(Text::Connected, &[x]), 60.0, 70.0+30.*i as f32, 30.0, WHITE);
     });
 
 }
-pub async fn connection_screen(players: &mut Vec<Box<dyn SnakeController>>) {
+pub async fn connection_screen(players: &mut Vec<Box<dyn SnakeController>>, grid_size: (i32, i32), rules: &GameRules) {
 
     clear_background(RED);
 
-    draw_text("Connecting", 20.0, 40.0, 40.0, WHITE);
+    draw_text(tr(Text::Connecting), 20.0, 40.0, 40.0, WHITE);
     
     draw_version_hud();
     next_frame().await;
 
     let mut connected_players: Vec<String> = Vec::new();
-    for (_, player) in players.iter_mut().enumerate(){ 
-        if player.connect() {
+    for (i, player) in players.iter_mut().enumerate(){ 
+        // Snakes get their ids in this order
+        if player.connect(&MatchInfo { snake_id: i as i32, grid_size, rules: rules.clone() }) {
             connected_players.push(player.get_name());
             draw_player_names(&connected_players);
             draw_version_hud();
@@ -43,7 +56,7 @@ pub async fn connection_screen(players: &mut Vec<Box<dyn SnakeController>>) {
         } else {
             println!("Unable to connect {}", player.get_name());
 
-            connected_players.push("<Connection Error>".to_owned());
+            connected_players.push(tr(Text::ConnectionError).to_owned());
             draw_player_names(&connected_players);
             draw_version_hud();
             next_frame().await;
@@ -53,37 +66,47 @@ pub async fn connection_screen(players: &mut Vec<Box<dyn SnakeController>>) {
 
 
 
-pub async  fn add_players() -> GameConfig {
-    let mut snake_controllers: Vec<Box<dyn SnakeController>> = Vec::new(); 
-    let mut current_pipe_index = 0;
+pub async  fn add_players(config: &mut Config) -> GameConfig {
+    // Starts with the roster of the config file
+    let mut snake_controllers: Vec<Box<dyn SnakeController>> = config.create_controllers(&config.players).unwrap_or_default();
+    let mut current_pipe_index = config.players.iter().filter(|x| x.uses_ai_slot()).count();
 
    
     
-    let (mut grid_size_x, mut grid_size_y) = (consts::GRID_SIZE.0.to_string(), consts::GRID_SIZE.1.to_string());
+    let (mut grid_size_x, mut grid_size_y) = (config.grid.width.to_string(), config.grid.height.to_string());
     let mut sandbox = false;
     let mut snake_draw = false;
+    let mut position_file = String::new();
+    let mut record_replays = true;
+    let mut replay_dir = consts::REPLAY_DIR.to_string();
+    let mut replay_file = String::new();
+    let mut watch_replay = false;
+    let mut live_export = 0;
+    let mut json_export = false;
+    let mut bracket_format = 0;
+    let mut start_bracket = false;
+    let mut scoreboard_file = consts::SCOREBOARD_FILE.to_string();
+    let mut scoreboard = Scoreboard::load(Path::new(consts::SCOREBOARD_FILE));
+    let mut edit_keys = false;
+    let mut bot_port = consts::DEFAULT_BOT_PORT.to_string();
+    let mut language = Language::ALL.iter().position(|x| *x == config.language).unwrap_or(0);
 
     loop {
 
         clear_background(RED);
 
-        draw_text("P - Spieler Hinzufügen", 20.0, 50.0, 30.0, WHITE);
+        draw_text(tr(Text::LobbyAddPlayer), 20.0, 50.0, 30.0, WHITE);
 
-        draw_text("O - Ai Hinzufügen", 20.0, 70.0, 30.0, WHITE);
+        draw_text(tr(Text::LobbyAddAi), 20.0, 70.0, 30.0, WHITE);
         
-        draw_text("Drücke <Enter> um zum Starten", 140., 500.0, 40.0, WHITE);
+        draw_text(tr(Text::LobbyStart), 140., 500.0, 40.0, WHITE);
 
         if is_key_pressed(KeyCode::P) {
-            snake_controllers.push(Box::new(KeyboardController::arrows()));
+            snake_controllers.push(Box::new(KeyboardController::new(config.key_profile("arrows").unwrap_or(&KeyBindings::arrows()))));
             println!("Added Player");
         }
         if is_key_pressed(KeyCode::O) {
-            #[cfg(target_os = "windows")]
-            snake_controllers.push(Box::new(PipeController::new(current_pipe_index)));
-
-            #[cfg(target_os = "linux")]
-            snake_controllers.push(Box::new(UnixSocketController::new(current_pipe_index)));
-
+            snake_controllers.push(create_ai_controller(current_pipe_index));
             current_pipe_index += 1;
             println!("Added Ai");
         }
@@ -97,28 +120,79 @@ pub async  fn add_players() -> GameConfig {
             draw_text(&format!("> {}", x.get_name()), 20.0, 90.0 + 20.*i as f32, 20.0, WHITE);
         });
 
-        widgets::Window::new(hash!(), vec2(870., 30.), vec2(300., 300.))
-            .label("Settings")
+        draw_text(tr(Text::Scoreboard), 450.0, 50.0, 30.0, WHITE);
+        scoreboard.get_entries().iter().take(18).enumerate().for_each(|(i, x)| {
+            draw_text(&format!("{}  {}/{}", x.name, x.wins, x.games), 450.0, 70.0 + 20.*i as f32, 20.0, WHITE);
+        });
+
+        widgets::Window::new(hash!(), vec2(870., 30.), vec2(300., 400.))
+            .label(tr(Text::Settings))
             .ui(&mut *root_ui(), |ui| {
 
                                
-                ui.label(None, "Grid Size");
-                ui.input_text(hash!(), "Grid X Size", &mut grid_size_x);
-                ui.input_text(hash!(), "Grid Y Size", &mut grid_size_y);
+                ui.combo_box(hash!(), tr(Text::Language), &Language::ALL.map(|x| x.label()), &mut language);
 
-                ui.tree_node(hash!(), "Debug", |ui| {
-                    if ui.button(None, "Sandbox") {
-                        sandbox = true;
+                ui.label(None, tr(Text::GridSize));
+                ui.input_text(hash!(), tr(Text::GridWidth), &mut grid_size_x);
+                ui.input_text(hash!(), tr(Text::GridHeight), &mut grid_size_y);
+
+                ui.tree_node(hash!(), tr(Text::Players), |ui| {
+                    for (name, keys) in &config.keys {
+                        if ui.button(None, trf(Text::AddPlayerProfile, &[name]).as_str()) {
+                            snake_controllers.push(Box::new(KeyboardController::new(keys)));
+                        }
                     }
-                    if ui.button(None, "Eval Test") {
-                        snake_draw = true;
+                    ui.input_text(hash!(), tr(Text::BotPort), &mut bot_port);
+                    let add_tcp = ui.button(None, tr(Text::AddTcpBot));
+                    let add_websocket = ui.button(None, tr(Text::AddWebSocketBot));
+                    match bot_port.trim().parse() {
+                        Ok(port) if add_tcp => snake_controllers.push(Box::new(TcpController::new(port))),
+                        Ok(port) if add_websocket => snake_controllers.push(Box::new(WebSocketController::new(port, config.websocket.clone()))),
+                        Err(_) if add_tcp || add_websocket => println!("Invalid Bot Port!"),
+                        _ => {}
                     }
-                    if ui.button(None, "Add Player (Arrow)") {
-                        snake_controllers.push(Box::new(KeyboardController::arrows()));
+                    if ui.button(None, tr(Text::KeyBindings)) {
+                        edit_keys = true;
                     }
+                });
 
-                    if ui.button(None, "Add Player (WASD)") {
-                        snake_controllers.push(Box::new(KeyboardController::wasd()));
+                ui.checkbox(hash!(), tr(Text::RecordReplays), &mut record_replays);
+                ui.input_text(hash!(), tr(Text::ReplayDir), &mut replay_dir);
+
+                ui.combo_box(hash!(), tr(Text::LiveExport), &[tr(Text::Off), AnimationFormat::Gif.label(), AnimationFormat::PngFrames.label()], &mut live_export);
+                ui.checkbox(hash!(), tr(Text::ExportMatchesJson), &mut json_export);
+
+                ui.combo_box(hash!(), tr(Text::Bracket), &[BracketFormat::swiss(0).name(), BracketFormat::SingleElimination.name(), BracketFormat::DoubleElimination.name()], &mut bracket_format);
+                if ui.button(None, tr(Text::StartBracket)) && snake_controllers.len() > 1 {
+                    start_bracket = true;
+                }
+
+                ui.input_text(hash!(), tr(Text::ScoreboardFile), &mut scoreboard_file);
+                if ui.button(None, tr(Text::LoadScoreboard)) && !scoreboard_file.trim().is_empty() {
+                    scoreboard = Scoreboard::load(Path::new(scoreboard_file.trim()));
+                }
+                if ui.button(None, tr(Text::ExportScoreboard)) {
+                    scoreboard.export_stats().save_session(Path::new(consts::EXPORT_DIR), "scoreboard");
+                }
+                if ui.button(None, tr(Text::ResetScoreboard)) {
+                    scoreboard.reset();
+                    if let Err(e) = scoreboard.save() {
+                        println!("[ERROR] {}", e);
+                    }
+                }
+
+                ui.input_text(hash!(), tr(Text::ReplayFile), &mut replay_file);
+                if ui.button(None, tr(Text::WatchReplay)) && !replay_file.trim().is_empty() {
+                    watch_replay = true;
+                }
+
+                ui.tree_node(hash!(), tr(Text::Debug), |ui| {
+                    if ui.button(None, tr(Text::Sandbox)) {
+                        sandbox = true;
+                    }
+                    ui.input_text(hash!(), tr(Text::PositionFile), &mut position_file);
+                    if ui.button(None, tr(Text::EvalTest)) {
+                        snake_draw = true;
                     }
 
                 });
@@ -126,27 +200,56 @@ pub async  fn add_players() -> GameConfig {
         draw_version_hud();
         next_frame().await;        
 
-        if sandbox || snake_draw {
+        if Language::ALL[language] != config.language {
+            config.language = Language::ALL[language];
+            set_language(config.language);
+        }
+
+        if edit_keys {
+            key_bindings_screen(config).await;
+            edit_keys = false;
+        }
+        if sandbox || snake_draw || watch_replay || start_bracket {
             break; // Sandbox button click
         }
     }
 
-    let mut parsed_grid_size_x = grid_size_x.parse();
-    let mut parsed_grid_size_y = grid_size_y.parse();
-    if parsed_grid_size_x.is_err() {
+    let parsed_grid_size_x = grid_size_x.parse().unwrap_or_else(|_| {
         println!("Invalid Grid Size!");
-        parsed_grid_size_x = consts::GRID_SIZE.0.parse();
-    }
-    if parsed_grid_size_y.is_err() {
+        config.grid.width
+    });
+    let parsed_grid_size_y = grid_size_y.parse().unwrap_or_else(|_| {
         println!("Invalid Grid Size!");
-        parsed_grid_size_y = consts::GRID_SIZE.1.parse();
-    }
+        config.grid.height
+    });
+
+    let bracket = match bracket_format {
+        _ if !start_bracket => None,
+        0 => Some(BracketFormat::swiss(snake_controllers.len())),
+        1 => Some(BracketFormat::SingleElimination),
+        _ => Some(BracketFormat::DoubleElimination)
+    };
 
     GameConfig { 
         snake_controller_list: snake_controllers,
-        grid_size: (parsed_grid_size_x.unwrap(), parsed_grid_size_y.unwrap()),
+        grid_size: (parsed_grid_size_x, parsed_grid_size_y),
         sandbox,
-        snake_draw_mode: snake_draw
+        snake_draw_mode: snake_draw,
+        rules: config.rules.clone(),
+        position_file: if position_file.trim().is_empty() { None } else { Some(position_file.trim().to_string()) },
+        replay_dir: if record_replays && !replay_dir.trim().is_empty() { Some(replay_dir.trim().to_string()) } else { None },
+        replay_file: if watch_replay { Some(replay_file.trim().to_string()) } else { None },
+        live_export: match live_export {
+            1 => Some(AnimationFormat::Gif),
+            2 => Some(AnimationFormat::PngFrames),
+            _ => None
+        },
+        json_export,
+        bracket,
+        scoreboard,
+        seed: None,
+        games: None,
+        export_dir: consts::EXPORT_DIR.to_string()
     }
 }
 
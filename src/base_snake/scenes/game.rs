@@ -0,0 +1,203 @@
+use std::{path::{Path, PathBuf}, sync::Arc, thread::sleep, time::{Duration, Instant, SystemTime, UNIX_EPOCH}};
+
+use macroquad::{audio::{load_sound, play_sound, PlaySoundParams, Sound}, prelude::*};
+
+use crate::base_snake::{export::{animation::{AnimationFormat, AnimationWriter}, match_json::{export_path, MatchExporter}, raster::{render_grid, DEFAULT_SQUARE_SIZE}}, locale::{tr, trf, Text}, match_result::MatchResult, replay::ReplayRecorder, rules::GameRules, scoreboard::Scoreboard, snake::SnakeController, snakegrid::SnakeGrid};
+
+pub struct GameSounds {
+    pub coin: Arc<Sound>,
+    pub explosion: Arc<Sound>,
+    pub volume: f32
+}
+
+impl GameSounds {
+    pub async fn load(volume: f32) -> Self {
+        Self {
+            coin: Arc::new(load_sound("assets/pickupCoin.wav").await.unwrap()),
+            explosion: Arc::new(load_sound("assets/explosion.wav").await.unwrap()),
+            volume
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GameOptions {
+    pub sandbox: bool,
+    pub replay_dir: Option<String>, // None if recording is disabled
+    pub live_export: Option<AnimationFormat>,
+    pub json_export: bool,
+    pub export_dir: PathBuf // Live exports and match JSON
+}
+
+pub fn create_grid<'a>(snake_controllers: &'a mut [Box<dyn SnakeController>], grid_size: (i32, i32), rules: &GameRules, seed: Option<u64>, colors: &[(u8, u8, u8)], sounds: &GameSounds) -> SnakeGrid<'a> {
+    let mut game_grid: SnakeGrid = SnakeGrid::new(grid_size.0, grid_size.1);
+    game_grid.rules = rules.clone();
+    game_grid.set_colors(colors);
+    if let Some(seed) = seed {
+        game_grid.reseed(seed);
+    }
+    game_grid.register_on_food_handler(Box::new({
+        let sound = Arc::clone(&sounds.coin);
+        let volume = sounds.volume;
+
+        move || play_sound(
+            &sound,
+            PlaySoundParams {
+                looped: false,
+                volume,
+            },
+        )
+    }));
+    game_grid.register_on_death(Box::new({
+        let sound = Arc::clone(&sounds.explosion);
+        let volume = sounds.volume;
+
+        move || play_sound(
+            &sound,
+            PlaySoundParams {
+                looped: false,
+                volume,
+            },
+        )
+    }));
+
+    snake_controllers.iter_mut().for_each(|x| { game_grid.add_snake(x.as_mut()); } );
+
+    game_grid.do_place_food();
+    game_grid
+}
+
+pub async fn play_match(game_grid: &mut SnakeGrid<'_>, slots: &[usize], options: &GameOptions, scoreboard: &mut Scoreboard) -> MatchResult {
+    // Runs one match on a grid from create_grid until somebody won. The caller sends the winner and disconnects the controllers.
+    // slots are the roster slots of the snakes in id order, they key the scoreboard.
+    let sandbox = options.sandbox;
+    let rules = game_grid.rules.clone();
+
+    game_grid.start_game();  // Initialize all the Snakes (Spawnpoints)
+    scoreboard.initalize(game_grid.get_all_snake_refs(), slots); // Draw the initial scoreboard now because the names and ids are unknown beforehand
+    game_grid.draw();
+
+    let mut recorder = match &options.replay_dir {
+        Some(dir) if !sandbox => ReplayRecorder::create(Path::new(dir), game_grid)
+            .map_err(|e| println!("[ERROR] {}", e))
+            .ok(),
+        _ => None
+    };
+
+    let mut animation = options.live_export.and_then(|format| {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|x| x.as_secs()).unwrap_or(0);
+        let name = if format == AnimationFormat::Gif { format!("live-{}.gif", timestamp) } else { format!("live-{}", timestamp) };
+        AnimationWriter::create(&options.export_dir.join(name), format, Duration::from_secs_f32(rules.tick_duration))
+            .map_err(|e| println!("[ERROR] {}", e))
+            .ok()
+    });
+    if let Some(Err(e)) = animation.as_mut().map(|x| x.add_frame(&render_grid(&game_grid.snapshot(), &game_grid.get_info_dict(), DEFAULT_SQUARE_SIZE))) {
+        println!("[ERROR] {}", e);
+        animation = None;
+    }
+    let mut exporter = if options.json_export && !sandbox { Some(MatchExporter::new(game_grid)) } else { None };
+
+    sleep(Duration::from_secs_f32(0.2));
+
+    let mut winner = None;
+    let start_time = Instant::now();
+
+    loop {
+        clear_background(BLACK);
+
+        game_grid.update_input();
+        game_grid.tick();
+
+        if let Some(Err(e)) = recorder.as_mut().map(|x| x.record_tick(game_grid)) {
+            println!("[ERROR] {}", e);
+            recorder = None;
+        }
+        if let Some(Err(e)) = animation.as_mut().map(|x| x.add_frame(&render_grid(&game_grid.snapshot(), &game_grid.get_info_dict(), DEFAULT_SQUARE_SIZE))) {
+            println!("[ERROR] {}", e);
+            animation = None;
+        }
+        if let Some(exporter) = exporter.as_mut() {
+            exporter.record_tick(game_grid);
+        }
+        scoreboard.record_tick(game_grid);
+
+        game_grid.draw();
+        scoreboard.draw_widget(game_grid.get_info_dict());
+
+        next_frame().await;
+
+
+        match game_grid.check_end() {
+            Ok(snake_data) => {
+                if !sandbox {
+                    game_grid.draw();
+                    draw_end_message(&trf(Text::Won, &[&snake_data.name])).await;
+                    winner = Some(snake_data.clone());
+                    break
+                }
+            },
+            Err(1) if (start_time.elapsed() < Duration::from_secs(rules.time_limit) || sandbox) => {} // Ongoing
+            _ => {  // Err 0 or timeout
+                if let Some(best_snake) = game_grid.get_all_snake_refs().iter().filter(|x| x.alive).max_by_key(|item| item.size) {
+                    if !sandbox {
+                        game_grid.draw();
+                        draw_end_message(&trf(Text::Won, &[&best_snake.name])).await;
+                        winner = Some(best_snake.clone());
+                    }
+                    break;
+                }
+                draw_end_message(tr(Text::Tie)).await;
+                break;
+            },
+        }
+
+        game_grid.send_gamestate();
+        let now = Instant::now();
+        while now.elapsed() < Duration::from_secs_f32(rules.tick_duration) {
+            game_grid.update_input(); // Read answers as they arrive, the direction is only used on the next tick
+            game_grid.draw();
+            scoreboard.draw_widget(game_grid.get_info_dict());
+            next_frame().await;
+        }
+    }
+
+    if let Some(recorder) = recorder {
+        match recorder.finish(winner.as_ref().map(|x| x.id)) {
+            Ok(path) => println!("Saved replay to {}", path.display()),
+            Err(e) => println!("[ERROR] {}", e),
+        }
+    }
+
+    if let Some(animation) = animation {
+        match animation.finish() {
+            Ok((path, frame_count)) => println!("Exported {} frames to {}", frame_count, path.display()),
+            Err(e) => println!("[ERROR] {}", e),
+        }
+    }
+
+    let result = MatchResult::from_grid(game_grid, start_time.elapsed().as_secs_f32());
+
+    if let Some(exporter) = exporter {
+        let path = export_path(&options.export_dir, game_grid.get_seed());
+        match exporter.finish(result.clone()).save(&path) {
+            Ok(()) => println!("Exported match to {}", path.display()),
+            Err(e) => println!("[ERROR] {}", e),
+        }
+    }
+
+    if !sandbox {
+        scoreboard.add_result(&result);
+    }
+    if let Some(winner) = &winner {
+        scoreboard.add_win(winner);
+    }
+
+    result
+}
+
+pub async fn draw_end_message(message: &str){
+    let (height, width) = (screen_height(), screen_width());
+    draw_text(message, width*0.2, height*0.4, 60.0, WHITE);
+    next_frame().await;
+    sleep(Duration::from_secs(2));
+}
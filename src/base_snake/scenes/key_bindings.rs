@@ -0,0 +1,129 @@
+use macroquad::{prelude::*, ui::{hash, root_ui, widgets}};
+
+use crate::base_snake::{config::Config, locale::{direction_name, tr, trf, Text}, snake::Direction, snake_controller::keyboard_controller::{key_name, KeyBindings, BINDABLE_KEYS}};
+
+use super::connect::draw_version_hud;
+
+const DIRECTIONS: [Direction; 4] = [Direction::UP, Direction::DOWN, Direction::LEFT, Direction::RIGHT];
+
+struct Remapping {
+    profile: String,
+    keys: KeyBindings,
+    index: usize // Into DIRECTIONS, the direction waiting for a key
+}
+
+fn save(config: &Config, message: &mut String) {
+    if let Err(e) = config.save_key_profiles() {
+        println!("[ERROR] {}", e);
+        *message = e;
+    }
+}
+
+pub async fn key_bindings_screen(config: &mut Config) {
+    // Changes are saved right away
+    let mut selected = 0;
+    let mut remapping: Option<Remapping> = None;
+    let mut new_profile = String::new();
+    let mut message = String::new();
+
+    loop {
+        let names: Vec<String> = config.keys.keys().cloned().collect();
+        selected = selected.min(names.len().saturating_sub(1));
+
+        if let Some(remap) = &mut remapping {
+            if is_key_pressed(KeyCode::Escape) {
+                message = trf(Text::RemapCancelled, &[&remap.profile]);
+                remapping = None;
+            } else if let Some(key) = get_last_key_pressed() {
+                if !BINDABLE_KEYS.contains(&key) {
+                    message = trf(Text::KeyNotBindable, &[&key_name(key)]);
+                } else if DIRECTIONS[..remap.index].iter().any(|x| remap.keys.get(*x) == key) {
+                    message = trf(Text::KeyAlreadyUsed, &[&key_name(key)]);
+                } else {
+                    remap.keys.set(DIRECTIONS[remap.index], key);
+                    remap.index += 1;
+                    message.clear();
+                    if remap.index == DIRECTIONS.len() {
+                        config.set_key_profile(&remap.profile, remap.keys);
+                        message = trf(Text::ProfileSaved, &[&remap.profile]);
+                        save(config, &mut message);
+                        remapping = None;
+                    }
+                }
+            }
+        } else {
+            if is_key_pressed(KeyCode::Escape) {
+                break;
+            }
+            if is_key_pressed(KeyCode::Up) {
+                selected = selected.saturating_sub(1);
+            }
+            if is_key_pressed(KeyCode::Down) {
+                selected = (selected + 1).min(names.len().saturating_sub(1));
+            }
+            if is_key_pressed(KeyCode::Enter) && !names.is_empty() {
+                remapping = Some(Remapping { profile: names[selected].clone(), keys: config.keys[&names[selected]], index: 0 });
+            }
+        }
+
+        clear_background(RED);
+        draw_text(tr(Text::KeyBindings), 20.0, 50.0, 40.0, WHITE);
+        draw_text(tr(Text::KeyBindingsHelp), 20.0, 80.0, 20.0, WHITE);
+
+        config.keys.iter().enumerate().for_each(|(i, (name, keys))| {
+            let marker = if i == selected { ">" } else { " " };
+            draw_text(&format!("{} {}: {}", marker, name, keys.label()), 20.0, 120.0 + 25.*i as f32, 25.0, WHITE);
+        });
+
+        let status_y = 140.0 + 25.*names.len() as f32;
+        if let Some(remap) = &remapping {
+            let direction = direction_name(DIRECTIONS[remap.index]);
+            draw_text(&trf(Text::PressKeyFor, &[&remap.profile, &direction]), 20.0, status_y, 30.0, YELLOW);
+        }
+        draw_text(&message, 20.0, status_y + 35., 25.0, WHITE);
+
+        let mut add_profile = false;
+        let mut delete_profile = false;
+        let mut back = false;
+        widgets::Window::new(hash!(), vec2(870., 30.), vec2(300., 180.))
+            .label(tr(Text::Profiles))
+            .ui(&mut root_ui(), |ui| {
+                ui.input_text(hash!(), tr(Text::Name), &mut new_profile);
+                add_profile = ui.button(None, tr(Text::AddProfile));
+                delete_profile = ui.button(None, tr(Text::DeleteSelected));
+                back = ui.button(None, tr(Text::Back));
+            });
+
+        if add_profile && remapping.is_none() {
+            let name = new_profile.trim().to_lowercase();
+            if name.is_empty() || name == "ai" || name.contains(',') {
+                message = trf(Text::InvalidProfileName, &[&new_profile.trim()]);
+            } else if config.keys.contains_key(&name) {
+                message = trf(Text::ProfileExists, &[&name]);
+            } else {
+                // Starts as a copy of the selected profile
+                let keys = names.get(selected).map(|x| config.keys[x]).unwrap_or(KeyBindings::arrows());
+                remapping = Some(Remapping { profile: name, keys, index: 0 });
+                new_profile.clear();
+            }
+        }
+        if delete_profile && remapping.is_none() {
+            if let Some(name) = names.get(selected) {
+                if Config::default().keys.contains_key(name) {
+                    message = trf(Text::DefaultProfile, &[name]);
+                } else {
+                    config.delete_key_profile(name);
+                    message = trf(Text::ProfileDeleted, &[name]);
+                    save(config, &mut message);
+                }
+            }
+        }
+
+        draw_version_hud();
+        next_frame().await;
+
+        if back {
+            break;
+        }
+    }
+}
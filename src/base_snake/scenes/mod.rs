@@ -1,2 +1,6 @@
 pub mod connect;
-pub mod snake_draw;
\ No newline at end of file
+pub mod snake_draw;
+pub mod replay_viewer;
+pub mod game;
+pub mod bracket;
+pub mod key_bindings;
\ No newline at end of file
@@ -0,0 +1,122 @@
+use std::{cell::Cell, path::{Path, PathBuf}, rc::Rc, thread::sleep, time::{Duration, Instant}};
+
+use macroquad::{prelude::*, ui::{hash, root_ui, widgets}};
+
+use crate::base_snake::{consts, locale::{tr, trf, Text}, match_result::MatchResult, export::{animation::{export_replay, AnimationFormat}, match_json::export_replay_json, raster::DEFAULT_SQUARE_SIZE}, replay::Replay, scoreboard::Scoreboard};
+
+use super::connect::draw_version_hud;
+
+const SPEEDS: [f32; 6] = [0.25, 0.5, 1., 2., 4., 8.];
+
+pub async fn replay_viewer(path: &Path) {
+    let replay = match Replay::load(path) {
+        Ok(replay) => replay,
+        Err(e) => {
+            println!("[ERROR] {}", e);
+            clear_background(RED);
+            draw_text(&e, 20.0, 40.0, 30.0, WHITE);
+            next_frame().await;
+            sleep(Duration::from_secs(2));
+            return;
+        }
+    };
+
+    let cursor = Rc::new(Cell::new(0));
+    let mut controllers = replay.create_controllers(&cursor);
+    let snake_count = controllers.len();
+    let mut game_grid = replay.create_grid(&mut controllers);
+
+    // Simulate the match once, scrubbing only restores the snapshot of a tick
+    let frames = replay.simulate();
+    game_grid.restore(frames.last().unwrap());
+    let last_frame = frames.len() - 1;
+
+    let mut scoreboard = Scoreboard::new(snake_count as i32);
+    scoreboard.initalize(game_grid.get_all_snake_refs(), &(0..snake_count).collect::<Vec<usize>>());
+    scoreboard.add_result(&MatchResult::from_grid(&game_grid, 0.));
+    if let Some(winner) = game_grid.get_all_snake_refs().iter().find(|x| Some(x.id) == replay.winner) {
+        scoreboard.add_win(winner);
+    }
+
+    let name = path.file_stem().map(|x| x.to_string_lossy().to_string()).unwrap_or("replay".to_string());
+    let mut position = 0;
+    let mut displayed = None;
+    let mut playing = true;
+    let mut speed_index = 2;
+    let mut last_step = Instant::now();
+
+    loop {
+        if is_key_pressed(KeyCode::Escape) {
+            break;
+        }
+        if is_key_pressed(KeyCode::Space) {
+            playing = !playing;
+            last_step = Instant::now();
+        }
+        if is_key_pressed(KeyCode::Right) {
+            position = (position + 1).min(last_frame);
+            playing = false;
+        }
+        if is_key_pressed(KeyCode::Left) {
+            position = position.saturating_sub(1);
+            playing = false;
+        }
+        if is_key_pressed(KeyCode::Up) {
+            speed_index = (speed_index + 1).min(SPEEDS.len() - 1);
+        }
+        if is_key_pressed(KeyCode::Down) {
+            speed_index = speed_index.saturating_sub(1);
+        }
+        if is_key_pressed(KeyCode::J) {
+            let output = PathBuf::from(consts::EXPORT_DIR).join(format!("{}.json", name));
+            match export_replay_json(&replay, &output) {
+                Ok(()) => println!("Exported match to {}", output.display()),
+                Err(e) => println!("[ERROR] {}", e),
+            }
+        }
+        for (key, format) in [(KeyCode::G, AnimationFormat::Gif), (KeyCode::P, AnimationFormat::PngFrames)] {
+            if is_key_pressed(key) {
+                let output = PathBuf::from(consts::EXPORT_DIR).join(if format == AnimationFormat::Gif { format!("{}.gif", name) } else { name.clone() });
+                match export_replay(&replay, &output, format, DEFAULT_SQUARE_SIZE) {
+                    Ok((output, frame_count)) => println!("Exported {} frames as {} to {}", frame_count, format.label(), output.display()),
+                    Err(e) => println!("[ERROR] {}", e),
+                }
+            }
+        }
+
+        if playing && last_step.elapsed().as_secs_f32() >= replay.header.rules.tick_duration / SPEEDS[speed_index] {
+            last_step = Instant::now();
+            position = (position + 1).min(last_frame);
+            if position == last_frame {
+                playing = false;
+            }
+        }
+
+        if displayed != Some(position) {
+            cursor.set(position);
+            game_grid.restore(&frames[position]);
+            displayed = Some(position);
+        }
+
+        clear_background(BLACK);
+        game_grid.draw();
+        scoreboard.draw_widget(game_grid.get_info_dict());
+
+        let mut timeline = position as f32;
+        widgets::Window::new(hash!(), vec2(620., screen_height() - 110.), vec2(570., 100.))
+            .label(tr(Text::Replay))
+            .ui(&mut root_ui(), |ui| {
+                ui.label(None, &trf(Text::ReplayStatus, &[&position, &last_frame, &SPEEDS[speed_index], &if playing { tr(Text::Playing) } else { tr(Text::Paused) }]));
+                ui.slider(hash!(), tr(Text::Timeline), 0f32..(last_frame.max(1) as f32), &mut timeline);
+                ui.label(None, tr(Text::ReplayHelp));
+            });
+        let scrubbed = (timeline.round() as usize).min(last_frame);
+        if scrubbed != position {
+            position = scrubbed;
+            playing = false;
+        }
+
+        draw_version_hud();
+        next_frame().await;
+    }
+}
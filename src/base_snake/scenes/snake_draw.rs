@@ -1,8 +1,11 @@
-use std::{thread::sleep, time::{Duration, Instant}};
+use std::{path::PathBuf, thread::sleep, time::{Duration, Instant, SystemTime, UNIX_EPOCH}};
 
 use macroquad::{input::{is_key_pressed, is_mouse_button_down, is_mouse_button_pressed, mouse_position, MouseButton}, miniquad::graphics, window::next_frame};
 
-use crate::base_snake::snakegrid::{SnakeGrid, GRID_OFFSET_X, GRID_OFFSET_Y};
+use crate::base_snake::{snakegrid::{SnakeGrid, GRID_OFFSET_X, GRID_OFFSET_Y}, snapshot::GridSnapshot};
+
+const MAX_HISTORY: usize = 1000;
+const POSITIONS_DIR: &str = "positions";
 
 fn get_clicked_square(game_grid: &SnakeGrid<'_>) -> (i32, i32) {
     let mut pos: (f32, f32) = mouse_position();
@@ -12,21 +15,32 @@ fn get_clicked_square(game_grid: &SnakeGrid<'_>) -> (i32, i32) {
     ((pos.0 / game_grid.total_square_size) as i32, (pos.1 / game_grid.total_square_size) as i32)
 }
 
+fn push_history(history: &mut Vec<GridSnapshot>, game_grid: &SnakeGrid<'_>) {
+    if history.len() >= MAX_HISTORY {
+        history.remove(0);
+    }
+    history.push(game_grid.snapshot());
+}
+
 pub async fn snake_draw(mut game_grid: SnakeGrid<'_>) {
     game_grid.send_gamestate();
+    let mut history: Vec<GridSnapshot> = Vec::new(); // Every change pushes the position before it
     
     loop {
         if is_mouse_button_pressed(MouseButton::Left) {
+            push_history(&mut history, &game_grid);
             let (x,y) = get_clicked_square(&game_grid);
             game_grid.set_square((x + y*game_grid.width) as usize, Some(0));
             game_grid.send_gamestate();
         }
         if is_mouse_button_pressed(MouseButton::Right) {
+            push_history(&mut history, &game_grid);
             let (x,y) = get_clicked_square(&game_grid);
             game_grid.set_square((x + y*game_grid.width) as usize, Some(1));
             game_grid.send_gamestate();
         }
         if is_mouse_button_pressed(MouseButton::Middle) {
+            push_history(&mut history, &game_grid);
             let (x,y) = get_clicked_square(&game_grid);
             game_grid.set_square((x + y*game_grid.width) as usize, None);
             game_grid.send_gamestate();
@@ -35,16 +49,35 @@ pub async fn snake_draw(mut game_grid: SnakeGrid<'_>) {
         game_grid.update_input();
         
         if is_key_pressed(macroquad::input::KeyCode::N) {
+            push_history(&mut history, &game_grid);
             game_grid.tick();
             game_grid.send_gamestate();
         }
+
+        if is_key_pressed(macroquad::input::KeyCode::B) {
+            // Step back and let the bots decide again from the same position
+            if let Some(snapshot) = history.pop() {
+                game_grid.restore(&snapshot);
+                game_grid.send_gamestate();
+            }
+        }
         
         if is_key_pressed(macroquad::input::KeyCode::R) {
             game_grid.reconnect();
             game_grid.send_gamestate();
         }
 
+        if is_key_pressed(macroquad::input::KeyCode::S) {
+            let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|x| x.as_secs()).unwrap_or(0);
+            let path = PathBuf::from(POSITIONS_DIR).join(format!("position-{}.json", timestamp));
+            match game_grid.save_state().save(&path) {
+                Ok(()) => println!("Saved position to {}", path.display()),
+                Err(e) => println!("[ERROR] {}", e),
+            }
+        }
+
         if is_key_pressed(macroquad::input::KeyCode::C) {
+            push_history(&mut history, &game_grid);
             game_grid.clear();
         }
     
@@ -1,9 +1,11 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fs, path::{Path, PathBuf}};
 use macroquad::prelude::*;
 use macroquad::ui::{Skin, Ui};
 use macroquad::{math::{vec2, Vec2}, ui::{hash, root_ui, widgets::{self, Group}}};
 
-use super::snake::{PlayerInfo, SnakeRefData};
+use serde::{Deserialize, Serialize};
+
+use super::{consts, locale::{tr, trf, Text}, event_log::EventLog, snakegrid::SnakeGrid, export::stats::{ScoreRow, StatsExport}, match_result::{MatchResult, RosterKeys}, stats::BotStats, rating::RatingTable, snake::{PlayerInfo, SnakeRefData}};
 
 fn with_skin<F>(ui: &mut Ui, skin: &Skin, func: F)
 where
@@ -14,37 +16,147 @@ where
     ui.pop_skin();
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScoreEntry {
+    pub name: String,
+    pub color: (u8, u8, u8),
+    pub wins: i32,
+    pub games: i32,
+    #[serde(default)]
+    pub stats: BotStats
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct Scoreboard {
-    scoretable: HashMap<i32, (SnakeRefData, i32)>,
+    scoretable: HashMap<String, ScoreEntry>, // Keyed by bot name, see RosterKeys
+    #[serde(skip)]
+    keys: RosterKeys, // Of this session
+    #[serde(skip)]
+    current: HashMap<i32, String>, // Snake id of the current match -> scoretable key
+    #[serde(skip)]
     default_position: Vec2,
-    current_display: Option<i32> // Id of the displayed snake
+    #[serde(skip)]
+    current_display: Option<i32>, // Id of the displayed snake
+    #[serde(skip)]
+    ratings: HashMap<String, f32>, // Same keys as the scoretable, empty if ratings are not shown
+    #[serde(skip)]
+    path: Option<PathBuf>, // Not persisted if None
+    #[serde(skip)]
+    matches: Vec<MatchResult>, // Results of this session
+    #[serde(skip)]
+    log: EventLog, // Of the current match
+    #[serde(skip)]
+    export_dir: Option<PathBuf> // consts::EXPORT_DIR if None
 }
 
+const LOG_HEIGHT: f32 = 200.;
+
 impl Scoreboard {
     pub fn new(len_snakes: i32) -> Self {
-        Self { scoretable: HashMap::with_capacity(len_snakes as usize), default_position: vec2(620., len_snakes as f32 * 30.), current_display: None }
+        Self { scoretable: HashMap::with_capacity(len_snakes as usize), default_position: vec2(620., len_snakes as f32 * 30.), ..Default::default() }
+    }
+
+    pub fn load(path: &Path) -> Self {
+        // A missing or broken file starts an empty scoreboard at the same path
+        let mut scoreboard = match fs::read_to_string(path) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                println!("[ERROR] Invalid scoreboard file {}: {}", path.display(), e);
+                Scoreboard::default()
+            }),
+            Err(_) => Scoreboard::default()
+        };
+        scoreboard.path = Some(path.to_path_buf());
+        scoreboard
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let json = serde_json::to_string_pretty(self).map_err(|e| format!("Unable to serialize scoreboard: {}", e))?;
+        fs::write(path, json).map_err(|e| format!("Unable to write {}: {}", path.display(), e))
+    }
+
+    pub fn set_export_dir(&mut self, directory: &Path) {
+        self.export_dir = Some(directory.to_path_buf());
+    }
+
+    pub fn reset(&mut self) {
+        self.scoretable.clear();
+        self.matches.clear(); // Exported with the scoreboard, so they are reset with it
+        self.current.clear();
+        self.current_display = None;
+    }
+
+    pub fn export_stats(&self) -> StatsExport {
+        let rows = self.get_entries().into_iter().map(|x| ScoreRow {
+            rating: self.ratings.get(&x.name).copied(),
+            name: x.name,
+            games: x.games.max(0) as u32,
+            wins: x.wins.max(0) as u32,
+            stats: x.stats
+        }).collect();
+        StatsExport::new(rows, self.matches.clone())
+    }
+
+    pub fn get_entries(&self) -> Vec<ScoreEntry> {
+        let mut entries: Vec<ScoreEntry> = self.scoretable.values().cloned().collect();
+        entries.sort_by(|a, b| b.wins.cmp(&a.wins).then(a.games.cmp(&b.games)).then(a.name.cmp(&b.name)));
+        entries
     }
 }
 
 impl Scoreboard {
-    pub fn initalize(&mut self, snake_refs: Vec<SnakeRefData>) {
-        if !self.scoretable.is_empty() { // Dont reinitalize
-            return;
+    pub fn initalize(&mut self, snake_refs: Vec<SnakeRefData>, slots: &[usize]) {
+        // Called at the start of every match, the ids are only valid for this match. slots[id] is the roster slot of a snake.
+        self.current.clear();
+        self.log.clear();
+        self.default_position = vec2(620., snake_refs.len() as f32 * 30.);
+        for snake in &snake_refs {
+            let name = self.keys.assign(slots.get(snake.id as usize).copied().unwrap_or(snake.id as usize), &snake.name);
+            let entry = self.scoretable.entry(name.clone()).or_insert(ScoreEntry { name: name.clone(), color: snake.color, wins: 0, games: 0, stats: BotStats::default() });
+            entry.color = snake.color;
+            self.current.insert(snake.id, name);
         }
+    }
 
-        snake_refs.iter().for_each(|snake| { self.scoretable.insert(snake.id, (snake.clone(), 0)); });
+    pub fn current_keys(&self) -> Vec<String> {
+        // Scoretable keys of the current match by snake id, the ratings use the same ones
+        (0..self.current.len() as i32).map(|id| self.current.get(&id).cloned().unwrap_or_default()).collect()
     }
 
     pub fn add_win(&mut self, snake: &SnakeRefData) {
-        let default = (snake.clone(), 0);
-        let mut val = self.scoretable.get(&snake.id).unwrap_or(&default).clone();
-        val.1 += 1;
-        self.scoretable.insert(snake.id,  val.clone());
+        if let Some(entry) = self.current.get(&snake.id).and_then(|x| self.scoretable.get_mut(x)) {
+            entry.wins += 1;
+        }
+    }
+
+    pub fn record_tick(&mut self, game_grid: &mut SnakeGrid) {
+        self.log.record_tick(game_grid);
+    }
+
+    pub fn add_result(&mut self, result: &MatchResult) {
+        // Every snake of the current match played one more game, the winner is added with add_win
+        self.matches.push(result.clone());
+        for standing in &result.standings {
+            if let Some(entry) = self.current.get(&standing.id).and_then(|x| self.scoretable.get_mut(x)) {
+                entry.games += 1;
+                entry.stats.add(&BotStats::from_standing(standing, result));
+            }
+        }
+    }
+
+    pub fn set_ratings(&mut self, ratings: &RatingTable) {
+        self.ratings = ratings.get_all().iter().map(|(name, rating)| (name.clone(), rating.rating)).collect();
     }
 
     pub fn draw_widget(&mut self, snake_infos: HashMap<i32, PlayerInfo>) {
-        let scoretable = self.scoretable.clone();
+        let mut scoretable: Vec<(i32, ScoreEntry)> = self.current.iter()
+            .filter_map(|(id, name)| self.scoretable.get(name).map(|x| (*id, x.clone())))
+            .collect();
+        scoretable.sort_by_key(|x| x.0);
+        let ratings = self.ratings.clone();
+        let mut export = false;
 
         let mut skin = Scoreboard::get_style();
         skin.window_style = root_ui()
@@ -54,15 +166,16 @@ impl Scoreboard {
                             .margin(RectOffset::new(10.0, 10.0, 10.0, 10.0))
                             .build();
 
-        let sum: i32 = self.scoretable.values().map(|x| x.1).sum();
-
         
         with_skin(&mut root_ui(), &skin, |ui| {
             widgets::Window::new(hash!(), self.default_position, vec2(350., 1200.))
-                .label("Scoreboard")
+                .label(tr(Text::Scoreboard))
                 .titlebar(true)
                 .ui(ui, |ui| {
-                for (id, (snake, score)) in scoretable {
+                if ui.button(None, tr(Text::ExportStats)) {
+                    export = true;
+                }
+                for (id, snake) in scoretable {
                         Group::new(hash!("scores"), Vec2::new(300., 80.)).ui(ui, |ui| {
                             let mut cell_style = skin.clone();
                             cell_style.label_style = ui
@@ -75,9 +188,11 @@ impl Scoreboard {
                                 ui.label(Vec2::new(2., 2.), &snake.name);
                             });
 
-                            ui.label(Vec2::new(2., 22.), &format!("{}/{}", score, sum));
+                            ui.label(Vec2::new(2., 22.), &format!("{}/{}", snake.wins, snake.games));
+                            if let Some(rating) = ratings.get(&snake.name) {
+                                ui.label(Vec2::new(80., 22.), &trf(Text::Rating, &[&format!("{:.0}", rating)]));
+                            }
 
-                            // Todo: Events to display a message log onclick
                             cell_style.button_style = ui
                                 .style_builder()
                                 .background_margin(RectOffset::new(8.0, 8.0, 8.0, 0.0))
@@ -90,9 +205,8 @@ impl Scoreboard {
                                 .build();
                             
                             with_skin(ui, &cell_style, |ui: &mut Ui| {
-                                if ui.button(Vec2::new(185., 40.), "Show Details") {
-                                    println!("A{}", snake.id);
-                                    self.current_display = Some(snake.id);
+                                if ui.button(Vec2::new(185., 40.), tr(Text::ShowDetails)) {
+                                    self.current_display = Some(id);
                                 }
                             });
                         });
@@ -101,19 +215,23 @@ impl Scoreboard {
                 });
         });
        
+        if export {
+            let directory = self.export_dir.clone().unwrap_or(PathBuf::from(consts::EXPORT_DIR));
+            self.export_stats().save_session(&directory, "session");
+        }
+
         if let Some(snake_id) = self.current_display {
-            let snake_opt = self.scoretable.get(&snake_id);
-            let info_opt = snake_infos.get(&snake_id);
+            let snake_opt = self.current.get(&snake_id).and_then(|x| self.scoretable.get(x));
             if snake_opt.is_none() {
                 println!("[ERROR] Trying to display unknown snake");
                 return;
             }
-            if info_opt.is_none() {
-                return;
-            }
             
             let snake = snake_opt.unwrap();
-            let infos = snake_infos.get(&snake_id).unwrap();
+            // Live infos of the controller first, then the stats of all recorded games
+            let mut lines = snake_infos.get(&snake_id).map(|x| x.info_lines.clone()).unwrap_or_default();
+            lines.extend(snake.stats.lines());
+            let log = self.log.get(snake_id);
 
             let mut widget_style = skin.clone();
             widget_style.window_style = root_ui()
@@ -125,24 +243,31 @@ impl Scoreboard {
 
             root_ui().push_skin(&widget_style);
 
-            widgets::Window::new(hash!(), vec2(self.default_position.x+250., self.default_position.y), vec2(280., 140.))
-            .label(&format!("Details for {}", snake.0.name))
+            widgets::Window::new(hash!(), vec2(self.default_position.x+250., self.default_position.y), vec2(330., 70. + lines.len() as f32 * 20. + LOG_HEIGHT))
+            .label(&trf(Text::DetailsFor, &[&snake.name]))
             .titlebar(true)
             .ui(&mut *root_ui(), |ui| {
                 let mut cell_style = skin.clone();
                 cell_style.label_style = ui
                     .style_builder()
-                    .text_color(Color::from_rgba(snake.0.color.0, snake.0.color.1, snake.0.color.2, 255))
+                    .text_color(Color::from_rgba(snake.color.0, snake.color.1, snake.color.2, 255))
                     .font_size(20)
                     .build();
 
                 ui.push_skin(&cell_style);
-                ui.label(Vec2::new(2., 2.), &snake.0.name);
+                ui.label(Vec2::new(2., 2.), &snake.name);
                 ui.pop_skin();
 
-                for (i, info_line) in infos.info_lines.iter().enumerate() {
+                for (i, info_line) in lines.iter().enumerate() {
                     ui.label(Vec2::new(2., 42. + i as f32 * 20.), &info_line);
                 }
+
+                // Newest first
+                Group::new(hash!("event_log"), vec2(320., LOG_HEIGHT)).position(vec2(2., 47. + lines.len() as f32 * 20.)).ui(ui, |ui| {
+                    for entry in log.iter().rev() {
+                        ui.label(None, &format!("{:>4}  {}", entry.tick, entry.message));
+                    }
+                });
             });
 
             root_ui().pop_skin();
@@ -181,3 +306,52 @@ impl Scoreboard {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use crate::base_snake::match_result::Standing;
+
+    use super::*;
+
+    fn snake(id: i32, name: &str) -> SnakeRefData {
+        SnakeRefData { id, name: name.to_string(), alive: true, size: 1, tiles: Vec::new(), color: (0, 0, 0) }
+    }
+
+    #[test]
+    fn bots_with_the_same_name_find_their_ratings() {
+        let mut scoreboard = Scoreboard::new(2);
+        scoreboard.initalize(vec![snake(0, "Bot"), snake(1, "Bot")], &[0, 1]);
+
+        let standing = |id: i32, placement: u32| Standing { placement, id, name: "Bot".to_string(), size: 1, alive: placement == 1, death_tick: None, death_cause: None, missed_inputs: 0, average_latency: None };
+        let mut ratings = RatingTable::default();
+        ratings.update(&[standing(1, 1), standing(0, 2)], &scoreboard.current_keys());
+        scoreboard.set_ratings(&ratings);
+
+        let rows = scoreboard.export_stats().scoreboard;
+        let rating = |name: &str| rows.iter().find(|x| x.name == name).and_then(|x| x.rating);
+        assert!(rating("Bot").is_some_and(|x| x < 1500.));
+        assert!(rating("Bot (2)").is_some_and(|x| x > 1500.));
+    }
+
+    #[test]
+    fn keys_stay_with_the_roster_slot() {
+        let mut scoreboard = Scoreboard::new(3);
+        scoreboard.initalize(vec![snake(0, "A"), snake(1, "A"), snake(2, "A (2)")], &[0, 1, 2]);
+        assert_eq!(scoreboard.current_keys(), ["A", "A (2)", "A (2) (2)"]);
+
+        // Slot 0 was disqualified, so the ids of the others moved down
+        scoreboard.initalize(vec![snake(0, "A"), snake(1, "A (2)")], &[1, 2]);
+        assert_eq!(scoreboard.current_keys(), ["A (2)", "A (2) (2)"]);
+    }
+
+    #[test]
+    fn reset_clears_the_match_history() {
+        let mut scoreboard = Scoreboard::new(2);
+        scoreboard.initalize(vec![snake(0, "A"), snake(1, "B")], &[0, 1]);
+        scoreboard.add_result(&MatchResult { seed: 0, ticks: 1, duration: 0., winner: None, standings: Vec::new() });
+        assert_eq!(scoreboard.export_stats().matches.len(), 1);
+        scoreboard.reset();
+        assert!(scoreboard.export_stats().matches.is_empty());
+        assert!(scoreboard.get_entries().is_empty());
+    }
+}
@@ -1,15 +1,38 @@
-use std::fmt::{Debug, Display, Write};
+use std::{fmt::{Debug, Display, Write}, time::Duration};
 
 use macroquad::color::Color;
+use serde::{Deserialize, Serialize};
 
-use super::object::Tile;
+use super::{object::Tile, snake_controller::handshake::MatchInfo, snapshot::SnakeSnapshot};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PlayerInfo  {
     pub marked_cells: Vec<u16>,
     pub info_lines: Vec<String>,
 }
 
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ControllerStats {
+    pub missed_inputs: u32, // Ticks without an answer to the last gamestate
+    pub responses: u32,
+    pub total_latency: Duration // Between sending a gamestate and receiving the answer, summed over all responses
+}
+
+impl ControllerStats {
+    pub fn average_latency(&self) -> Option<Duration> {
+        if self.responses == 0 {
+            return None;
+        }
+        Some(self.total_latency / self.responses)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ControllerLog {
+    Rejected(String), // Packet the controller could not handle
+    Message(String) // Log line sent by the bot
+}
+
 pub struct SnakeData<'a> {
     pub height: u16,
     pub width: u16,
@@ -48,48 +71,23 @@ impl Display for SnakeResponseData {
 }
 
 impl<'a> SnakeData<'a> {
-    pub fn encode(&self, snake_id: i32) -> Vec<u8> {
-        let mut data: Vec<u8> = Vec::new();
-
-        // Packet ID
-        data.push(0);        
-
-        // push the height, width and snakes attrivutes to the u8 vec
-        data.extend(&self.height.to_le_bytes());
-        data.extend(&self.width.to_le_bytes());
-        data.extend((snake_id as u16).to_le_bytes());
-
-        // Encode the grid by pushing each tile's ID
-        for tile in self.grid {
-            data.extend((tile.get_tile_id() as i16).to_le_bytes());
-        }
-
-        // Encode the number of snakes (as u32 for safety)
-        let snake_count = self.snakes.len() as u16;
-        data.extend(snake_count.to_le_bytes());
-
-        // Encode each snake
-        for snake in &self.snakes {
-            // Encode the snake ID (i32 -> 4 bytes, in little-endian order)
-            data.extend(&(snake.id as i16).to_le_bytes());
-
-            // Encode the snake's name length and content
-            let name_bytes = snake.name.as_bytes();
-            let name_length = name_bytes.len() as u16; // Use u16 for name length
-            data.extend(name_length.to_le_bytes());
-            data.extend(name_bytes);
-            data.extend((snake.size as u16).to_le_bytes());
-
-            data.extend((snake.tiles.len() as u16).to_le_bytes());
-            for tile in snake.tiles.iter() {
-                data.extend((*tile as u16).to_le_bytes());
-            }
-
-            // Encode the alive status (as a single byte: 1 for true, 0 for false)
-            data.push(if snake.alive { 1 } else { 0 });
-        }
-
-        data
+    pub fn to_json(&self, snake_id: i32) -> String {
+        // Same content as the gamestate packet for bots using JSON, the grid holds the tile ids
+        let snakes: Vec<serde_json::Value> = self.snakes.iter().map(|snake| serde_json::json!({
+            "id": snake.id,
+            "name": snake.name,
+            "size": snake.size,
+            "tiles": snake.tiles,
+            "alive": snake.alive
+        })).collect();
+        serde_json::json!({
+            "type": "gamestate",
+            "height": self.height,
+            "width": self.width,
+            "me": snake_id,
+            "grid": self.grid.iter().map(|x| x.get_tile_id()).collect::<Vec<i32>>(),
+            "snakes": snakes
+        }).to_string()
     }
 }
 
@@ -178,13 +176,13 @@ impl SnakeResponseData {
 }
 
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum SnakeState {
     ALIVE,
     DEAD
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum Direction {
     UP,
     DOWN,
@@ -214,7 +212,7 @@ impl Direction {
     }
 }
 
-#[derive(Eq, Hash, PartialEq, Clone, Debug)]
+#[derive(Eq, Hash, PartialEq, Clone, Debug, Serialize, Deserialize)]
 pub struct SnakeRefData {
     pub id: i32,
     pub name: String,
@@ -253,6 +251,9 @@ impl<'a> Snake<'a> {
     pub fn update_controller(&mut self) {
         self.controller.update();
     }
+    pub fn report_move(&mut self, direction: Direction) {
+        self.controller.on_move(direction);
+    }
     pub fn get_name(&self) -> String {
         self.controller.get_name()
     }
@@ -322,22 +323,41 @@ impl<'a> Snake<'a> {
     pub fn get_info(&self) -> Option<PlayerInfo> {
         self.controller.get_info()
     }
+    pub fn get_stats(&self) -> Option<ControllerStats> {
+        self.controller.get_stats()
+    }
+    pub fn take_controller_log(&mut self) -> Vec<ControllerLog> {
+        self.controller.take_log()
+    }
     pub fn disconnect_controller(&mut self) {
         self.controller.disconnect()
     }
-    pub fn connect_controller(&mut self) -> bool {
-        self.controller.connect()
+    pub fn connect_controller(&mut self, info: &MatchInfo) -> bool {
+        self.controller.connect(info)
+    }
+    pub fn snapshot(&self) -> SnakeSnapshot {
+        SnakeSnapshot { id: self.id, name: self.get_name(), tiles: self.tiles.clone(), max_size: self.max_size, state: self.state, color: self.color }
+    }
+    pub fn restore(&mut self, snapshot: &SnakeSnapshot) {
+        self.tiles = snapshot.tiles.clone();
+        self.max_size = snapshot.max_size;
+        self.state = snapshot.state;
+        self.color = snapshot.color;
     }
 }
 
 pub trait SnakeController : Debug {
     fn report_data(&mut self, _data: SnakeData, _snake_id: i32) {}
     fn send_winner(&mut self, winner: i32) {}
-    fn connect(&mut self) -> bool { true } // Only used for ai_controllers
+    fn connect(&mut self, _info: &MatchInfo) -> bool { true } // Only used for ai_controllers
     fn disconnect(&self) {}
     fn get_name(&self) -> String;
     fn update(&mut self) {}
     fn next_direction(&self) -> Direction;
+    fn on_move(&mut self, _direction: Direction) {} // Called after every tick with the direction the snake moved in
     fn clone_weak(&self) -> Box<dyn SnakeController>;
     fn get_info(&self) -> Option<PlayerInfo> { None } 
+    fn get_stats(&self) -> Option<ControllerStats> { None } // Only tracked by ai_controllers
+    fn take_log(&mut self) -> Vec<ControllerLog> { Vec::new() } // Log lines since the last call
+    fn is_disqualified(&self) -> bool { false } // Bots that crashed too often are left out of the next games
 }
@@ -1,7 +1,12 @@
 use std::ptr;
-use std::sync::Arc;
 use std::fmt::Debug;
-use crate::base_snake::snake::{Direction, PlayerInfo, SnakeController, SnakeData};
+use std::time::{Duration, Instant};
+use crate::base_snake::locale::{direction_name, tr, trf, Text};
+use crate::base_snake::snake::{ControllerLog, ControllerStats, Direction, PlayerInfo, SnakeController, SnakeData};
+use super::bot_process::{BotCommand, BotProcess, SharedBotProcess};
+use super::framing::{encode_packet, FrameReader};
+use super::handshake::{MatchInfo, STREAM_FEATURES};
+use super::packets::{ClientPacket, ServerPacket};
 
 #[cfg(target_os = "windows")]
 mod platform_imports {
@@ -11,16 +16,99 @@ mod platform_imports {
     pub use windows::Win32::System::Pipes::{ConnectNamedPipe, CreateNamedPipeA, PeekNamedPipe, PIPE_READMODE_MESSAGE, PIPE_TYPE_MESSAGE, PIPE_UNLIMITED_INSTANCES, PIPE_WAIT};
     pub use windows::Win32::System::IO::{GetOverlappedResult, OVERLAPPED}; 
     pub use windows::core::s;
+    pub use std::sync::Arc;
 
 }
 #[cfg(target_os = "linux")]
 mod platform_imports {
     pub use std::os::unix::net::{UnixListener, UnixStream};
-    pub use std::io::{Read, Write};
+    pub use std::io::Write;
+    pub use super::super::framing::{read_available, write_available};
 }
 
 use platform_imports::*;
 
+const MAX_LOG_LINES: usize = 100; // Kept until the game takes them
+
+pub(super) fn push_log(log: &mut Vec<ControllerLog>, line: ControllerLog) {
+    if log.len() >= MAX_LOG_LINES {
+        log.remove(0);
+    }
+    log.push(line);
+}
+
+pub fn create_ai_controller(index: usize) -> Box<dyn SnakeController> {
+    // The controller for the ai player slot on this platform
+    #[cfg(target_os = "windows")]
+    return Box::new(PipeController::new(index));
+
+    #[cfg(target_os = "linux")]
+    return Box::new(UnixSocketController::new(index as i32));
+}
+
+pub fn create_bot_controller(index: usize, command: &BotCommand) -> Box<dyn SnakeController> {
+    // Like create_ai_controller, but the bot process is started by the game
+    let bot = Some(BotProcess::shared(command.clone(), index));
+
+    #[cfg(target_os = "windows")]
+    return Box::new(PipeController { bot, ..PipeController::new(index) });
+
+    #[cfg(target_os = "linux")]
+    return Box::new(UnixSocketController { bot, ..UnixSocketController::new(index as i32) });
+}
+
+pub fn socket_path(index: usize) -> String {
+    // Where the bot of the ai player slot connects to
+    #[cfg(target_os = "windows")]
+    return format!(r"\\.\pipe\SnakePipe{}", index + 1);
+
+    #[cfg(target_os = "linux")]
+    return format!("/tmp/multisnake{}.sock", index + 1);
+}
+
+pub(super) fn read_response(bytes: &[u8], direction: &mut Direction, marked_cells: &mut Vec<u16>, log: &mut Vec<ControllerLog>) {
+    // Packets sent by a bot in answer to a gamestate
+    let (packets, error) = ClientPacket::decode_all(bytes);
+    for packet in packets {
+        match packet {
+            ClientPacket::Turn(x) => *direction = x,
+            ClientPacket::MarkedCells(cells) => *marked_cells = cells,
+            ClientPacket::Log(text) => push_log(log, ControllerLog::Message(text)),
+            ClientPacket::Hello(_) => push_log(log, ControllerLog::Rejected("Hello after the handshake".to_string())),
+        }
+    }
+    if let Some(e) = error {
+        push_log(log, ControllerLog::Rejected(e.to_string()));
+    }
+}
+
+pub(super) fn read_packets(bytes: &[u8], frames: Option<&mut FrameReader>, direction: &mut Direction, marked_cells: &mut Vec<u16>, log: &mut Vec<ControllerLog>) {
+    // Legacy bots without frames send their packets as one block
+    let Some(frames) = frames else {
+        read_response(bytes, direction, marked_cells, log);
+        return;
+    };
+    frames.push(bytes);
+    loop {
+        match frames.next_packet() {
+            Ok(Some(packet)) => read_response(&packet, direction, marked_cells, log),
+            Ok(None) => break,
+            Err(e) => {
+                push_log(log, ControllerLog::Rejected(e));
+                break;
+            }
+        }
+    }
+}
+
+fn bot_info_lines(bot: &Option<SharedBotProcess>) -> Vec<String> {
+    match bot.as_ref().map(|x| x.lock().unwrap()) {
+        Some(bot) if bot.is_disqualified() => vec![trf(Text::BotDisqualified, &[&bot.get_crashes()])],
+        Some(bot) => vec![trf(Text::BotCrashes, &[&bot.get_crashes()])],
+        None => Vec::new()
+    }
+}
+
 // WINDOWS IMPLEMENTATION
 
 #[cfg(target_os = "windows")]
@@ -32,6 +120,12 @@ pub struct PipeController {
     missed_inputs: i32,
     marked_cells: Vec<u16>,
     pending_writes: Vec<(Arc<Vec<u8>>, OVERLAPPED)>,
+    frames: Option<FrameReader>, // None for legacy bots without frames
+    awaiting_since: Option<Instant>, // Gamestate sent but not answered yet
+    responses: u32,
+    total_latency: Duration,
+    log: Vec<ControllerLog>,
+    bot: Option<SharedBotProcess>, // Started by the game if set
 }
 
 #[cfg(target_os = "windows")]
@@ -53,7 +147,7 @@ impl PipeController {
             s!(r"\\.\pipe\SnakePipe12"),
         ];
 
-        Self { direction: Direction::RIGHT, pipe: None, pipe_name: pipe_names[pipe_index], ai_name: "Unknown Ai".to_string(), missed_inputs: 0, marked_cells: Vec::new(), pending_writes: Vec::new() }
+        Self { direction: Direction::RIGHT, pipe: None, pipe_name: pipe_names[pipe_index], ai_name: "Unknown Ai".to_string(), missed_inputs: 0, marked_cells: Vec::new(), pending_writes: Vec::new(), frames: None, awaiting_since: None, responses: 0, total_latency: Duration::ZERO, log: Vec::new(), bot: None }
     }
 
     fn is_connected(&self) -> bool {
@@ -70,7 +164,6 @@ impl PipeController {
                     &mut bytes_transferred,
                     false,
                 );
-                println!("Result: {:?}", result);
                 if result.is_err() {
                     return true;
                 }
@@ -86,7 +179,7 @@ impl PipeController {
 #[cfg(target_os = "windows")]
 impl SnakeController for PipeController {
     fn clone_weak(&self) -> Box<(dyn SnakeController)> {
-        Box::new(PipeController { pipe: None, pipe_name: self.pipe_name, direction: self.direction, ai_name: self.ai_name.clone(), missed_inputs: 0, marked_cells: Vec::new(), pending_writes: Vec::new() })
+        Box::new(PipeController { pipe: None, pipe_name: self.pipe_name, direction: self.direction, ai_name: self.ai_name.clone(), missed_inputs: 0, marked_cells: Vec::new(), pending_writes: Vec::new(), frames: None, awaiting_since: None, responses: 0, total_latency: Duration::ZERO, log: Vec::new(), bot: self.bot.clone() })
     }
 
     fn next_direction(&self) -> Direction {
@@ -110,10 +203,12 @@ impl SnakeController for PipeController {
             )
         };
         if peek_result.is_err() || available_bytes <= 0 {
-            self.missed_inputs += 1;
-            println!("{}", self.missed_inputs);
             return;
         }
+        if let Some(sent) = self.awaiting_since.take() {
+            self.total_latency += sent.elapsed();
+            self.responses += 1;
+        }
 
         let mut buffer = vec![0u8; available_bytes as usize];
         let mut bytes_read = 0;
@@ -121,35 +216,8 @@ impl SnakeController for PipeController {
         let _ = unsafe {
             ReadFile(self.pipe.unwrap(), Some(&mut buffer), Some(&mut bytes_read), None)
         };
-        println!(" B {:?}", buffer);
-        while !buffer.is_empty() {
-            let a = buffer.remove(0);
-            println!("PacketId {}", a);
-            match a {
-                10 => { self.direction = Direction::UP; println!("UP")},
-                11 => { self.direction = Direction::DOWN; println!("DOWN")},
-                12 => { self.direction = Direction::LEFT; println!("LEFT")},
-                13 => { self.direction = Direction::RIGHT; println!("RIGHT")},
-                20 if buffer.len() >= 2 => {
-                    println!("InfoPacket");
-                    let bytes: Vec<u8> = buffer.drain(0..2).collect();
-                    let length = u16::from_le_bytes([bytes[0], bytes[1]]);
-                    println!("C {}", length);
-                    self.marked_cells = (0..length).map(|i| {
-                        let bytes: Vec<u8> = buffer.drain(0..2).collect();
-                        u16::from_le_bytes([bytes[0], bytes[1]])
-                    }).collect();
-                    //println!("A {:?}", self.marked_cells);
-                    
-                }
-                _ => println!("Invalid Message"),    
-            }
-            
-            if *(buffer.first().unwrap_or(&0)) == 0 {
-                break;
-            }
-        }
-
+        buffer.truncate(bytes_read as usize);
+        read_packets(&buffer, self.frames.as_mut(), &mut self.direction, &mut self.marked_cells, &mut self.log);
     }
     fn report_data(&mut self, data: SnakeData, snake_id: i32) {
         if !self.is_connected() {
@@ -164,7 +232,7 @@ impl SnakeController for PipeController {
         };
 
 
-        let buffer = Arc::new(data.encode(snake_id).to_vec());
+        let buffer = Arc::new(encode_packet(ServerPacket::GameState { data: &data, snake_id }, self.frames.is_some()));
         let buffer_ptr = buffer.as_ptr(); 
         let mut overlapped = OVERLAPPED::default();
         unsafe {
@@ -173,6 +241,9 @@ impl SnakeController for PipeController {
         // Storing the buffer for it to not be dropped
         self.check_write_completion();
         self.pending_writes.push((buffer, overlapped));
+        if self.awaiting_since.replace(Instant::now()).is_some() {
+            self.missed_inputs += 1; // The previous gamestate was never answered
+        }
 
     }
     fn send_winner(&mut self, winner_id: i32) {
@@ -180,9 +251,7 @@ impl SnakeController for PipeController {
             return;
         }
 
-        let mut buffer = Vec::from([2]); // PacketId + WinnerId
-        buffer.extend((winner_id as i32).to_le_bytes());
-        let buff_ptr = Arc::new(buffer);
+        let buff_ptr = Arc::new(encode_packet(ServerPacket::Winner(winner_id), self.frames.is_some()));
 
         let mut overlapped = OVERLAPPED::default();
         unsafe {
@@ -192,9 +261,15 @@ impl SnakeController for PipeController {
         self.pending_writes.push((buff_ptr, overlapped));
 
     }
-    fn connect(&mut self) -> bool {
+    fn connect(&mut self, info: &MatchInfo) -> bool {
+        // A bot that crashes while the pipe waits for it is only noticed on the next connect
+        if let Some(bot) = &self.bot {
+            if !bot.lock().unwrap().ensure_running() {
+                return false;
+            }
+        }
         unsafe {
-            let pipe = CreateNamedPipeA(
+            let pipe = match CreateNamedPipeA(
                 self.pipe_name,
                 PIPE_ACCESS_DUPLEX | FILE_FLAG_OVERLAPPED,
                 PIPE_TYPE_MESSAGE | PIPE_READMODE_MESSAGE | PIPE_WAIT,
@@ -203,9 +278,13 @@ impl SnakeController for PipeController {
                 5044,
                 0,
                 Some(ptr::null_mut()),
-            ).unwrap_or_else(|e| {
-                panic!("CreateNamedPipeA Failed With Error: {e}");
-            });
+            ) {
+                Ok(x) => x,
+                Err(e) => {
+                    println!("[ERROR] Unable to create pipe {:?}: {}", self.pipe_name.to_string(), e);
+                    return false;
+                }
+            };
 
             println!("Waiting for connection {:?}", self.pipe_name.to_string());
             if ConnectNamedPipe(pipe, None).is_err() {
@@ -221,7 +300,26 @@ impl SnakeController for PipeController {
         unsafe {
             let _ = ReadFile(self.pipe.unwrap(), Some(&mut buffer), Some(&mut bytes_read), None);
         };
-        self.ai_name = String::from_utf8_lossy(&buffer[..bytes_read as usize]).to_string();
+        // Pipe messages always hold the whole hello
+        let mut frames = FrameReader::default();
+        let (hello, framed) = match frames.read_hello(&buffer[..bytes_read as usize]).and_then(|x| x.map_or_else(|| frames.finish_hello(), Ok)) {
+            Ok(x) => x,
+            Err(e) => {
+                println!("[ERROR] {}", e);
+                return false;
+            }
+        };
+        if let Some(welcome) = hello.answer(info, &STREAM_FEATURES) {
+            let buffer = Arc::new(encode_packet(ServerPacket::Welcome(&welcome), framed));
+            let mut overlapped = OVERLAPPED::default();
+            unsafe {
+                let _ = WriteFile(self.pipe.unwrap(), Some(std::slice::from_raw_parts(buffer.as_ptr(), buffer.len())), Some(&mut (buffer.len() as u32)), Some(&mut overlapped));
+            };
+            self.pending_writes.push((buffer, overlapped));
+        }
+        println!("{} connected ({})", hello.name(), hello.describe());
+        self.ai_name = hello.name();
+        self.frames = framed.then_some(frames);
  
         true
        
@@ -237,14 +335,25 @@ impl SnakeController for PipeController {
         self.ai_name.clone()
     }
     fn get_info(&self) -> Option<PlayerInfo> {
+        let mut info_lines = vec![
+            trf(Text::MissedInputs, &[&self.missed_inputs]),
+            trf(Text::CurrentDirection, &[&direction_name(self.direction)])
+        ];
+        info_lines.extend(bot_info_lines(&self.bot));
         Some(PlayerInfo {
             marked_cells: self.marked_cells.clone(),
-            info_lines: vec![
-                format!("Missed Inputs: {}", self.missed_inputs),
-                format!("Current Direction: {}", self.direction.to_string())
-            ]
+            info_lines
         })
     }
+    fn is_disqualified(&self) -> bool {
+        self.bot.as_ref().is_some_and(|x| x.lock().unwrap().is_disqualified())
+    }
+    fn get_stats(&self) -> Option<ControllerStats> {
+        Some(ControllerStats { missed_inputs: self.missed_inputs as u32, responses: self.responses, total_latency: self.total_latency })
+    }
+    fn take_log(&mut self) -> Vec<ControllerLog> {
+        std::mem::take(&mut self.log)
+    }
 }
 
 #[cfg(target_os = "windows")]
@@ -263,20 +372,40 @@ pub struct UnixSocketController {
     marked_cells: Vec<u16>,
     socket_id: i32,
     stream: Option<UnixStream>,
-    listener: Option<UnixListener>
-
-
+    frames: Option<FrameReader>, // None for legacy bots without frames
+    outbox: Vec<u8>, // Sent once the bot reads again
+    listener: Option<UnixListener>,
+    awaiting_since: Option<Instant>, // Gamestate sent but not answered yet
+    responses: u32,
+    total_latency: Duration,
+    log: Vec<ControllerLog>,
+    bot: Option<SharedBotProcess> // Started by the game if set
 }
 
 #[cfg(target_os = "linux")]
 impl UnixSocketController {
     pub fn new(socket_id: i32) -> Self {
-        Self { direction: Direction::RIGHT, socket_id, ai_name: "Unknown Ai".to_string(), missed_inputs: 0, marked_cells: Vec::new(), stream: None, listener: None }
+        Self { direction: Direction::RIGHT, socket_id, ai_name: "Unknown Ai".to_string(), missed_inputs: 0, marked_cells: Vec::new(), stream: None, frames: None, outbox: Vec::new(), listener: None, awaiting_since: None, responses: 0, total_latency: Duration::ZERO, log: Vec::new(), bot: None }
     }
 
     pub fn is_connected(&self) -> bool {
         self.stream.is_some()
     }
+
+    fn send(&mut self, packet: ServerPacket) {
+        self.outbox.extend(encode_packet(packet, self.frames.is_some()));
+        self.flush();
+    }
+
+    fn flush(&mut self) {
+        let Some(stream) = &mut self.stream else {
+            return;
+        };
+        if let Err(e) = write_available(stream, &mut self.outbox) {
+            println!("[ERROR] Unable to send to {}: {}", self.ai_name, e);
+            self.outbox.clear();
+        }
+    }
 }
 
 #[cfg(target_os = "linux")]
@@ -286,45 +415,79 @@ impl SnakeController for UnixSocketController {
             return;
         }
 
-        let buffer = Arc::new(data.encode(snake_id).to_vec());
-        self.stream.as_ref().unwrap().write_all(buffer.as_slice());
+        self.send(ServerPacket::GameState { data: &data, snake_id });
+        if self.awaiting_since.replace(Instant::now()).is_some() {
+            self.missed_inputs += 1; // The previous gamestate was never answered
+        }
     }
     fn send_winner(&mut self, winner_id: i32) {
         if !self.is_connected() {
             return;
         }
 
-        let mut buffer = Vec::from([2]); // PacketId + WinnerId
-        buffer.extend((winner_id as i32).to_le_bytes());
-
-        self.stream.as_ref().unwrap().write_all(buffer.as_slice());
+        self.send(ServerPacket::Winner(winner_id));
     }
 
-    fn connect(&mut self) -> bool { 
-        let socket_path = format!("/tmp/multisnake{}.sock", self.socket_id+1);
+    fn connect(&mut self, info: &MatchInfo) -> bool { 
+        let socket_path = socket_path(self.socket_id as usize);
         std::fs::remove_file(&socket_path).ok();
 
-        self.listener = UnixListener::bind(&socket_path).ok();
-        let (mut stream, _addr) = self.listener.as_ref().unwrap().accept().unwrap();
-
-        self.stream = Some(stream);
-
-        println!(" {:?}", self.stream);
-
-        // Read Name
-        let mut buffer = [0u8; 216];
-
-        if let Some(stream) = &mut self.stream {
+        let listener = match UnixListener::bind(&socket_path) {
+            Ok(x) => self.listener.insert(x),
+            Err(e) => {
+                println!("[ERROR] Unable to listen on {}: {}", socket_path, e);
+                self.listener = None;
+                return false;
+            }
+        };
+        if let Some(bot) = &self.bot {
+            if !bot.lock().unwrap().ensure_running() {
+                return false;
+            }
+            listener.set_nonblocking(true).ok(); // To notice crashes while waiting
+        }
 
-            stream.set_nonblocking(false);
-            println!("Waiting");
-            let bytes_read = stream.read(&mut buffer).unwrap();
-            println!("Connection {}", bytes_read);
+        let stream = loop {
+            match listener.accept() {
+                Ok((stream, _addr)) => break stream,
+                Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                    if let Some(bot) = &self.bot {
+                        if !bot.lock().unwrap().ensure_running() {
+                            return false;
+                        }
+                    }
+                    std::thread::sleep(Duration::from_millis(10));
+                }
+                Err(e) => {
+                    println!("[ERROR] Unable to accept {}: {}", socket_path, e);
+                    return false;
+                }
+            }
+        };
 
-            self.ai_name = String::from_utf8_lossy(&buffer[..bytes_read as usize]).to_string();
-            
-            stream.set_nonblocking(true);
+        // The bot starts with a hello or its name
+        let mut stream = stream;
+        let mut frames = FrameReader::default();
+        stream.set_nonblocking(false).ok();
+        stream.set_read_timeout(Some(Duration::from_secs(5))).ok();
+        let (hello, framed) = match frames.read_hello_from(&mut stream) {
+            Ok(x) => x,
+            Err(e) => {
+                println!("[ERROR] {}: {}", socket_path, e);
+                return false;
+            }
+        };
+        if let Some(welcome) = hello.answer(info, &STREAM_FEATURES) {
+            stream.write_all(&encode_packet(ServerPacket::Welcome(&welcome), framed)).ok();
         }
+        stream.set_read_timeout(None).ok();
+        stream.set_nonblocking(true).ok();
+
+        println!("{} connected ({})", hello.name(), hello.describe());
+        self.ai_name = hello.name();
+        self.frames = framed.then_some(frames);
+        self.outbox.clear();
+        self.stream = Some(stream);
 
         true
 
@@ -335,61 +498,56 @@ impl SnakeController for UnixSocketController {
             return;
         }
 
-        let mut buffer = vec![0u8; 256];
-        match self.stream.as_ref().unwrap().read(&mut buffer) {
-            Ok(n) => {
-                while !buffer.is_empty() {
-                    let a = buffer.remove(0);
-                    println!("PacketId {}", a);
-                    match a {
-                        10 => { self.direction = Direction::UP; println!("UP")},
-                        11 => { self.direction = Direction::DOWN; println!("DOWN")},
-                        12 => { self.direction = Direction::LEFT; println!("LEFT")},
-                        13 => { self.direction = Direction::RIGHT; println!("RIGHT")},
-                        20 if buffer.len() >= 2 => {
-                            println!("InfoPacket");
-                            let bytes: Vec<u8> = buffer.drain(0..2).collect();
-                            let length = u16::from_le_bytes([bytes[0], bytes[1]]);
-                            println!("C {}", length);
-                            self.marked_cells = (0..length).map(|i| {
-                                let bytes: Vec<u8> = buffer.drain(0..2).collect();
-                                u16::from_le_bytes([bytes[0], bytes[1]])
-                            }).collect();
-                            //println!("A {:?}", self.marked_cells);
-                            
-                        }
-                        _ => println!("Invalid Message"),    
-                    }
-                    
-                    if *(buffer.first().unwrap_or(&0)) == 0 {
-                        break;
-                    }
-                }
-            }
-            Err (ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+        self.flush();
+        let Some(stream) = &mut self.stream else {
+            return;
+        };
+        let (bytes, closed) = match read_available(stream) {
+            Ok(x) => x,
+            Err(e) => {
+                println!("[ERROR] Unable to read from {}: {}", self.ai_name, e);
                 return;
             }
-            Err(e) => {
-                println!("Some error reading socket!");
+        };
+        if !bytes.is_empty() {
+            if let Some(sent) = self.awaiting_since.take() {
+                self.total_latency += sent.elapsed();
+                self.responses += 1;
+            }
+            read_packets(&bytes, self.frames.as_mut(), &mut self.direction, &mut self.marked_cells, &mut self.log);
+        }
+        if closed { // Closed by the bot
+            self.stream = None;
+            if self.bot.as_ref().is_some_and(|x| x.lock().unwrap().check_crash()) {
+                push_log(&mut self.log, ControllerLog::Message(tr(Text::LogBotCrashed).to_string()));
             }
         }
-        println!(" B {:?}", buffer);
-
     }
 
     fn clone_weak(&self) -> Box<dyn SnakeController> {
-        let mut newObj = UnixSocketController { direction: self.direction, socket_id: self.socket_id, ai_name: self.ai_name.clone(), missed_inputs: 0, marked_cells: Vec::new(), stream: None, listener: None };
+        let mut newObj = UnixSocketController { direction: self.direction, socket_id: self.socket_id, ai_name: self.ai_name.clone(), missed_inputs: 0, marked_cells: Vec::new(), stream: None, frames: None, outbox: Vec::new(), listener: None, awaiting_since: None, responses: 0, total_latency: Duration::ZERO, log: Vec::new(), bot: self.bot.clone() };
         Box::new(newObj)
     }
     fn get_info(&self) -> Option<PlayerInfo> {
+        let mut info_lines = vec![
+            trf(Text::MissedInputs, &[&self.missed_inputs]),
+            trf(Text::CurrentDirection, &[&direction_name(self.direction)])
+        ];
+        info_lines.extend(bot_info_lines(&self.bot));
         Some(PlayerInfo {
             marked_cells: self.marked_cells.clone(),
-            info_lines: vec![
-                format!("Missed Inputs: {}", self.missed_inputs),
-                format!("Current Direction: {}", self.direction.to_string())
-            ]
+            info_lines
         })
     }
+    fn is_disqualified(&self) -> bool {
+        self.bot.as_ref().is_some_and(|x| x.lock().unwrap().is_disqualified())
+    }
+    fn get_stats(&self) -> Option<ControllerStats> {
+        Some(ControllerStats { missed_inputs: self.missed_inputs as u32, responses: self.responses, total_latency: self.total_latency })
+    }
+    fn take_log(&mut self) -> Vec<ControllerLog> {
+        std::mem::take(&mut self.log)
+    }
 
     fn get_name(&self) -> String {
         self.ai_name.clone()
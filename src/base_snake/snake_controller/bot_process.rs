@@ -0,0 +1,217 @@
+use std::{fs::{self, OpenOptions}, io::Write, path::PathBuf, process::{Child, Command, Stdio}, sync::{Arc, Mutex}};
+
+use serde::{Deserialize, Serialize};
+
+use crate::base_snake::{consts, locale::get_language};
+
+use super::ai_controller::socket_path;
+
+fn default_restarts() -> u32 {
+    3
+}
+
+pub fn split_command(command: &str) -> Result<Vec<String>, String> {
+    // Splits at whitespace outside of quotes, so paths with spaces can be quoted with "" or ''.
+    // Backslashes are kept for Windows paths, only \" inside double quotes is a literal quote.
+    let mut arguments = Vec::new();
+    let mut current: Option<String> = None; // None between arguments
+    let mut quote: Option<char> = None;
+    let mut chars = command.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some('"'), '\\') if chars.peek() == Some(&'"') => {
+                current.get_or_insert_with(String::new).push('"');
+                chars.next();
+            },
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => current.get_or_insert_with(String::new).push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                current.get_or_insert_with(String::new);
+            },
+            (None, c) if c.is_whitespace() => arguments.extend(current.take()),
+            (None, c) => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err(format!("Unterminated quote in bot command {}", command));
+    }
+    arguments.extend(current);
+    if arguments.is_empty() {
+        return Err("Empty bot command".to_string());
+    }
+    Ok(arguments)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BotCommand {
+    pub command: String, // {slot} and {socket} are replaced, the slot is appended if neither is used
+    #[serde(default = "default_restarts")]
+    pub restarts: u32 // Crashes that are restarted before the bot is disqualified
+}
+
+impl BotCommand {
+    pub fn new(command: String) -> Self {
+        Self { command, restarts: default_restarts() }
+    }
+}
+
+// Shared by all copies of a controller so the process outlives single games
+pub type SharedBotProcess = Arc<Mutex<BotProcess>>;
+
+#[derive(Debug)]
+pub struct BotProcess {
+    command: BotCommand,
+    slot: usize, // Starts at 1 like the socket names
+    child: Option<Child>,
+    crashes: u32,
+    disqualified: bool
+}
+
+impl BotProcess {
+    pub fn new(command: BotCommand, index: usize) -> Self {
+        Self { command, slot: index + 1, child: None, crashes: 0, disqualified: false }
+    }
+
+    pub fn shared(command: BotCommand, index: usize) -> SharedBotProcess {
+        Arc::new(Mutex::new(Self::new(command, index)))
+    }
+
+    pub fn log_path(&self) -> PathBuf {
+        PathBuf::from(consts::BOT_LOG_DIR).join(format!("bot{}.log", self.slot))
+    }
+
+    pub fn get_crashes(&self) -> u32 {
+        self.crashes
+    }
+
+    pub fn is_disqualified(&self) -> bool {
+        self.disqualified
+    }
+
+    fn arguments(&self) -> Result<Vec<String>, String> {
+        let socket = socket_path(self.slot - 1);
+        let mut arguments: Vec<String> = split_command(&self.command.command)?.into_iter()
+            .map(|x| x.replace("{slot}", &self.slot.to_string()).replace("{socket}", &socket))
+            .collect();
+        if !self.command.command.contains("{slot}") && !self.command.command.contains("{socket}") {
+            arguments.push(self.slot.to_string());
+        }
+        Ok(arguments)
+    }
+
+    fn spawn(&mut self) -> Result<(), String> {
+        let arguments = self.arguments()?;
+        let (program, arguments) = arguments.split_first().ok_or("Empty bot command".to_string())?;
+
+        // stdout and stderr of all runs are appended to the same file
+        let path = self.log_path();
+        fs::create_dir_all(consts::BOT_LOG_DIR).map_err(|e| format!("Unable to create {}: {}", consts::BOT_LOG_DIR, e))?;
+        let mut stdout = OpenOptions::new().create(true).append(true).open(&path).map_err(|e| format!("Unable to open {}: {}", path.display(), e))?;
+        let _ = writeln!(stdout, "--- {} ---", self.command.command);
+        let stderr = stdout.try_clone().map_err(|e| format!("Unable to open {}: {}", path.display(), e))?;
+
+        let child = Command::new(program)
+            .args(arguments)
+            .env("MULTISNAKE_SLOT", self.slot.to_string())
+            .env("MULTISNAKE_SOCKET", socket_path(self.slot - 1))
+            .env("MULTISNAKE_LANGUAGE", get_language().code())
+            .env("PYTHONUNBUFFERED", "1") // Otherwise the log of python bots is only written on exit
+            .stdin(Stdio::null())
+            .stdout(Stdio::from(stdout))
+            .stderr(Stdio::from(stderr))
+            .spawn()
+            .map_err(|e| format!("Unable to start {}: {}", self.command.command, e))?;
+        println!("Started bot {} ({}), log in {}", self.slot, self.command.command, path.display());
+        self.child = Some(child);
+        Ok(())
+    }
+
+    pub fn check_crash(&mut self) -> bool {
+        // True if the process exited since the last check, too many crashes disqualify the bot
+        let Some(child) = &mut self.child else {
+            return false;
+        };
+        match child.try_wait() {
+            Ok(None) => false,
+            Ok(Some(status)) => {
+                self.child = None;
+                self.crashes += 1;
+                println!("[ERROR] Bot {} exited ({}), see {}", self.slot, status, self.log_path().display());
+                if self.crashes > self.command.restarts {
+                    self.disqualified = true;
+                    println!("Bot {} disqualified after {} crashes", self.slot, self.crashes);
+                }
+                true
+            },
+            Err(e) => {
+                println!("[ERROR] Unable to check bot {}: {}", self.slot, e);
+                false
+            }
+        }
+    }
+
+    pub fn ensure_running(&mut self) -> bool {
+        // Starts the bot or restarts it after a crash, false once it is disqualified
+        self.check_crash();
+        if self.disqualified {
+            return false;
+        }
+        if self.child.is_none() {
+            if let Err(e) = self.spawn() {
+                println!("[ERROR] {}", e);
+                self.disqualified = true;
+            }
+        }
+        !self.disqualified
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+impl Drop for BotProcess {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(command: &str) -> Vec<String> {
+        split_command(command).unwrap()
+    }
+
+    #[test]
+    fn splits_at_whitespace() {
+        assert_eq!(split("  python3   bot.py\t--fast "), ["python3", "bot.py", "--fast"]);
+    }
+
+    #[test]
+    fn keeps_quoted_spaces() {
+        assert_eq!(split(r#""C:\Program Files\bot.exe" --name 'My Bot'"#), [r"C:\Program Files\bot.exe", "--name", "My Bot"]);
+        assert_eq!(split(r#"bot --dir=/tmp/"my dir"/x"#), ["bot", "--dir=/tmp/my dir/x"]);
+        assert_eq!(split(r#"bot "" 'a"b' "say \"hi\"""#), ["bot", "", "a\"b", "say \"hi\""]);
+    }
+
+    #[test]
+    fn rejects_bad_commands() {
+        assert!(split_command("bot \"unterminated").is_err());
+        assert!(split_command("bot 'unterminated").is_err());
+        assert!(split_command("   ").is_err());
+    }
+
+    #[test]
+    fn replaces_placeholders() {
+        let process = BotProcess::new(BotCommand::new("'my bot' --slot {slot}".to_string()), 1);
+        assert_eq!(process.arguments().unwrap(), ["my bot", "--slot", "2"]);
+        let process = BotProcess::new(BotCommand::new("'my bot'".to_string()), 0);
+        assert_eq!(process.arguments().unwrap(), ["my bot", "1"]);
+    }
+}
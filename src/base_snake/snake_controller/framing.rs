@@ -0,0 +1,256 @@
+use std::io::{Error, ErrorKind, Read, Write};
+
+use super::{handshake::Hello, packets::{decode_hello, ServerPacket, HELLO_PACKET_ID}};
+
+// Every packet is sent as a u32 length followed by the packet id and its data.
+// Only bots that only send their name use the old stream without lengths, websocket messages are frames already.
+pub const MAX_PACKET_SIZE: usize = 1 << 20;
+
+pub fn frame(packet: &[u8]) -> Vec<u8> {
+    let mut data = (packet.len() as u32).to_le_bytes().to_vec();
+    data.extend(packet);
+    data
+}
+
+pub fn encode_packet(packet: ServerPacket, framed: bool) -> Vec<u8> {
+    let packet = packet.encode();
+    if framed { frame(&packet) } else { packet }
+}
+
+pub fn read_available(stream: &mut impl Read) -> std::io::Result<(Vec<u8>, bool)> {
+    // Everything a nonblocking stream has right now and whether it was closed
+    let mut bytes = Vec::new();
+    let mut buffer = [0u8; 4096];
+    while bytes.len() <= MAX_PACKET_SIZE {
+        match stream.read(&mut buffer) {
+            Ok(0) => return Ok((bytes, true)),
+            Ok(n) => bytes.extend(&buffer[..n]),
+            Err(ref e) if e.kind() == ErrorKind::WouldBlock => break,
+            Err(e) => return Err(e)
+        }
+    }
+    Ok((bytes, false))
+}
+
+pub fn write_available(stream: &mut impl Write, outbox: &mut Vec<u8>) -> std::io::Result<()> {
+    // Writes as much as a nonblocking stream takes right now, the rest stays queued for the next call
+    while !outbox.is_empty() {
+        match stream.write(outbox) {
+            Ok(0) => return Err(ErrorKind::WriteZero.into()),
+            Ok(n) => { outbox.drain(..n); },
+            Err(ref e) if e.kind() == ErrorKind::WouldBlock => break,
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => {},
+            Err(e) => return Err(e)
+        }
+    }
+    if outbox.len() > MAX_PACKET_SIZE {
+        return Err(Error::other("the bot stopped reading"));
+    }
+    Ok(())
+}
+
+fn could_be_framed_hello(bytes: &[u8]) -> bool {
+    // A legacy bot starts with its name, which never has a hello packet id as fifth byte.
+    // The length of a hello is small, so a name is usually told apart after three bytes.
+    let mut length = [0u8; 4];
+    let header = &bytes[..bytes.len().min(4)];
+    length[..header.len()].copy_from_slice(header);
+    u32::from_le_bytes(length) as usize <= MAX_PACKET_SIZE && bytes.get(4).is_none_or(|x| *x == HELLO_PACKET_ID)
+}
+
+// Collects partial reads until a whole packet arrived
+#[derive(Debug, Default)]
+pub struct FrameReader {
+    buffer: Vec<u8>
+}
+
+impl FrameReader {
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buffer.extend(bytes);
+    }
+
+    pub fn next_packet(&mut self) -> Result<Option<Vec<u8>>, String> {
+        let Some(header) = self.buffer.get(..4) else {
+            return Ok(None);
+        };
+        let length = u32::from_le_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        if length > MAX_PACKET_SIZE {
+            self.buffer.clear(); // The stream can not be trusted anymore
+            return Err(format!("Packet of {} bytes is too large", length));
+        }
+        if self.buffer.len() < 4 + length {
+            return Ok(None);
+        }
+        let packet = self.buffer[4..4 + length].to_vec();
+        self.buffer.drain(..4 + length);
+        Ok(Some(packet))
+    }
+
+    pub fn read_hello(&mut self, bytes: &[u8]) -> Result<Option<(Hello, bool)>, String> {
+        // The hello and whether the bot uses frames, None until the whole hello arrived
+        self.push(bytes);
+        if !could_be_framed_hello(&self.buffer) {
+            let name = String::from_utf8_lossy(&std::mem::take(&mut self.buffer)).to_string();
+            return Ok(Some((Hello::legacy(name), false)));
+        }
+        if self.buffer.len() < 5 {
+            return Ok(None); // Reads may be cut off anywhere
+        }
+        match self.next_packet()? {
+            Some(packet) => Ok(Some((decode_hello(&packet)?, true))),
+            None => Ok(None)
+        }
+    }
+
+    pub fn finish_hello(&mut self) -> Result<(Hello, bool), String> {
+        // Once the bot stopped sending, a few bytes that could have been a hello are a short name
+        if self.buffer.is_empty() || self.buffer.len() >= 5 {
+            return Err("Incomplete hello".to_string());
+        }
+        let name = String::from_utf8_lossy(&std::mem::take(&mut self.buffer)).to_string();
+        Ok((Hello::legacy(name), false))
+    }
+
+    pub fn read_hello_from(&mut self, stream: &mut impl Read) -> Result<(Hello, bool), String> {
+        // Blocks until the hello arrived, the stream should have a read timeout
+        let mut buffer = [0u8; 256];
+        loop {
+            let bytes_read = match stream.read(&mut buffer) {
+                Ok(0) if self.buffer.is_empty() => return Err("The bot closed the connection before sending its name".to_string()),
+                Ok(0) => return self.finish_hello(),
+                Ok(n) => n,
+                Err(ref e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) && !self.buffer.is_empty() => return self.finish_hello(),
+                Err(e) => return Err(format!("Unable to read the hello: {}", e))
+            };
+            if let Some(hello) = self.read_hello(&buffer[..bytes_read])? {
+                return Ok(hello);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base_snake::snake_controller::handshake::Feature;
+
+    fn hello_packet(name: &str) -> Vec<u8> {
+        // Version 1, the name and the debug overlay
+        let mut packet = vec![HELLO_PACKET_ID, 1, 0];
+        packet.extend((name.len() as u16).to_le_bytes());
+        packet.extend(name.as_bytes());
+        packet.extend([1, Feature::DebugOverlay.id()]);
+        packet
+    }
+
+    // Hands out the bytes in the given chunks, then times out like a quiet socket
+    struct ChunkedStream(Vec<Vec<u8>>);
+
+    impl Read for ChunkedStream {
+        fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+            if self.0.is_empty() {
+                return Err(ErrorKind::WouldBlock.into());
+            }
+            let chunk = self.0.remove(0);
+            buffer[..chunk.len()].copy_from_slice(&chunk);
+            Ok(chunk.len())
+        }
+    }
+
+    #[test]
+    fn hello_split_at_every_byte() {
+        let bytes = frame(&hello_packet("Bot"));
+        let mut frames = FrameReader::default();
+        for byte in &bytes[..bytes.len() - 1] {
+            assert_eq!(frames.read_hello(&[*byte]).unwrap(), None);
+        }
+        let (hello, framed) = frames.read_hello(&bytes[bytes.len() - 1..]).unwrap().unwrap();
+        assert!(framed);
+        assert_eq!(hello.name, "Bot");
+        assert_eq!(hello.features, [Feature::DebugOverlay]);
+    }
+
+    #[test]
+    fn hello_from_cut_off_reads() {
+        let bytes = frame(&hello_packet("Bot"));
+        let mut stream = ChunkedStream(vec![bytes[..2].to_vec(), bytes[2..7].to_vec(), bytes[7..].to_vec()]);
+        let (hello, framed) = FrameReader::default().read_hello_from(&mut stream).unwrap();
+        assert!(framed && hello.name == "Bot");
+    }
+
+    #[test]
+    fn legacy_names() {
+        let mut frames = FrameReader::default();
+        assert_eq!(frames.read_hello(b"Legacy Bot").unwrap(), Some((Hello::legacy("Legacy Bot".to_string()), false)));
+
+        // Three bytes of a name are never the length of a hello
+        let mut frames = FrameReader::default();
+        assert_eq!(frames.read_hello(b"Le").unwrap(), None);
+        assert_eq!(frames.read_hello(b"gacy").unwrap(), Some((Hello::legacy("Legacy".to_string()), false)));
+
+        // Shorter names are only known once the bot waits for the game
+        let mut stream = ChunkedStream(vec![b"A".to_vec(), b"l".to_vec()]);
+        assert_eq!(FrameReader::default().read_hello_from(&mut stream).unwrap(), (Hello::legacy("Al".to_string()), false));
+    }
+
+    #[test]
+    fn incomplete_hello() {
+        let bytes = frame(&hello_packet("Bot"));
+        let mut stream = ChunkedStream(vec![bytes[..6].to_vec()]);
+        assert!(FrameReader::default().read_hello_from(&mut stream).is_err());
+        let mut stream = ChunkedStream(Vec::new());
+        assert!(FrameReader::default().read_hello_from(&mut stream).is_err());
+    }
+
+    #[test]
+    fn packets_across_reads() {
+        let mut bytes = frame(&[10]);
+        bytes.extend(frame(&[21, 2, 0, b'h', b'i']));
+        bytes.extend(frame(&[13]));
+        let mut frames = FrameReader::default();
+        frames.push(&bytes[..7]);
+        assert_eq!(frames.next_packet().unwrap(), Some(vec![10]));
+        assert_eq!(frames.next_packet().unwrap(), None);
+        frames.push(&bytes[7..]);
+        assert_eq!(frames.next_packet().unwrap(), Some(vec![21, 2, 0, b'h', b'i']));
+        assert_eq!(frames.next_packet().unwrap(), Some(vec![13]));
+        assert_eq!(frames.next_packet().unwrap(), None);
+    }
+
+    #[test]
+    fn oversized_packet() {
+        let mut frames = FrameReader::default();
+        frames.push(&((MAX_PACKET_SIZE + 1) as u32).to_le_bytes());
+        assert!(frames.next_packet().is_err());
+        assert_eq!(frames.next_packet().unwrap(), None);
+    }
+
+    #[test]
+    fn queued_writes() {
+        // Takes at most three bytes per write, then blocks
+        struct SlowStream(Vec<u8>, usize);
+        impl Write for SlowStream {
+            fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+                if self.1 == 0 {
+                    return Err(ErrorKind::WouldBlock.into());
+                }
+                self.1 -= 1;
+                let n = bytes.len().min(3);
+                self.0.extend(&bytes[..n]);
+                Ok(n)
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut stream = SlowStream(Vec::new(), 2);
+        let mut outbox = frame(&[2, 1, 0, 0, 0]);
+        write_available(&mut stream, &mut outbox).unwrap();
+        assert_eq!(outbox.len(), 3);
+        stream.1 = 5;
+        write_available(&mut stream, &mut outbox).unwrap();
+        assert!(outbox.is_empty());
+        assert_eq!(stream.0, frame(&[2, 1, 0, 0, 0]));
+    }
+}
@@ -0,0 +1,172 @@
+use serde::{Deserialize, Serialize};
+
+use crate::base_snake::rules::GameRules;
+
+// Bots start with a hello, the game answers with a welcome.
+// Bots that only send their name are still accepted, they get no welcome.
+pub const PROTOCOL_VERSION: u16 = 1;
+const MAX_NAME_LENGTH: usize = 64;
+
+// Features the socket, pipe and tcp controllers can grant, deltas are not sent yet
+pub const STREAM_FEATURES: [Feature; 1] = [Feature::DebugOverlay];
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Feature {
+    DebugOverlay, // Marked cells and log lines
+    Json, // Gamestates as json instead of binary packets
+    Deltas // Only the changed cells of a gamestate, bots may ask for it but no controller grants it yet
+}
+
+impl Feature {
+    pub fn id(&self) -> u8 {
+        match self {
+            Feature::DebugOverlay => 1,
+            Feature::Json => 2,
+            Feature::Deltas => 3,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Feature::DebugOverlay => "debug_overlay",
+            Feature::Json => "json",
+            Feature::Deltas => "deltas",
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            1 => Some(Feature::DebugOverlay),
+            2 => Some(Feature::Json),
+            3 => Some(Feature::Deltas),
+            _ => None
+        }
+    }
+
+    pub fn from_label(label: &str) -> Option<Self> {
+        [Feature::DebugOverlay, Feature::Json, Feature::Deltas].into_iter().find(|x| x.label() == label)
+    }
+}
+
+// What a controller needs to know about the match before it connects
+#[derive(Debug, Clone)]
+pub struct MatchInfo {
+    pub snake_id: i32,
+    pub grid_size: (i32, i32),
+    pub rules: GameRules
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Hello {
+    pub version: u16, // 0 for bots that only sent their name
+    pub name: String,
+    #[serde(default, deserialize_with = "known_features")]
+    pub features: Vec<Feature>
+}
+
+fn known_features<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Vec<Feature>, D::Error> {
+    // Features of newer bots are ignored like unknown ids in binary hellos
+    let labels = Vec::<String>::deserialize(deserializer)?;
+    Ok(labels.iter().filter_map(|x| Feature::from_label(x)).collect())
+}
+
+impl Hello {
+    pub fn legacy(name: String) -> Self {
+        // Old bots already send marked cells and log lines
+        Self { version: 0, name, features: vec![Feature::DebugOverlay] }
+    }
+
+    pub fn is_legacy(&self) -> bool {
+        self.version == 0
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        // {"type": "hello", "version": 1, "name": "...", "features": ["json"]}
+        #[derive(Deserialize)]
+        struct JsonHello {
+            #[serde(rename = "type")]
+            kind: String,
+            #[serde(flatten)]
+            hello: Hello
+        }
+        match serde_json::from_str::<JsonHello>(text) {
+            Ok(x) if x.kind == "hello" && x.hello.version > 0 => Ok(x.hello),
+            Ok(_) => Err("Invalid hello".to_string()),
+            Err(_) => Ok(Hello::legacy(text.to_string())) // Plain name
+        }
+    }
+
+    pub fn answer(&self, info: &MatchInfo, supported: &[Feature]) -> Option<Welcome> {
+        // Grants the wanted features the controller supports and uses the older version, legacy bots get no answer
+        if self.is_legacy() {
+            return None;
+        }
+        Some(Welcome {
+            version: self.version.min(PROTOCOL_VERSION),
+            snake_id: info.snake_id,
+            width: info.grid_size.0,
+            height: info.grid_size.1,
+            tick_duration: info.rules.tick_duration,
+            time_limit: info.rules.time_limit,
+            features: self.features.iter().filter(|x| supported.contains(x)).copied().collect()
+        })
+    }
+
+    pub fn describe(&self) -> String {
+        if self.is_legacy() {
+            return "legacy protocol".to_string();
+        }
+        let features: Vec<&str> = self.features.iter().map(|x| x.label()).collect();
+        format!("protocol v{}, wants [{}]", self.version, features.join(", "))
+    }
+
+    pub fn name(&self) -> String {
+        self.name.chars().take(MAX_NAME_LENGTH).collect::<String>().trim().to_string()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Welcome {
+    pub version: u16,
+    pub snake_id: i32,
+    pub width: i32,
+    pub height: i32,
+    pub tick_duration: f32, // Seconds
+    pub time_limit: u64, // Seconds
+    pub features: Vec<Feature> // Granted to the bot
+}
+
+impl Welcome {
+    pub fn has(&self, feature: Feature) -> bool {
+        self.features.contains(&feature)
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut value = serde_json::to_value(self).unwrap_or_default();
+        value["type"] = "welcome".into();
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_hello_ignores_unknown_features() {
+        let hello = Hello::from_json(r#"{"type": "hello", "version": 1, "name": "Bot", "features": ["json", "colors", "deltas"]}"#).unwrap();
+        assert_eq!(hello, Hello { version: 1, name: "Bot".to_string(), features: vec![Feature::Json, Feature::Deltas] });
+        assert_eq!(Hello::from_json("Bot").unwrap(), Hello::legacy("Bot".to_string()));
+    }
+
+    #[test]
+    fn deltas_are_not_granted_yet() {
+        let info = MatchInfo { snake_id: 0, grid_size: (20, 18), rules: GameRules::default() };
+        let hello = Hello { version: 1, name: "Bot".to_string(), features: vec![Feature::DebugOverlay, Feature::Deltas] };
+        assert_eq!(hello.answer(&info, &STREAM_FEATURES).unwrap().features, [Feature::DebugOverlay]);
+        assert_eq!(Feature::from_id(3), Some(Feature::Deltas));
+        assert_eq!(Feature::from_label(Feature::Deltas.label()), Some(Feature::Deltas));
+        assert!(!STREAM_FEATURES.contains(&Feature::Deltas));
+    }
+}
@@ -1,52 +1,227 @@
-use macroquad::input::is_key_down;
+use std::collections::VecDeque;
+
+use macroquad::input::is_key_pressed;
 use macroquad::input::KeyCode;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use crate::base_snake::snake::{Direction, SnakeController};
 
+const MAX_QUEUED_TURNS: usize = 3;
+
+// Keys that can be used for steering, stored by their KeyCode name in the config
+pub const BINDABLE_KEYS: [KeyCode; 66] = [
+    KeyCode::Up, KeyCode::Down, KeyCode::Left, KeyCode::Right,
+    KeyCode::A, KeyCode::B, KeyCode::C, KeyCode::D, KeyCode::E, KeyCode::F, KeyCode::G, KeyCode::H, KeyCode::I,
+    KeyCode::J, KeyCode::K, KeyCode::L, KeyCode::M, KeyCode::N, KeyCode::O, KeyCode::P, KeyCode::Q, KeyCode::R,
+    KeyCode::S, KeyCode::T, KeyCode::U, KeyCode::V, KeyCode::W, KeyCode::X, KeyCode::Y, KeyCode::Z,
+    KeyCode::Key0, KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4,
+    KeyCode::Key5, KeyCode::Key6, KeyCode::Key7, KeyCode::Key8, KeyCode::Key9,
+    KeyCode::Kp0, KeyCode::Kp1, KeyCode::Kp2, KeyCode::Kp3, KeyCode::Kp4,
+    KeyCode::Kp5, KeyCode::Kp6, KeyCode::Kp7, KeyCode::Kp8, KeyCode::Kp9,
+    KeyCode::Space, KeyCode::Comma, KeyCode::Period, KeyCode::Slash, KeyCode::Semicolon, KeyCode::Apostrophe,
+    KeyCode::LeftBracket, KeyCode::RightBracket, KeyCode::Minus, KeyCode::Equal,
+    KeyCode::LeftShift, KeyCode::RightShift, KeyCode::LeftControl, KeyCode::RightControl, KeyCode::LeftAlt, KeyCode::RightAlt,
+];
+
+pub fn key_name(key: KeyCode) -> String {
+    format!("{:?}", key)
+}
+
+pub fn key_from_name(name: &str) -> Option<KeyCode> {
+    BINDABLE_KEYS.iter().copied().find(|x| key_name(*x).eq_ignore_ascii_case(name.trim()))
+}
+
+fn serialize_key<S: Serializer>(key: &KeyCode, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&key_name(*key))
+}
+
+fn deserialize_key<'de, D: Deserializer<'de>>(deserializer: D) -> Result<KeyCode, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    key_from_name(&name).ok_or(serde::de::Error::custom(format!("Unknown key {}", name)))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct KeyBindings {
+    #[serde(serialize_with = "serialize_key", deserialize_with = "deserialize_key")]
+    pub up: KeyCode,
+    #[serde(serialize_with = "serialize_key", deserialize_with = "deserialize_key")]
+    pub down: KeyCode,
+    #[serde(serialize_with = "serialize_key", deserialize_with = "deserialize_key")]
+    pub left: KeyCode,
+    #[serde(serialize_with = "serialize_key", deserialize_with = "deserialize_key")]
+    pub right: KeyCode
+}
+
+impl KeyBindings {
+    pub fn arrows() -> Self {
+        Self { up: KeyCode::Up, down: KeyCode::Down, left: KeyCode::Left, right: KeyCode::Right }
+    }
+    pub fn wasd() -> Self {
+        Self { up: KeyCode::W, down: KeyCode::S, left: KeyCode::A, right: KeyCode::D }
+    }
+    pub fn ijkl() -> Self {
+        Self { up: KeyCode::I, down: KeyCode::K, left: KeyCode::J, right: KeyCode::L }
+    }
+    pub fn numpad() -> Self {
+        Self { up: KeyCode::Kp8, down: KeyCode::Kp5, left: KeyCode::Kp4, right: KeyCode::Kp6 }
+    }
+
+    pub fn get(&self, direction: Direction) -> KeyCode {
+        match direction {
+            Direction::UP => self.up,
+            Direction::DOWN => self.down,
+            Direction::LEFT => self.left,
+            Direction::RIGHT => self.right,
+            Direction::NONE => KeyCode::Unknown,
+        }
+    }
+
+    pub fn set(&mut self, direction: Direction, key: KeyCode) {
+        match direction {
+            Direction::UP => self.up = key,
+            Direction::DOWN => self.down = key,
+            Direction::LEFT => self.left = key,
+            Direction::RIGHT => self.right = key,
+            Direction::NONE => {}
+        }
+    }
+
+    pub fn label(&self) -> String {
+        format!("{}/{}/{}/{}", key_name(self.up), key_name(self.left), key_name(self.down), key_name(self.right))
+    }
+}
+
+fn is_opposite(a: Direction, b: Direction) -> bool {
+    matches!((a, b), (Direction::UP, Direction::DOWN) | (Direction::DOWN, Direction::UP) | (Direction::LEFT, Direction::RIGHT) | (Direction::RIGHT, Direction::LEFT))
+}
+
+#[derive(Debug, Clone)]
+pub struct TurnQueue {
+    executed: Direction, // Direction of the last move
+    queue: VecDeque<Direction> // Turns pressed since then, one is used per tick
+}
+
+impl TurnQueue {
+    pub fn new(executed: Direction) -> Self {
+        Self { executed, queue: VecDeque::new() }
+    }
+
+    pub fn push(&mut self, direction: Direction) -> bool {
+        // Checked against the direction the snake will be moving in when the turn is used
+        let previous = self.queue.back().copied().unwrap_or(self.executed);
+        if self.queue.len() >= MAX_QUEUED_TURNS || direction == previous || is_opposite(direction, previous) {
+            return false;
+        }
+        self.queue.push_back(direction);
+        true
+    }
+
+    pub fn next_direction(&self) -> Direction {
+        self.queue.front().copied().unwrap_or(self.executed)
+    }
+
+    pub fn on_move(&mut self, direction: Direction) {
+        if self.queue.front() == Some(&direction) {
+            self.queue.pop_front();
+        }
+        self.executed = direction;
+    }
+}
+
 #[derive(Debug)]
 pub struct KeyboardController {
-    direction: Direction,
+    turns: TurnQueue,
     up_input: KeyCode,
     down_input: KeyCode,
     left_input: KeyCode,
     right_input: KeyCode,
 }
 impl KeyboardController {
+    pub fn new(bindings: &KeyBindings) -> Self {
+        Self { turns: TurnQueue::new(Direction::RIGHT), up_input: bindings.up, down_input: bindings.down, left_input: bindings.left, right_input: bindings.right }
+    }
     pub fn arrows() -> Self {
-        Self { direction: Direction::RIGHT, up_input: KeyCode::Up, down_input: KeyCode::Down, left_input: KeyCode::Left, right_input: KeyCode::Right }
+        Self::new(&KeyBindings::arrows())
     }
     pub fn wasd() -> Self {
-        Self { direction: Direction::RIGHT, up_input: KeyCode::W, down_input: KeyCode::S, left_input: KeyCode::A, right_input: KeyCode::D }
+        Self::new(&KeyBindings::wasd())
     }
 
 }
 
 impl SnakeController for KeyboardController {
     fn next_direction(&self) -> Direction {
-        self.direction
+        self.turns.next_direction()
     }
     fn update(&mut self) {
-        if is_key_down(self.up_input) && self.direction != Direction::DOWN {
-            self.direction = Direction::UP;
-        }
-        else if is_key_down(self.down_input) && self.direction != Direction::UP {
-            self.direction = Direction::DOWN;
-        }
-        else if is_key_down(self.left_input) && self.direction != Direction::RIGHT {
-            self.direction = Direction::LEFT;
-        }
-        else if is_key_down(self.right_input) && self.direction != Direction::LEFT {
-            self.direction = Direction::RIGHT;
+        let inputs = [(self.up_input, Direction::UP), (self.down_input, Direction::DOWN), (self.left_input, Direction::LEFT), (self.right_input, Direction::RIGHT)];
+        for (key, direction) in inputs {
+            if is_key_pressed(key) {
+                self.turns.push(direction);
+            }
         }
     }
+    fn on_move(&mut self, direction: Direction) {
+        self.turns.on_move(direction);
+    }
     fn get_name(&self) -> String {
         format!("Player {:?}/{:?}/{:?}/{:?}", self.up_input, self.left_input, self.down_input, self.right_input)
     }
 
     fn clone_weak(&self) -> Box<(dyn SnakeController)> {
-        Box::new(KeyboardController { direction: self.direction, up_input: self.up_input, down_input: self.down_input, left_input: self.left_input, right_input: self.right_input })
+        Box::new(KeyboardController { turns: TurnQueue::new(self.turns.executed), up_input: self.up_input, down_input: self.down_input, left_input: self.left_input, right_input: self.right_input })
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
 
+    #[test]
+    fn queues_at_most_max_turns() {
+        let mut turns = TurnQueue::new(Direction::RIGHT);
+        assert!(turns.push(Direction::UP));
+        assert!(turns.push(Direction::LEFT));
+        assert!(turns.push(Direction::DOWN));
+        assert!(!turns.push(Direction::RIGHT));
+        assert_eq!(turns.queue.len(), MAX_QUEUED_TURNS);
+    }
+
+    #[test]
+    fn quick_double_turns_are_kept() {
+        // Up and left within one tick turn the snake around over two ticks
+        let mut turns = TurnQueue::new(Direction::RIGHT);
+        assert!(turns.push(Direction::UP));
+        assert!(turns.push(Direction::LEFT));
+        assert_eq!(turns.next_direction(), Direction::UP);
+        turns.on_move(Direction::UP);
+        assert_eq!(turns.next_direction(), Direction::LEFT);
+    }
 
+    #[test]
+    fn rejects_reversals_and_repeats() {
+        let mut turns = TurnQueue::new(Direction::RIGHT);
+        assert!(!turns.push(Direction::LEFT));
+        assert!(!turns.push(Direction::RIGHT));
+        // Checked against the executed move, not the queued turn that was already used
+        assert!(turns.push(Direction::UP));
+        turns.on_move(Direction::UP);
+        assert!(!turns.push(Direction::DOWN));
+        assert!(turns.push(Direction::LEFT));
+        assert_eq!(turns.next_direction(), Direction::LEFT);
+    }
 
+    #[test]
+    fn uses_one_turn_per_tick() {
+        let mut turns = TurnQueue::new(Direction::RIGHT);
+        turns.push(Direction::UP);
+        turns.push(Direction::LEFT);
+        turns.on_move(turns.next_direction());
+        assert_eq!(turns.executed, Direction::UP);
+        assert_eq!(turns.queue, [Direction::LEFT]);
+        turns.on_move(turns.next_direction());
+        assert_eq!(turns.executed, Direction::LEFT);
+        assert!(turns.queue.is_empty());
+        // Without queued turns the snake keeps going
+        assert_eq!(turns.next_direction(), Direction::LEFT);
+    }
+}
@@ -1,2 +1,9 @@
 pub mod keyboard_controller;
-pub mod ai_controller;
\ No newline at end of file
+pub mod ai_controller;
+pub mod replay_controller;
+pub mod bot_process;
+pub mod handshake;
+pub mod framing;
+pub mod packets;
+pub mod tcp_controller;
+pub mod websocket_controller;
\ No newline at end of file
@@ -0,0 +1,270 @@
+use std::fmt::Display;
+
+use crate::base_snake::snake::{Direction, SnakeData};
+
+use super::handshake::{Feature, Hello, Welcome};
+
+// The first byte of every packet between the game and a bot
+pub const GAMESTATE_PACKET_ID: u8 = 0;
+pub const WINNER_PACKET_ID: u8 = 2;
+pub const UP_PACKET_ID: u8 = 10;
+pub const DOWN_PACKET_ID: u8 = 11;
+pub const LEFT_PACKET_ID: u8 = 12;
+pub const RIGHT_PACKET_ID: u8 = 13;
+pub const MARKED_CELLS_PACKET_ID: u8 = 20;
+pub const LOG_PACKET_ID: u8 = 21;
+pub const HELLO_PACKET_ID: u8 = 30;
+pub const WELCOME_PACKET_ID: u8 = 31;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PacketError {
+    Empty,
+    TooShort(u8), // Id of the cut off packet
+    UnknownId(u8),
+    InvalidVersion(u16)
+}
+
+impl Display for PacketError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PacketError::Empty => write!(f, "Empty packet"),
+            PacketError::TooShort(id) => write!(f, "Packet {} is too short", id),
+            PacketError::UnknownId(id) => write!(f, "Invalid packet id {}", id),
+            PacketError::InvalidVersion(version) => write!(f, "Invalid protocol version {}", version),
+        }
+    }
+}
+
+impl From<PacketError> for String {
+    fn from(value: PacketError) -> Self {
+        value.to_string()
+    }
+}
+
+// Packets the game sends to a bot
+#[derive(Clone, Copy)]
+pub enum ServerPacket<'a> {
+    GameState { data: &'a SnakeData<'a>, snake_id: i32 },
+    Winner(i32),
+    Welcome(&'a Welcome)
+}
+
+impl ServerPacket<'_> {
+    pub fn encode(&self) -> Vec<u8> {
+        match self {
+            ServerPacket::GameState { data, snake_id } => encode_gamestate(data, *snake_id),
+            ServerPacket::Winner(winner_id) => {
+                let mut buffer = vec![WINNER_PACKET_ID];
+                buffer.extend(winner_id.to_le_bytes());
+                buffer
+            },
+            ServerPacket::Welcome(welcome) => {
+                // Version u16, snake id u16, width u16, height u16, tick duration ms u32, time limit s u32, feature count u8, feature ids u8
+                let mut buffer = vec![WELCOME_PACKET_ID];
+                buffer.extend(welcome.version.to_le_bytes());
+                buffer.extend((welcome.snake_id as u16).to_le_bytes());
+                buffer.extend((welcome.width as u16).to_le_bytes());
+                buffer.extend((welcome.height as u16).to_le_bytes());
+                buffer.extend(((welcome.tick_duration * 1000.).round() as u32).to_le_bytes());
+                buffer.extend((welcome.time_limit as u32).to_le_bytes());
+                buffer.push(welcome.features.len() as u8);
+                buffer.extend(welcome.features.iter().map(|x| x.id()));
+                buffer
+            }
+        }
+    }
+}
+
+fn encode_gamestate(data: &SnakeData, snake_id: i32) -> Vec<u8> {
+    // Height u16, width u16, snake id u16, tile ids i16, snake count u16 and the snakes
+    let mut buffer = vec![GAMESTATE_PACKET_ID];
+    buffer.extend(data.height.to_le_bytes());
+    buffer.extend(data.width.to_le_bytes());
+    buffer.extend((snake_id as u16).to_le_bytes());
+    for tile in data.grid {
+        buffer.extend((tile.get_tile_id() as i16).to_le_bytes());
+    }
+
+    // Id i16, name length u16, name, size u16, tile count u16, tiles u16, alive u8
+    buffer.extend((data.snakes.len() as u16).to_le_bytes());
+    for snake in &data.snakes {
+        buffer.extend((snake.id as i16).to_le_bytes());
+        buffer.extend((snake.name.len() as u16).to_le_bytes());
+        buffer.extend(snake.name.as_bytes());
+        buffer.extend((snake.size as u16).to_le_bytes());
+        buffer.extend((snake.tiles.len() as u16).to_le_bytes());
+        for tile in snake.tiles.iter() {
+            buffer.extend((*tile as u16).to_le_bytes());
+        }
+        buffer.push(if snake.alive { 1 } else { 0 });
+    }
+    buffer
+}
+
+// Packets a bot sends to the game
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClientPacket {
+    Hello(Hello),
+    Turn(Direction),
+    MarkedCells(Vec<u16>),
+    Log(String)
+}
+
+impl ClientPacket {
+    pub fn decode(bytes: &[u8]) -> Result<(Self, usize), PacketError> {
+        // The packet at the start of the bytes and its length
+        let mut reader = PacketReader { bytes, index: 1, id: *bytes.first().ok_or(PacketError::Empty)? };
+        let packet = match reader.id {
+            UP_PACKET_ID => ClientPacket::Turn(Direction::UP),
+            DOWN_PACKET_ID => ClientPacket::Turn(Direction::DOWN),
+            LEFT_PACKET_ID => ClientPacket::Turn(Direction::LEFT),
+            RIGHT_PACKET_ID => ClientPacket::Turn(Direction::RIGHT),
+            MARKED_CELLS_PACKET_ID => {
+                // Cell count u16, cells u16
+                let count = reader.u16()?;
+                ClientPacket::MarkedCells((0..count).map(|_| reader.u16()).collect::<Result<_, _>>()?)
+            },
+            LOG_PACKET_ID => {
+                // Length u16, text
+                let length = reader.u16()? as usize;
+                ClientPacket::Log(String::from_utf8_lossy(reader.take(length)?).to_string())
+            },
+            HELLO_PACKET_ID => {
+                // Version u16, name length u16, name, feature count u8, feature ids u8
+                let version = reader.u16()?;
+                if version == 0 {
+                    return Err(PacketError::InvalidVersion(version));
+                }
+                let length = reader.u16()? as usize;
+                let name = String::from_utf8_lossy(reader.take(length)?).to_string();
+                let count = reader.u8()? as usize;
+                let features = reader.take(count)?.iter().filter_map(|x| Feature::from_id(*x)).collect();
+                ClientPacket::Hello(Hello { version, name, features })
+            },
+            id => return Err(PacketError::UnknownId(id))
+        };
+        Ok((packet, reader.index))
+    }
+
+    pub fn decode_all(bytes: &[u8]) -> (Vec<Self>, Option<PacketError>) {
+        // Legacy bots send all packets of an answer as one block, the rest is dropped after an error
+        let mut packets = Vec::new();
+        let mut index = 0;
+        while index < bytes.len() {
+            match ClientPacket::decode(&bytes[index..]) {
+                Ok((packet, length)) => {
+                    packets.push(packet);
+                    index += length;
+                },
+                Err(e) => return (packets, Some(e))
+            }
+        }
+        (packets, None)
+    }
+}
+
+pub fn decode_hello(bytes: &[u8]) -> Result<Hello, PacketError> {
+    // The first packet of a bot, legacy bots only send their name
+    match ClientPacket::decode(bytes) {
+        Ok((ClientPacket::Hello(hello), _)) => Ok(hello),
+        Err(e) if bytes.first() == Some(&HELLO_PACKET_ID) => Err(e),
+        _ => Ok(Hello::legacy(String::from_utf8_lossy(bytes).to_string()))
+    }
+}
+
+struct PacketReader<'a> {
+    bytes: &'a [u8],
+    index: usize,
+    id: u8
+}
+
+impl<'a> PacketReader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], PacketError> {
+        let bytes = self.bytes.get(self.index..self.index + length).ok_or(PacketError::TooShort(self.id))?;
+        self.index += length;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, PacketError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, PacketError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base_snake::{object::Tile, rules::GameRules, snake::{SnakeRefData, SnakeResponseData}};
+    use super::super::handshake::MatchInfo;
+
+    #[test]
+    fn gamestate_round_trip() {
+        let grid = vec![Tile::EMPTY, Tile::FOOD, Tile::Snake { id: 0 }, Tile::Snake { id: 1 }, Tile::DeadSnake, Tile::EMPTY];
+        let snakes = vec![
+            SnakeRefData { id: 0, name: "Äpfel".to_string(), alive: true, size: 3, tiles: vec![2, 1], color: (0, 0, 0) },
+            SnakeRefData { id: 1, name: String::new(), alive: false, size: 1, tiles: vec![3], color: (0, 0, 0) },
+        ];
+        let data = SnakeData { height: 2, width: 3, grid: &grid, snakes };
+        let bytes = ServerPacket::GameState { data: &data, snake_id: 1 }.encode();
+        assert_eq!(bytes[0], GAMESTATE_PACKET_ID);
+
+        let decoded = SnakeResponseData::decode(&bytes).unwrap();
+        assert_eq!((decoded.height, decoded.width, decoded.my_snake_id), (2, 3, 1));
+        assert_eq!(decoded.grid, grid);
+        assert_eq!(decoded.snakes.len(), 2);
+        assert_eq!((decoded.snakes[0].name.as_str(), decoded.snakes[0].size, decoded.snakes[0].alive), ("Äpfel", 3, true));
+        assert_eq!(decoded.snakes[0].tiles, [2, 1]);
+        assert_eq!((decoded.snakes[1].id, decoded.snakes[1].alive, decoded.snakes[1].tiles.as_slice()), (1, false, &[3][..]));
+    }
+
+    #[test]
+    fn server_packets() {
+        assert_eq!(ServerPacket::Winner(-1).encode(), [WINNER_PACKET_ID, 255, 255, 255, 255]);
+
+        let info = MatchInfo { snake_id: 2, grid_size: (20, 18), rules: GameRules { tick_duration: 0.15, time_limit: 600 } };
+        let hello = Hello { version: 1, name: "Bot".to_string(), features: vec![Feature::Json, Feature::DebugOverlay] };
+        let welcome = hello.answer(&info, &[Feature::DebugOverlay]).unwrap();
+        assert_eq!(ServerPacket::Welcome(&welcome).encode(), [WELCOME_PACKET_ID, 1, 0, 2, 0, 20, 0, 18, 0, 150, 0, 0, 0, 88, 2, 0, 0, 1, 1]);
+    }
+
+    #[test]
+    fn client_packets() {
+        assert_eq!(ClientPacket::decode(&[LEFT_PACKET_ID, 99]), Ok((ClientPacket::Turn(Direction::LEFT), 1)));
+        assert_eq!(ClientPacket::decode(&[MARKED_CELLS_PACKET_ID, 2, 0, 5, 0, 1, 1]), Ok((ClientPacket::MarkedCells(vec![5, 257]), 7)));
+        assert_eq!(ClientPacket::decode(&[LOG_PACKET_ID, 2, 0, b'h', b'i']), Ok((ClientPacket::Log("hi".to_string()), 5)));
+        let hello = [HELLO_PACKET_ID, 1, 0, 3, 0, b'B', b'o', b't', 2, 1, 99];
+        let expected = Hello { version: 1, name: "Bot".to_string(), features: vec![Feature::DebugOverlay] }; // Unknown features are ignored
+        assert_eq!(ClientPacket::decode(&hello), Ok((ClientPacket::Hello(expected), hello.len())));
+    }
+
+    #[test]
+    fn invalid_client_packets() {
+        assert_eq!(ClientPacket::decode(&[]), Err(PacketError::Empty));
+        assert_eq!(ClientPacket::decode(&[99]), Err(PacketError::UnknownId(99)));
+        assert_eq!(ClientPacket::decode(&[MARKED_CELLS_PACKET_ID, 2, 0, 5, 0]), Err(PacketError::TooShort(MARKED_CELLS_PACKET_ID)));
+        assert_eq!(ClientPacket::decode(&[LOG_PACKET_ID, 9, 0, b'h']), Err(PacketError::TooShort(LOG_PACKET_ID)));
+        assert_eq!(ClientPacket::decode(&[HELLO_PACKET_ID, 0, 0, 0, 0, 0]), Err(PacketError::InvalidVersion(0)));
+    }
+
+    #[test]
+    fn decode_all_stops_at_errors() {
+        let (packets, error) = ClientPacket::decode_all(&[UP_PACKET_ID, LOG_PACKET_ID, 1, 0, b'x', RIGHT_PACKET_ID]);
+        assert_eq!(packets, [ClientPacket::Turn(Direction::UP), ClientPacket::Log("x".to_string()), ClientPacket::Turn(Direction::RIGHT)]);
+        assert_eq!(error, None);
+
+        let (packets, error) = ClientPacket::decode_all(&[DOWN_PACKET_ID, 42, UP_PACKET_ID]);
+        assert_eq!(packets, [ClientPacket::Turn(Direction::DOWN)]);
+        assert_eq!(error, Some(PacketError::UnknownId(42)));
+    }
+
+    #[test]
+    fn hello_or_legacy_name() {
+        assert_eq!(decode_hello(b"Legacy Bot"), Ok(Hello::legacy("Legacy Bot".to_string())));
+        assert_eq!(decode_hello(&[HELLO_PACKET_ID, 2, 0, 1, 0, b'B', 0]), Ok(Hello { version: 2, name: "B".to_string(), features: Vec::new() }));
+        assert_eq!(decode_hello(&[HELLO_PACKET_ID, 1, 0, 9, 0]), Err(PacketError::TooShort(HELLO_PACKET_ID)));
+    }
+}
@@ -0,0 +1,46 @@
+use std::{cell::Cell, fmt::Debug, rc::Rc};
+
+use crate::base_snake::{replay::TickRecord, snake::{Direction, PlayerInfo, SnakeController}};
+
+pub struct ReplayController {
+    // Plays back the recorded inputs of one snake. All controllers of a replay share the cursor,
+    // which is the number of ticks that have been applied to the grid.
+    snake_id: i32,
+    name: String,
+    ticks: Rc<Vec<TickRecord>>,
+    cursor: Rc<Cell<usize>>
+}
+
+impl ReplayController {
+    pub fn new(snake_id: i32, name: String, ticks: Rc<Vec<TickRecord>>, cursor: Rc<Cell<usize>>) -> Self {
+        Self { snake_id, name, ticks, cursor }
+    }
+}
+
+impl SnakeController for ReplayController {
+    fn next_direction(&self) -> Direction {
+        self.ticks.get(self.cursor.get())
+            .and_then(|x| x.inputs.get(self.snake_id as usize))
+            .copied()
+            .unwrap_or(Direction::NONE)
+    }
+
+    fn get_name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn get_info(&self) -> Option<PlayerInfo> {
+        let applied = self.cursor.get().checked_sub(1)?;
+        self.ticks.get(applied)?.infos.get(&self.snake_id).cloned()
+    }
+
+    fn clone_weak(&self) -> Box<dyn SnakeController> {
+        Box::new(ReplayController::new(self.snake_id, self.name.clone(), Rc::clone(&self.ticks), Rc::clone(&self.cursor)))
+    }
+}
+
+impl Debug for ReplayController {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReplayController").field("snake_id", &self.snake_id).field("name", &self.name).field("cursor", &self.cursor.get()).finish()
+    }
+}
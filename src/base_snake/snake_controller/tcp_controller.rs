@@ -0,0 +1,299 @@
+use std::{io::{ErrorKind, Read, Write}, net::{SocketAddr, TcpListener, TcpStream}, sync::{Arc, Mutex}, time::{Duration, Instant}};
+
+use crate::base_snake::{consts, locale::{direction_name, trf, Text}, snake::{ControllerLog, ControllerStats, Direction, PlayerInfo, SnakeController, SnakeData}};
+
+use super::{ai_controller::read_packets, framing::{encode_packet, read_available, write_available, FrameReader}, handshake::{Hello, MatchInfo, STREAM_FEATURES}, packets::ServerPacket};
+
+pub fn accept_with_timeout(listener: &TcpListener, port: u16) -> Result<(TcpStream, SocketAddr), String> {
+    // The listener has to be nonblocking so the game does not wait forever
+    let deadline = Instant::now() + consts::BOT_CONNECT_TIMEOUT;
+    loop {
+        match listener.accept() {
+            Ok((stream, address)) => {
+                stream.set_nonblocking(false).ok();
+                return Ok((stream, address));
+            },
+            Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                if Instant::now() >= deadline {
+                    return Err(format!("No bot connected to port {} within {}s", port, consts::BOT_CONNECT_TIMEOUT.as_secs()));
+                }
+                std::thread::sleep(Duration::from_millis(10));
+            },
+            Err(e) => return Err(format!("Unable to accept on port {}: {}", port, e))
+        }
+    }
+}
+
+// The read timeout starts again with every byte, so a bot sending its hello slowly would block forever without a deadline
+struct DeadlineStream<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant
+}
+
+impl Read for DeadlineStream<'_> {
+    fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(ErrorKind::TimedOut.into());
+        }
+        self.stream.set_read_timeout(Some(remaining))?;
+        let mut stream = self.stream;
+        stream.read(buffer)
+    }
+}
+
+fn read_hello(stream: &TcpStream, timeout: Duration) -> Result<(Hello, Option<FrameReader>), String> {
+    // The hello and the frame reader of bots that use frames
+    let mut frames = FrameReader::default();
+    let (hello, framed) = frames.read_hello_from(&mut DeadlineStream { stream, deadline: Instant::now() + timeout })?;
+    stream.set_read_timeout(None).ok();
+    Ok((hello, framed.then_some(frames)))
+}
+
+// Same protocol as the socket and pipe controllers, for bots on other machines.
+// The listener is shared by all copies of a controller so the port stays bound between games.
+#[derive(Debug)]
+pub struct TcpController {
+    port: u16,
+    listener: Arc<Mutex<Option<TcpListener>>>,
+    stream: Option<TcpStream>,
+    frames: Option<FrameReader>, // None for legacy bots without frames
+    outbox: Vec<u8>, // Sent once the bot reads again
+    address: Option<SocketAddr>, // Of the connected bot
+    direction: Direction,
+    ai_name: String,
+    missed_inputs: i32,
+    marked_cells: Vec<u16>,
+    awaiting_since: Option<Instant>, // Gamestate sent but not answered yet
+    responses: u32,
+    total_latency: Duration,
+    log: Vec<ControllerLog>
+}
+
+impl TcpController {
+    pub fn new(port: u16) -> Self {
+        Self { port, listener: Arc::new(Mutex::new(None)), stream: None, frames: None, outbox: Vec::new(), address: None, direction: Direction::RIGHT, ai_name: "Unknown Ai".to_string(), missed_inputs: 0, marked_cells: Vec::new(), awaiting_since: None, responses: 0, total_latency: Duration::ZERO, log: Vec::new() }
+    }
+
+    pub fn from_listener(listener: TcpListener) -> Result<Self, String> {
+        // For a listener that is already bound, e.g. to port 0
+        let port = listener.local_addr().map_err(|e| format!("Unable to read the listener address: {}", e))?.port();
+        listener.set_nonblocking(true).map_err(|e| format!("Unable to listen on port {}: {}", port, e))?;
+        Ok(Self { listener: Arc::new(Mutex::new(Some(listener))), ..Self::new(port) })
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    fn accept(&self) -> Result<(TcpStream, SocketAddr), String> {
+        let mut listener = self.listener.lock().unwrap();
+        if listener.is_none() {
+            let address = format!("{}:{}", consts::TCP_BIND_ADDRESS, self.port);
+            let bound = TcpListener::bind(&address).map_err(|e| format!("Unable to listen on {}: {}", address, e))?;
+            bound.set_nonblocking(true).ok();
+            *listener = Some(bound);
+            println!("Waiting for bots on {}", address);
+        }
+
+        accept_with_timeout(listener.as_ref().unwrap(), self.port)
+    }
+
+    fn send(&mut self, packet: ServerPacket) {
+        self.outbox.extend(encode_packet(packet, self.frames.is_some()));
+        self.flush();
+    }
+
+    fn flush(&mut self) {
+        let Some(stream) = &mut self.stream else {
+            return;
+        };
+        if let Err(e) = write_available(stream, &mut self.outbox) {
+            println!("[ERROR] Unable to send to {}: {}", self.ai_name, e);
+            self.stream = None;
+        }
+    }
+}
+
+impl SnakeController for TcpController {
+    fn report_data(&mut self, data: SnakeData, snake_id: i32) {
+        if !self.is_connected() {
+            return;
+        }
+
+        self.send(ServerPacket::GameState { data: &data, snake_id });
+        if self.awaiting_since.replace(Instant::now()).is_some() {
+            self.missed_inputs += 1; // The previous gamestate was never answered
+        }
+    }
+
+    fn send_winner(&mut self, winner_id: i32) {
+        self.send(ServerPacket::Winner(winner_id));
+    }
+
+    fn connect(&mut self, info: &MatchInfo) -> bool {
+        let (mut stream, address) = match self.accept() {
+            Ok(x) => x,
+            Err(e) => {
+                println!("[ERROR] {}", e);
+                return false;
+            }
+        };
+
+        // The bot starts with a hello or its name
+        let (hello, frames) = match read_hello(&stream, consts::BOT_HELLO_TIMEOUT) {
+            Ok(x) => x,
+            Err(e) => {
+                println!("[ERROR] {}: {}", address, e);
+                return false;
+            }
+        };
+        if let Some(welcome) = hello.answer(info, &STREAM_FEATURES) {
+            if let Err(e) = stream.write_all(&encode_packet(ServerPacket::Welcome(&welcome), frames.is_some())) {
+                println!("[ERROR] Unable to welcome {}: {}", address, e);
+                return false;
+            }
+        }
+        stream.set_nodelay(true).ok();
+        stream.set_nonblocking(true).ok();
+
+        println!("{} connected from {} ({})", hello.name(), address, hello.describe());
+        self.ai_name = hello.name();
+        self.address = Some(address);
+        self.frames = frames;
+        self.outbox.clear();
+        self.stream = Some(stream);
+        true
+    }
+
+    fn update(&mut self) {
+        self.flush();
+        let Some(stream) = &mut self.stream else {
+            return;
+        };
+
+        let (bytes, closed) = match read_available(stream) {
+            Ok(x) => x,
+            Err(e) => {
+                println!("[ERROR] Unable to read from {}: {}", self.ai_name, e);
+                self.stream = None;
+                return;
+            }
+        };
+        if !bytes.is_empty() {
+            if let Some(sent) = self.awaiting_since.take() {
+                self.total_latency += sent.elapsed();
+                self.responses += 1;
+            }
+            read_packets(&bytes, self.frames.as_mut(), &mut self.direction, &mut self.marked_cells, &mut self.log);
+        }
+        if closed {
+            println!("{} disconnected", self.ai_name);
+            self.stream = None;
+        }
+    }
+
+    fn get_name(&self) -> String {
+        self.ai_name.clone()
+    }
+
+    fn next_direction(&self) -> Direction {
+        self.direction
+    }
+
+    fn clone_weak(&self) -> Box<dyn SnakeController> {
+        Box::new(TcpController { listener: self.listener.clone(), ai_name: self.ai_name.clone(), ..TcpController::new(self.port) })
+    }
+
+    fn get_info(&self) -> Option<PlayerInfo> {
+        let address = self.address.map(|x| x.to_string()).unwrap_or("-".to_string());
+        Some(PlayerInfo {
+            marked_cells: self.marked_cells.clone(),
+            info_lines: vec![
+                trf(Text::MissedInputs, &[&self.missed_inputs]),
+                trf(Text::CurrentDirection, &[&direction_name(self.direction)]),
+                trf(Text::RemoteAddress, &[&address]),
+            ]
+        })
+    }
+
+    fn get_stats(&self) -> Option<ControllerStats> {
+        Some(ControllerStats { missed_inputs: self.missed_inputs as u32, responses: self.responses, total_latency: self.total_latency })
+    }
+
+    fn take_log(&mut self) -> Vec<ControllerLog> {
+        std::mem::take(&mut self.log)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+    use crate::base_snake::{object::Tile, rules::GameRules, snake_controller::{framing::frame, handshake::Feature, packets::{GAMESTATE_PACKET_ID, HELLO_PACKET_ID, LEFT_PACKET_ID, WELCOME_PACKET_ID}}};
+
+    fn read_frame(stream: &mut TcpStream) -> Vec<u8> {
+        let mut length = [0u8; 4];
+        stream.read_exact(&mut length).unwrap();
+        let mut packet = vec![0u8; u32::from_le_bytes(length) as usize];
+        stream.read_exact(&mut packet).unwrap();
+        packet
+    }
+
+    #[test]
+    fn plays_with_a_framed_bot() {
+        let mut controller = TcpController::from_listener(TcpListener::bind("127.0.0.1:0").unwrap()).unwrap();
+        let port = controller.port;
+        let bot = thread::spawn(move || {
+            let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+            stream.write_all(&frame(&[HELLO_PACKET_ID, 1, 0, 3, 0, b'B', b'o', b't', 1, Feature::DebugOverlay.id()])).unwrap();
+            let welcome = read_frame(&mut stream);
+            let gamestate = read_frame(&mut stream);
+            stream.write_all(&frame(&[LEFT_PACKET_ID])).unwrap();
+            (welcome[0], gamestate[0])
+        });
+
+        let info = MatchInfo { snake_id: 0, grid_size: (3, 2), rules: GameRules::default() };
+        assert!(controller.connect(&info));
+        assert_eq!(controller.get_name(), "Bot");
+        let grid = vec![Tile::EMPTY; 6];
+        controller.report_data(SnakeData { height: 2, width: 3, grid: &grid, snakes: Vec::new() }, 0);
+
+        // The bot turns and then disconnects
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while controller.is_connected() && Instant::now() < deadline {
+            controller.update();
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(bot.join().unwrap(), (WELCOME_PACKET_ID, GAMESTATE_PACKET_ID));
+        assert_eq!(controller.next_direction(), Direction::LEFT);
+        assert!(!controller.is_connected());
+        assert_eq!(controller.get_stats().unwrap().responses, 1);
+    }
+
+    #[test]
+    fn a_slow_hello_times_out() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let bot = thread::spawn(move || {
+            // Every byte comes before a read timeout would run out, but the hello never ends
+            let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+            let hello = frame(&[HELLO_PACKET_ID, 1, 0, 3, 0, b'B', b'o', b't', 0]);
+            stream.write_all(&hello[..6]).unwrap();
+            for byte in &hello[6..hello.len() - 1] {
+                thread::sleep(Duration::from_millis(200));
+                if stream.write_all(&[*byte]).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let (stream, _) = listener.accept().unwrap();
+        let started = Instant::now();
+        assert_eq!(read_hello(&stream, Duration::from_millis(200)).unwrap_err(), "Incomplete hello");
+        assert!(started.elapsed() < Duration::from_secs(1));
+        drop(stream);
+        bot.join().unwrap();
+    }
+}
@@ -0,0 +1,305 @@
+use std::{io::ErrorKind, net::{SocketAddr, TcpListener, TcpStream}, sync::{Arc, Mutex}, time::{Duration, Instant}};
+
+use serde::Deserialize;
+use tungstenite::{handshake::server::{Callback, ErrorResponse, Request, Response}, http::StatusCode, Message, WebSocket};
+
+use crate::base_snake::{config::WebSocketConfig, locale::{direction_name, trf, Text}, snake::{ControllerLog, ControllerStats, Direction, PlayerInfo, SnakeController, SnakeData}};
+
+use super::{ai_controller::{push_log, read_response}, handshake::{Feature, Hello, MatchInfo}, packets::{decode_hello, ServerPacket}, tcp_controller::accept_with_timeout};
+
+const SUPPORTED_FEATURES: [Feature; 2] = [Feature::DebugOverlay, Feature::Json]; // Deltas are not sent yet
+
+// Answer of a bot using JSON, every field is optional
+#[derive(Debug, Deserialize)]
+struct JsonResponse {
+    direction: Option<String>, // up, down, left or right
+    marked_cells: Option<Vec<u16>>,
+    log: Option<String>
+}
+
+fn is_allowed_origin(origin: Option<&str>, settings: &WebSocketConfig) -> bool {
+    // Bots outside of a browser send no origin, pages opened from a file send null
+    let Some(origin) = origin else {
+        return true;
+    };
+    if origin == "null" || origin.starts_with("file://") {
+        return settings.allow_file_origins;
+    }
+    if settings.allowed_origins.iter().any(|x| x.trim_end_matches('/').eq_ignore_ascii_case(origin)) {
+        return true;
+    }
+    let Some(host) = origin.strip_prefix("http://").or(origin.strip_prefix("https://")) else {
+        return false;
+    };
+    let host = match host.strip_prefix('[') {
+        Some(ipv6) => ipv6.split(']').next().unwrap_or_default(),
+        None => host.split(':').next().unwrap_or_default()
+    };
+    host.eq_ignore_ascii_case("localhost") || host == "127.0.0.1" || host == "::1"
+}
+
+// Rejects the handshake of websites that are not allowed, any website open in a browser could connect otherwise
+struct OriginCheck<'a>(&'a WebSocketConfig);
+
+impl Callback for OriginCheck<'_> {
+    fn on_request(self, request: &Request, response: Response) -> Result<Response, ErrorResponse> {
+        let origin = request.headers().get("Origin").map(|x| x.to_str().unwrap_or_default());
+        if is_allowed_origin(origin, self.0) {
+            return Ok(response);
+        }
+        let mut error = ErrorResponse::new(Some(format!("Origin {} is not allowed", origin.unwrap_or_default())));
+        *error.status_mut() = StatusCode::FORBIDDEN;
+        Err(error)
+    }
+}
+
+// Carries the packets of the socket controllers as binary frames, for bots running in a browser.
+// The first frame is a hello or the name of the bot, a json hello or a name sent as text switches to JSON.
+#[derive(Debug)]
+pub struct WebSocketController {
+    port: u16,
+    settings: WebSocketConfig,
+    listener: Arc<Mutex<Option<TcpListener>>>, // Shared by all copies like the tcp controller
+    socket: Option<WebSocket<TcpStream>>,
+    address: Option<SocketAddr>,
+    json: bool,
+    direction: Direction,
+    ai_name: String,
+    missed_inputs: i32,
+    marked_cells: Vec<u16>,
+    awaiting_since: Option<Instant>, // Gamestate sent but not answered yet
+    responses: u32,
+    total_latency: Duration,
+    log: Vec<ControllerLog>
+}
+
+impl WebSocketController {
+    pub fn new(port: u16, settings: WebSocketConfig) -> Self {
+        Self { port, settings, listener: Arc::new(Mutex::new(None)), socket: None, address: None, json: false, direction: Direction::RIGHT, ai_name: "Unknown Ai".to_string(), missed_inputs: 0, marked_cells: Vec::new(), awaiting_since: None, responses: 0, total_latency: Duration::ZERO, log: Vec::new() }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.socket.is_some()
+    }
+
+    fn accept(&self) -> Result<(WebSocket<TcpStream>, SocketAddr), String> {
+        let mut listener = self.listener.lock().unwrap();
+        if listener.is_none() {
+            let address = format!("{}:{}", self.settings.bind_address, self.port);
+            let bound = TcpListener::bind(&address).map_err(|e| format!("Unable to listen on {}: {}", address, e))?;
+            bound.set_nonblocking(true).ok();
+            *listener = Some(bound);
+            println!("Waiting for websocket bots on ws://{}", address);
+        }
+        let (stream, address) = accept_with_timeout(listener.as_ref().unwrap(), self.port)?;
+        stream.set_read_timeout(Some(Duration::from_secs(5))).ok();
+        let socket = tungstenite::accept_hdr(stream, OriginCheck(&self.settings)).map_err(|e| format!("Websocket handshake with {} failed: {}", address, e))?;
+        Ok((socket, address))
+    }
+
+    fn send(&mut self, message: Message) {
+        let Some(socket) = &mut self.socket else {
+            return;
+        };
+        match socket.send(message) {
+            Ok(()) => {},
+            Err(tungstenite::Error::Io(ref e)) if e.kind() == ErrorKind::WouldBlock => {} // Queued, sent with the next message
+            Err(e) => {
+                println!("[ERROR] Unable to send to {}: {}", self.ai_name, e);
+                self.socket = None;
+            }
+        }
+    }
+
+    fn read_json(&mut self, text: &str) {
+        let response = match serde_json::from_str::<JsonResponse>(text) {
+            Ok(x) => x,
+            Err(e) => {
+                push_log(&mut self.log, ControllerLog::Rejected(format!("Invalid json: {}", e)));
+                return;
+            }
+        };
+        match response.direction.as_deref().map(|x| x.to_lowercase()).as_deref() {
+            Some("up") => self.direction = Direction::UP,
+            Some("down") => self.direction = Direction::DOWN,
+            Some("left") => self.direction = Direction::LEFT,
+            Some("right") => self.direction = Direction::RIGHT,
+            Some(other) => push_log(&mut self.log, ControllerLog::Rejected(format!("Invalid direction {}", other))),
+            None => {}
+        }
+        if let Some(cells) = response.marked_cells {
+            self.marked_cells = cells;
+        }
+        if let Some(line) = response.log {
+            push_log(&mut self.log, ControllerLog::Message(line));
+        }
+    }
+}
+
+impl SnakeController for WebSocketController {
+    fn report_data(&mut self, data: SnakeData, snake_id: i32) {
+        if !self.is_connected() {
+            return;
+        }
+
+        let message = if self.json { Message::text(data.to_json(snake_id)) } else { Message::binary(ServerPacket::GameState { data: &data, snake_id }.encode()) };
+        self.send(message);
+        if self.awaiting_since.replace(Instant::now()).is_some() {
+            self.missed_inputs += 1; // The previous gamestate was never answered
+        }
+    }
+
+    fn send_winner(&mut self, winner_id: i32) {
+        let message = if self.json {
+            Message::text(serde_json::json!({ "type": "winner", "winner": winner_id }).to_string())
+        } else {
+            Message::binary(ServerPacket::Winner(winner_id).encode())
+        };
+        self.send(message);
+    }
+
+    fn connect(&mut self, info: &MatchInfo) -> bool {
+        let (mut socket, address) = match self.accept() {
+            Ok(x) => x,
+            Err(e) => {
+                println!("[ERROR] {}", e);
+                return false;
+            }
+        };
+
+        let (hello, text) = match socket.read() {
+            Ok(Message::Text(text)) => (Hello::from_json(&text), true),
+            Ok(Message::Binary(bytes)) => (decode_hello(&bytes).map_err(String::from), false),
+            _ => (Err(format!("{} did not send a name", address)), false)
+        };
+        let hello = match hello {
+            Ok(x) => x,
+            Err(e) => {
+                println!("[ERROR] {}", e);
+                return false;
+            }
+        };
+        let welcome = hello.answer(info, &SUPPORTED_FEATURES);
+        let json = match &welcome {
+            Some(welcome) => welcome.has(Feature::Json),
+            None => text // Legacy bots that sent their name as text
+        };
+        if let Some(welcome) = welcome {
+            let message = if json { Message::text(welcome.to_json()) } else { Message::binary(ServerPacket::Welcome(&welcome).encode()) };
+            if let Err(e) = socket.send(message) {
+                println!("[ERROR] Unable to welcome {}: {}", address, e);
+                return false;
+            }
+        }
+        socket.get_mut().set_read_timeout(None).ok();
+        socket.get_mut().set_nodelay(true).ok();
+        socket.get_mut().set_nonblocking(true).ok();
+
+        println!("{} connected from {} ({}){}", hello.name(), address, hello.describe(), if json { ", json" } else { "" });
+        self.ai_name = hello.name();
+        self.address = Some(address);
+        self.json = json;
+        self.socket = Some(socket);
+        true
+    }
+
+    fn update(&mut self) {
+        // Reads every message that arrived since the last frame
+        while let Some(socket) = &mut self.socket {
+            let message = match socket.read() {
+                Ok(message) => message,
+                Err(tungstenite::Error::Io(ref e)) if e.kind() == ErrorKind::WouldBlock => return,
+                Err(tungstenite::Error::ConnectionClosed) => {
+                    println!("{} disconnected", self.ai_name);
+                    self.socket = None;
+                    return;
+                }
+                Err(e) => {
+                    println!("[ERROR] Unable to read from {}: {}", self.ai_name, e);
+                    self.socket = None;
+                    return;
+                }
+            };
+            if !(message.is_text() || message.is_binary()) {
+                continue; // Pings are answered by tungstenite
+            }
+
+            if let Some(sent) = self.awaiting_since.take() {
+                self.total_latency += sent.elapsed();
+                self.responses += 1;
+            }
+            match message {
+                Message::Text(text) => self.read_json(&text),
+                Message::Binary(bytes) => read_response(&bytes, &mut self.direction, &mut self.marked_cells, &mut self.log),
+                _ => {}
+            }
+        }
+    }
+
+    fn get_name(&self) -> String {
+        self.ai_name.clone()
+    }
+
+    fn next_direction(&self) -> Direction {
+        self.direction
+    }
+
+    fn clone_weak(&self) -> Box<dyn SnakeController> {
+        Box::new(WebSocketController { listener: self.listener.clone(), ai_name: self.ai_name.clone(), ..WebSocketController::new(self.port, self.settings.clone()) })
+    }
+
+    fn get_info(&self) -> Option<PlayerInfo> {
+        let address = self.address.map(|x| x.to_string()).unwrap_or("-".to_string());
+        Some(PlayerInfo {
+            marked_cells: self.marked_cells.clone(),
+            info_lines: vec![
+                trf(Text::MissedInputs, &[&self.missed_inputs]),
+                trf(Text::CurrentDirection, &[&direction_name(self.direction)]),
+                trf(Text::RemoteAddress, &[&address]),
+            ]
+        })
+    }
+
+    fn get_stats(&self) -> Option<ControllerStats> {
+        Some(ControllerStats { missed_inputs: self.missed_inputs as u32, responses: self.responses, total_latency: self.total_latency })
+    }
+
+    fn take_log(&mut self) -> Vec<ControllerLog> {
+        std::mem::take(&mut self.log)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allows_local_origins() {
+        for origin in [None, Some("http://localhost:8000"), Some("https://127.0.0.1"), Some("http://[::1]:3000")] {
+            assert!(is_allowed_origin(origin, &WebSocketConfig::default()), "{:?}", origin);
+        }
+    }
+
+    #[test]
+    fn rejects_other_origins() {
+        for origin in ["https://example.com", "http://localhost.example.com", "http://127.0.0.1.example.com", "ws://localhost"] {
+            assert!(!is_allowed_origin(Some(origin), &WebSocketConfig::default()), "{}", origin);
+        }
+        let settings = WebSocketConfig { allowed_origins: vec!["https://Example.com/".to_string()], ..WebSocketConfig::default() };
+        assert!(is_allowed_origin(Some("https://example.com"), &settings));
+    }
+
+    #[test]
+    fn deltas_are_not_granted_yet() {
+        assert!(!SUPPORTED_FEATURES.contains(&Feature::Deltas));
+    }
+
+    #[test]
+    fn file_origins_need_the_setting() {
+        let settings = WebSocketConfig { allow_file_origins: true, ..WebSocketConfig::default() };
+        for origin in ["null", "file:///home/bot.html"] {
+            assert!(!is_allowed_origin(Some(origin), &WebSocketConfig::default()), "{}", origin);
+            assert!(is_allowed_origin(Some(origin), &settings), "{}", origin);
+        }
+    }
+}
@@ -1,8 +1,9 @@
 use std::{cmp::min, collections::HashMap};
 
 use macroquad::prelude::*;
-use super::{object::Tile, snake::{self, Direction, PlayerInfo, Snake, SnakeController, SnakeData, SnakeRefData}};
-use ::rand::{thread_rng, Rng};
+use super::{object::Tile, snake::{self, ControllerLog, ControllerStats, Direction, PlayerInfo, Snake, SnakeController, SnakeData, SnakeRefData}, snapshot::{GameState, GridSnapshot}, snake_controller::handshake::MatchInfo, rules::GameRules, events::{DeathCause, DeathRecord, GameEvent}};
+use ::rand::{thread_rng, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 
 
 pub const GRID_OFFSET_X: f32 = 10.;
@@ -20,6 +21,13 @@ pub struct SnakeGrid<'a> {
     square_size: f32,
     square_margin: f32,
     pub total_square_size: f32,
+    pub rules: GameRules,
+    seed: u64,
+    rng: ChaCha8Rng,
+    tick_count: u32,
+    last_inputs: Vec<Direction>, // Directions the snakes were asked to move in during the last tick
+    last_events: Vec<GameEvent>,
+    deaths: Vec<DeathRecord>,
 
     on_food_handler: Option<Box<dyn Fn() -> ()>>,
     on_death_handler: Option<Box<dyn Fn() -> ()>>
@@ -45,9 +53,10 @@ impl<'a> SnakeGrid<'a> {
         let total_square_size = min((GRID_SCREEN_SIZE.0 / width as f32) as i32, (GRID_SCREEN_SIZE.1 / height as f32) as i32) as f32;
         let square_size = total_square_size * 0.7;
         let square_margin = total_square_size - square_size;
+        let seed = thread_rng().gen();
 
         Self {
-            width, height, snakes: Vec::new(), grid: empty_grid, snake_colors, square_size, square_margin, total_square_size, on_food_handler: None, on_death_handler: None
+            width, height, snakes: Vec::new(), grid: empty_grid, snake_colors, square_size, square_margin, total_square_size, rules: GameRules::default(), seed, rng: ChaCha8Rng::seed_from_u64(seed), tick_count: 0, last_inputs: Vec::new(), last_events: Vec::new(), deaths: Vec::new(), on_food_handler: None, on_death_handler: None
         }
     }
 
@@ -94,6 +103,13 @@ impl<'a> SnakeGrid<'a> {
             });  
     }
 
+    pub fn set_colors(&mut self, colors: &[(u8, u8, u8)]) {
+        // Has to be called before the snakes are added
+        if !colors.is_empty() {
+            self.snake_colors = colors.iter().map(|(r, g, b)| Color::from_rgba(*r, *g, *b, 255)).collect();
+        }
+    }
+
     pub fn add_snake(&mut self, controller: &'a mut dyn SnakeController) {
         while self.snakes.len()+1 > self.snake_colors.len() {
             self.snake_colors.push(random_color_bright_non_red());
@@ -112,8 +128,9 @@ impl<'a> SnakeGrid<'a> {
         x + width*y
     }
 
-    pub fn kill_snake(grid: &mut Vec<Tile>, snake: &mut Snake, handler: &Option<Box<dyn Fn()>>) {
+    pub fn kill_snake(grid: &mut Vec<Tile>, snake: &mut Snake, cause: DeathCause, events: &mut Vec<GameEvent>, handler: &Option<Box<dyn Fn()>>) {
         println!("{:?} died", snake);
+        events.push(GameEvent::Died { snake_id: snake.get_id(), cause });
         let tiles = snake.kill();
         for tile in tiles {
             grid[*tile as usize] = Tile::DeadSnake;
@@ -124,25 +141,48 @@ impl<'a> SnakeGrid<'a> {
     }
 
     pub fn do_place_food(&mut self) {
-        SnakeGrid::place_food(&mut self.grid);
+        SnakeGrid::place_food(&mut self.grid, &mut self.rng);
     }
 
-    pub fn place_food(grid: &mut Vec<Tile>) {
-        let mut rng = thread_rng();
+    pub fn place_food(grid: &mut Vec<Tile>, rng: &mut ChaCha8Rng) -> Option<i32> {
+        // Returns the index of the new food
         for _ in 0..20 {
             let x = rng.gen_range(0..grid.len());
             if let Tile::EMPTY = grid[x] {
                 grid[x] = Tile::FOOD;
-                return;
+                return Some(x as i32);
             }
         }
         println!("[WARNING] Couldnt place food");
+        None
     }
 
     pub fn tick(&mut self) {
+        self.tick_count += 1;
+        self.last_inputs = self.snakes.iter().map(|x| x.next_direction()).collect();
+        self.last_events.clear();
+
+        // Snakes after a collision do not move this tick, so only the moves that happened are reported
+        let moved = self.move_snakes();
+        for ((snake, direction), moved) in self.snakes.iter_mut().zip(&self.last_inputs).zip(moved) {
+            if moved {
+                snake.report_move(*direction);
+            }
+        }
+
+        for event in &self.last_events {
+            if let GameEvent::Died { snake_id, cause } = event {
+                self.deaths.push(DeathRecord { snake_id: *snake_id, tick: self.tick_count, cause: *cause });
+            }
+        }
+    }
+
+    fn move_snakes(&mut self) -> Vec<bool> {
+        // Returns which snakes moved
         let width = self.width;
 
         let mut collisions =  Vec::new();
+        let mut moved = vec![false; self.snakes.len()];
         
         for snake in &mut self.snakes.iter_mut() {
             if snake.is_dead() {
@@ -162,34 +202,38 @@ impl<'a> SnakeGrid<'a> {
             
             // Check Borders
             if x < 0 || x >= self.width || y < 0 || y >= self.height {
-                SnakeGrid::kill_snake(&mut self.grid, snake, &self.on_death_handler);
+                SnakeGrid::kill_snake(&mut self.grid, snake, DeathCause::Wall, &mut self.last_events, &self.on_death_handler);
                 continue;
             }
             let new_head = y*self.width + x; // Where to move to
 
             // Check collisions
             if collisions.contains(&new_head) {
-                SnakeGrid::kill_snake(&mut self.grid, snake, &self.on_death_handler);
-                return;
+                SnakeGrid::kill_snake(&mut self.grid, snake, DeathCause::HeadOn, &mut self.last_events, &self.on_death_handler);
+                return moved;
             }
             
             match &self.grid[new_head as usize] {
                 Tile::EMPTY => {},
                 Tile::FOOD => { 
                     snake.grow(); 
-                    SnakeGrid::place_food(&mut self.grid);
+                    self.last_events.push(GameEvent::FoodEaten { snake_id: snake.get_id(), cell: new_head });
+                    if let Some(cell) = SnakeGrid::place_food(&mut self.grid, &mut self.rng) {
+                        self.last_events.push(GameEvent::FoodPlaced { cell });
+                    }
                     if let Some(handler) = &mut &self.on_food_handler {
                         handler();
                     }
                 },
-                Tile::Snake { id: _ } => {
+                Tile::Snake { id } => {
+                    let cause = DeathCause::Snake { id: *id };
                     collisions.push(new_head);
-                    SnakeGrid::kill_snake(&mut self.grid, snake, &self.on_death_handler);
-                    return;
+                    SnakeGrid::kill_snake(&mut self.grid, snake, cause, &mut self.last_events, &self.on_death_handler);
+                    return moved;
                 }
                 _ => {
-                    SnakeGrid::kill_snake(&mut self.grid, snake, &self.on_death_handler);
-                    return;
+                    SnakeGrid::kill_snake(&mut self.grid, snake, DeathCause::DeadSnake, &mut self.last_events, &self.on_death_handler);
+                    return moved;
                 }
             }
 
@@ -198,14 +242,15 @@ impl<'a> SnakeGrid<'a> {
             for removed in snake.move_head(new_head) {
                 self.grid[removed as usize] = Tile::EMPTY;
             }
+            moved[snake.get_id() as usize] = true;
         }
+        moved
     }
-    pub fn get_random_spawn_positions(&self) -> Vec<i32> {
-        let mut rng = thread_rng();
+    pub fn get_random_spawn_positions(&mut self) -> Vec<i32> {
         let mut new_vec: Vec<i32> = Vec::new();
 
         for i in 0..1000 {
-            let x = rng.gen_range(0..self.grid.len()) as i32;
+            let x = self.rng.gen_range(0..self.grid.len()) as i32;
             if !new_vec.contains(&x) {
                 new_vec.push(x);
             }
@@ -261,6 +306,18 @@ impl<'a> SnakeGrid<'a> {
         }
         alive_snake
     }
+    pub fn get_winner(&self) -> Option<SnakeRefData> {
+        // The last snake alive, or the longest alive snake if the game was stopped early
+        match self.check_end() {
+            Ok(snake) => Some(snake),
+            Err(_) => self.get_all_snake_refs().into_iter().filter(|x| x.alive).max_by_key(|x| x.size)
+        }
+    }
+
+    pub fn get_deaths(&self) -> &Vec<DeathRecord> {
+        &self.deaths
+    }
+
     pub fn get_all_snake_refs(&self) -> Vec<SnakeRefData> {
         self.snakes.iter().map(|x| x.get_data()).collect()
     }
@@ -272,6 +329,19 @@ impl<'a> SnakeGrid<'a> {
             .map(|x| (x.0, x.1.unwrap())).collect::<HashMap<_, _>>()
     }
 
+    pub fn get_stats_dict(&self) -> HashMap<i32, ControllerStats> {
+        self.snakes.iter()
+            .filter_map(|x| x.get_stats().map(|stats| (x.get_id(), stats)))
+            .collect()
+    }
+
+    pub fn take_controller_logs(&mut self) -> HashMap<i32, Vec<ControllerLog>> {
+        self.snakes.iter_mut()
+            .map(|x| (x.get_id(), x.take_controller_log()))
+            .filter(|x| !x.1.is_empty())
+            .collect()
+    }
+
     pub fn set_square(&mut self, index: usize, snake: Option<i32>) {
         // Very very unsafe! Only for debugging   (Because the snake'id is not bound to be the index) 
         let old = &self.grid[index];
@@ -294,7 +364,11 @@ impl<'a> SnakeGrid<'a> {
     }
 
     pub fn reconnect(&mut self) {
-        self.snakes.iter_mut().for_each(|snake| { snake.disconnect_controller(); snake.connect_controller(); });
+        let (grid_size, rules) = ((self.width, self.height), self.rules.clone());
+        self.snakes.iter_mut().for_each(|snake| {
+            snake.disconnect_controller();
+            snake.connect_controller(&MatchInfo { snake_id: snake.get_id(), grid_size, rules: rules.clone() });
+        });
     }
     
     pub fn clear(&mut self) {
@@ -312,6 +386,217 @@ impl<'a> SnakeGrid<'a> {
         self.on_death_handler = Some(handler);
     }
 
+    pub fn snapshot(&self) -> GridSnapshot {
+        GridSnapshot {
+            width: self.width,
+            height: self.height,
+            tick: self.tick_count,
+            grid: self.grid.clone(),
+            deaths: self.deaths.clone(),
+            snakes: self.snakes.iter().map(|x| x.snapshot()).collect(),
+            rng: self.rng.clone()
+        }
+    }
+
+    pub fn restore(&mut self, snapshot: &GridSnapshot) {
+        // Snakes are matched by id, the controllers stay attached to their snakes
+        if snapshot.width != self.width || snapshot.height != self.height {
+            println!("[ERROR] Snapshot has a different grid size ({}x{})", snapshot.width, snapshot.height);
+            return;
+        }
+        self.grid = snapshot.grid.clone();
+        self.tick_count = snapshot.tick;
+        self.deaths = snapshot.deaths.clone();
+        self.rng = snapshot.rng.clone();
+        for snake_snapshot in &snapshot.snakes {
+            if let Some(snake) = self.snakes.iter_mut().find(|x| x.get_id() == snake_snapshot.id) {
+                snake.restore(snake_snapshot);
+                let (r, g, b) = snake_snapshot.color;
+                self.snake_colors[snake_snapshot.id as usize] = Color::from_rgba(r, g, b, 255);
+            }
+        }
+    }
+
+    pub fn get_height(&self) -> i32 {
+        self.height
+    }
+
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn reseed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = ChaCha8Rng::seed_from_u64(seed);
+    }
+
+    pub fn get_tick_count(&self) -> u32 {
+        self.tick_count
+    }
+
+    pub fn get_last_inputs(&self) -> &Vec<Direction> {
+        &self.last_inputs
+    }
+
+    pub fn get_last_events(&self) -> &Vec<GameEvent> {
+        &self.last_events
+    }
+
+    pub fn save_state(&self) -> GameState {
+        GameState::new(self.rules.clone(), self.snapshot())
+    }
+
+    pub fn load_state(&mut self, state: &GameState) -> Result<(), String> {
+        state.grid.validate()?;
+        if state.grid.snakes.len() != self.snakes.len() {
+            return Err(format!("Game state has {} snakes but {} players are connected", state.grid.snakes.len(), self.snakes.len()));
+        }
+        if (state.grid.width, state.grid.height) != (self.width, self.height) {
+            return Err(format!("Game state has a different grid size ({}x{})", state.grid.width, state.grid.height));
+        }
+        self.rules = state.rules.clone();
+        self.restore(&state.grid);
+        Ok(())
+    }
+
+
+
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    struct FixedController(Direction);
+
+    impl SnakeController for FixedController {
+        fn get_name(&self) -> String {
+            "Fixed".to_string()
+        }
+
+        fn next_direction(&self) -> Direction {
+            self.0
+        }
+
+        fn clone_weak(&self) -> Box<dyn SnakeController> {
+            Box::new(FixedController(self.0))
+        }
+    }
+
+    #[derive(Debug)]
+    struct RecordingController {
+        direction: Direction,
+        moves: Vec<Direction>
+    }
+
+    impl SnakeController for RecordingController {
+        fn get_name(&self) -> String {
+            "Recording".to_string()
+        }
+
+        fn next_direction(&self) -> Direction {
+            self.direction
+        }
+
+        fn on_move(&mut self, direction: Direction) {
+            self.moves.push(direction);
+        }
+
+        fn clone_weak(&self) -> Box<dyn SnakeController> {
+            Box::new(RecordingController { direction: self.direction, moves: Vec::new() })
+        }
+    }
+
+    fn started_grid(controllers: &mut [FixedController]) -> SnakeGrid<'_> {
+        let mut grid = SnakeGrid::new(12, 10);
+        controllers.iter_mut().for_each(|x| grid.add_snake(x));
+        grid.reseed(7);
+        grid.start_game();
+        grid.do_place_food();
+        grid
+    }
+
+    #[test]
+    fn restore_returns_to_the_snapshot() {
+        let mut controllers = [FixedController(Direction::RIGHT), FixedController(Direction::DOWN)];
+        let mut grid = started_grid(&mut controllers);
+        let start = grid.snapshot();
 
+        (0..3).for_each(|_| grid.tick());
+        let after_ticks = grid.snapshot();
+        assert_ne!(start.checksum(), after_ticks.checksum());
 
-}
\ No newline at end of file
+        grid.restore(&start);
+        assert_eq!(grid.snapshot().checksum(), start.checksum());
+        assert_eq!(grid.clone_raw_grid(), start.grid);
+        assert_eq!(grid.get_tick_count(), 0);
+
+        // Playing the same ticks again after stepping back ends in the same position
+        (0..3).for_each(|_| grid.tick());
+        assert_eq!(grid.snapshot().checksum(), after_ticks.checksum());
+    }
+
+    #[test]
+    fn restore_keeps_dead_snakes_and_deaths() {
+        let mut controllers = [FixedController(Direction::UP), FixedController(Direction::DOWN)];
+        let mut grid = started_grid(&mut controllers);
+        while grid.check_end() == Err(1) {
+            grid.tick();
+        }
+        let end = grid.snapshot();
+        assert!(!end.deaths.is_empty());
+
+        let mut controllers = [FixedController(Direction::UP), FixedController(Direction::DOWN)];
+        let mut other = started_grid(&mut controllers);
+        other.restore(&end);
+        assert_eq!(other.get_deaths(), &end.deaths);
+        assert_eq!(other.snapshot().checksum(), end.checksum());
+        assert_ne!(other.check_end(), Err(1)); // The game is over in the restored grid too
+    }
+
+    #[test]
+    fn load_state_needs_the_same_snakes() {
+        let mut controllers = [FixedController(Direction::RIGHT), FixedController(Direction::LEFT)];
+        let state = started_grid(&mut controllers).save_state();
+
+        let mut controllers = [FixedController(Direction::RIGHT), FixedController(Direction::LEFT), FixedController(Direction::UP)];
+        let mut grid = started_grid(&mut controllers);
+        let before = grid.snapshot();
+        assert!(grid.load_state(&state).is_err());
+        assert_eq!(grid.snapshot().checksum(), before.checksum());
+
+        let mut controllers = [FixedController(Direction::RIGHT), FixedController(Direction::LEFT)];
+        let mut grid = SnakeGrid::new(12, 10);
+        controllers.iter_mut().for_each(|x| grid.add_snake(x));
+        assert!(grid.load_state(&state).is_ok());
+        assert_eq!(grid.snapshot().checksum(), state.grid.checksum());
+    }
+
+    #[test]
+    fn only_reports_moves_that_happened() {
+        let mut controllers = [Direction::RIGHT, Direction::UP].map(|direction| RecordingController { direction, moves: Vec::new() });
+        let mut grid = SnakeGrid::new(12, 10);
+        controllers.iter_mut().for_each(|x| grid.add_snake(x));
+        grid.start_game();
+        // The first snake crashes into a dead snake, which stops the tick before the second snake moves
+        let head = grid.snakes[0].get_head();
+        grid.grid[head as usize + 1] = Tile::DeadSnake;
+        grid.tick();
+        grid.tick();
+        drop(grid);
+        assert!(controllers[0].moves.is_empty());
+        assert_eq!(controllers[1].moves, vec![Direction::UP]);
+    }
+
+    #[test]
+    fn restore_ignores_a_different_grid_size() {
+        let mut controllers = [FixedController(Direction::RIGHT), FixedController(Direction::LEFT)];
+        let mut grid = started_grid(&mut controllers);
+        let before = grid.snapshot();
+        let mut other = before.clone();
+        other.width = 5;
+        other.tick = 9;
+        grid.restore(&other);
+        assert_eq!(grid.snapshot().checksum(), before.checksum());
+    }
+}
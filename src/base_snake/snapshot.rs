@@ -0,0 +1,212 @@
+use std::{fs, path::Path};
+
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use super::{events::{DeathCause, DeathRecord}, object::Tile, rules::GameRules, snake::SnakeState};
+
+pub const STATE_FORMAT_VERSION: u32 = 1;
+
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+fn fnv_hash(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, byte| (hash ^ *byte as u64).wrapping_mul(FNV_PRIME))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnakeSnapshot {
+    pub id: i32,
+    pub name: String, // Informational only, restoring keeps the name of the connected controller
+    pub tiles: Vec<i32>,
+    pub max_size: i32,
+    pub state: SnakeState,
+    pub color: (u8, u8, u8)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GridSnapshot {
+    // Everything needed to put a SnakeGrid back into the exact same position. The controllers are not part of it.
+    pub width: i32,
+    pub height: i32,
+    #[serde(default)]
+    pub tick: u32,
+    pub grid: Vec<Tile>,
+    #[serde(default)]
+    pub deaths: Vec<DeathRecord>,
+    pub snakes: Vec<SnakeSnapshot>,
+    pub rng: ChaCha8Rng
+}
+
+impl GridSnapshot {
+    pub fn checksum(&self) -> u64 {
+        // FNV-1a over the engine state. Unlike the std hasher it stays the same across Rust versions, so it can be archived.
+        let mut hash = FNV_OFFSET;
+        hash = fnv_hash(hash, &self.tick.to_le_bytes());
+        for tile in &self.grid {
+            hash = fnv_hash(hash, &(tile.get_tile_id() as i16).to_le_bytes());
+        }
+        for snake in &self.snakes {
+            hash = fnv_hash(hash, &snake.id.to_le_bytes());
+            hash = fnv_hash(hash, &snake.max_size.to_le_bytes());
+            hash = fnv_hash(hash, &[if snake.state == SnakeState::ALIVE { 1 } else { 0 }]);
+            for tile in &snake.tiles {
+                hash = fnv_hash(hash, &tile.to_le_bytes());
+            }
+        }
+        fnv_hash(hash, &self.rng.get_word_pos().to_le_bytes())
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        // Snake ids are their join order, so they have to be 0..count and every cell has to be inside the grid
+        // Checked before multiplying, a broken file may hold any size
+        let cells = if self.width > 0 && self.height > 0 { self.width.checked_mul(self.height) } else { None };
+        let Some(cells) = cells.map(|x| x as usize).filter(|x| *x == self.grid.len()) else {
+            return Err("Game state grid does not match its size".into());
+        };
+        let count = self.snakes.len() as i32;
+        for (i, snake) in self.snakes.iter().enumerate() {
+            if snake.id != i as i32 {
+                return Err(format!("Snake {} has the id {}, expected {}", i, snake.id, i));
+            }
+            if let Some(tile) = snake.tiles.iter().find(|x| **x < 0 || **x as usize >= cells) {
+                return Err(format!("Snake {} has the tile {} outside of the grid", snake.id, tile));
+            }
+        }
+        let unknown = |id: i32| id < 0 || id >= count;
+        if let Some(Tile::Snake { id }) = self.grid.iter().find(|x| matches!(x, Tile::Snake { id } if unknown(*id))) {
+            return Err(format!("Grid contains the unknown snake {}", id));
+        }
+        if let Some(death) = self.deaths.iter().find(|x| unknown(x.snake_id) || matches!(x.cause, DeathCause::Snake { id } if unknown(id))) {
+            return Err(format!("Death of snake {} refers to an unknown snake", death.snake_id));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameState {
+    // The stable on-disk format of a position (JSON)
+    pub version: u32,
+    pub rules: GameRules,
+    pub grid: GridSnapshot
+}
+
+impl GameState {
+    pub fn new(rules: GameRules, grid: GridSnapshot) -> Self {
+        Self { version: STATE_FORMAT_VERSION, rules, grid }
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| format!("Unable to serialize game state: {}", e))
+    }
+
+    pub fn from_json(json: &str) -> Result<GameState, String> {
+        let state: GameState = serde_json::from_str(json).map_err(|e| format!("Invalid game state: {}", e))?;
+        if state.version > STATE_FORMAT_VERSION {
+            return Err(format!("Game state version {} is newer than the supported version {}", state.version, STATE_FORMAT_VERSION));
+        }
+        state.rules.validate()?;
+        state.grid.validate()?;
+        Ok(state)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Unable to create {}: {}", parent.display(), e))?;
+        }
+        fs::write(path, self.to_json()?).map_err(|e| format!("Unable to write {}: {}", path.display(), e))
+    }
+
+    pub fn load(path: &Path) -> Result<GameState, String> {
+        let json = fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+        GameState::from_json(&json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+
+    fn snake(id: i32, tiles: Vec<i32>) -> SnakeSnapshot {
+        SnakeSnapshot { id, name: format!("Snake {}", id), max_size: tiles.len() as i32, tiles, state: SnakeState::ALIVE, color: (0, 0, 0) }
+    }
+
+    fn state() -> GameState {
+        let mut grid = vec![Tile::EMPTY; 12];
+        grid[0] = Tile::Snake { id: 0 };
+        grid[11] = Tile::Snake { id: 1 };
+        grid[5] = Tile::FOOD;
+        let snapshot = GridSnapshot { width: 4, height: 3, tick: 2, grid, deaths: Vec::new(), snakes: vec![snake(0, vec![0]), snake(1, vec![11])], rng: ChaCha8Rng::seed_from_u64(1) };
+        GameState::new(GameRules::default(), snapshot)
+    }
+
+    fn load(state: &GameState) -> Result<GameState, String> {
+        GameState::from_json(&state.to_json().unwrap())
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let state = state();
+        let loaded = load(&state).unwrap();
+        assert_eq!(loaded.grid.checksum(), state.grid.checksum());
+        assert_eq!(loaded.grid.grid, state.grid.grid);
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        let mut state = state();
+        state.version = STATE_FORMAT_VERSION + 1;
+        assert!(load(&state).is_err());
+    }
+
+    #[test]
+    fn rejects_a_grid_of_the_wrong_size() {
+        let mut state = state();
+        state.grid.height = 4;
+        assert!(load(&state).is_err());
+    }
+
+    #[test]
+    fn rejects_huge_grid_sizes() {
+        let mut state = state();
+        state.grid.width = i32::MAX;
+        assert!(state.grid.validate().is_err());
+        state.grid.height = -1;
+        assert!(state.grid.validate().is_err());
+    }
+
+    #[test]
+    fn rejects_snake_ids_out_of_order() {
+        let mut state = state();
+        state.grid.snakes[1].id = 5;
+        assert!(load(&state).is_err());
+    }
+
+    #[test]
+    fn rejects_tiles_outside_of_the_grid() {
+        let mut state = state();
+        state.grid.snakes[0].tiles.push(12);
+        assert!(load(&state).is_err());
+        state.grid.snakes[0].tiles = vec![-1];
+        assert!(load(&state).is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_snakes_in_the_grid() {
+        let mut state = state();
+        state.grid.grid[3] = Tile::Snake { id: 2 };
+        assert!(load(&state).is_err());
+    }
+
+    #[test]
+    fn rejects_deaths_of_unknown_snakes() {
+        let mut state = state();
+        state.grid.deaths.push(DeathRecord { snake_id: 0, tick: 1, cause: DeathCause::Snake { id: 3 } });
+        assert!(load(&state).is_err());
+        state.grid.deaths[0].cause = DeathCause::Wall;
+        assert!(load(&state).is_ok());
+    }
+}
@@ -0,0 +1,90 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::{events::DeathCause, locale::{trf, Text}, match_result::{MatchResult, Standing}};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BotStats {
+    // Summed over all games, the averages are computed on display
+    pub games: u32,
+    pub kills: u32, // Other snakes that ran into this one
+    pub deaths: BTreeMap<String, u32>, // Keyed by DeathCause::label
+    pub food_eaten: u32,
+    pub length_sum: u32,
+    pub max_length: u32,
+    pub survival_ticks: u32,
+    pub missed_inputs: u32,
+    pub latency_sum: f32, // Milliseconds
+    pub latency_games: u32 // Games with a measured latency
+}
+
+impl BotStats {
+    pub fn from_standing(standing: &Standing, result: &MatchResult) -> Self {
+        let length = standing.size.max(0) as u32;
+        let kills = result.standings.iter()
+            .filter(|x| x.id != standing.id && x.death_cause == Some(DeathCause::Snake { id: standing.id }))
+            .count() as u32;
+
+        let mut stats = BotStats {
+            games: 1,
+            kills,
+            food_eaten: length.saturating_sub(1), // Every snake starts with a length of one
+            length_sum: length,
+            max_length: length,
+            survival_ticks: standing.death_tick.unwrap_or(result.ticks),
+            missed_inputs: standing.missed_inputs,
+            ..Default::default()
+        };
+        if let Some(cause) = standing.death_cause {
+            stats.deaths.insert(cause.label().to_string(), 1);
+        }
+        if let Some(latency) = standing.average_latency {
+            stats.latency_sum = latency;
+            stats.latency_games = 1;
+        }
+        stats
+    }
+
+    pub fn add(&mut self, other: &BotStats) {
+        self.games += other.games;
+        self.kills += other.kills;
+        for (cause, count) in &other.deaths {
+            *self.deaths.entry(cause.clone()).or_insert(0) += count;
+        }
+        self.food_eaten += other.food_eaten;
+        self.length_sum += other.length_sum;
+        self.max_length = self.max_length.max(other.max_length);
+        self.survival_ticks += other.survival_ticks;
+        self.missed_inputs += other.missed_inputs;
+        self.latency_sum += other.latency_sum;
+        self.latency_games += other.latency_games;
+    }
+
+    pub fn total_deaths(&self) -> u32 {
+        self.deaths.values().sum()
+    }
+
+    pub fn average_length(&self) -> f32 {
+        if self.games == 0 { 0. } else { self.length_sum as f32 / self.games as f32 }
+    }
+
+    pub fn average_survival(&self) -> f32 {
+        if self.games == 0 { 0. } else { self.survival_ticks as f32 / self.games as f32 }
+    }
+
+    pub fn average_latency(&self) -> Option<f32> {
+        if self.latency_games == 0 { None } else { Some(self.latency_sum / self.latency_games as f32) }
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        let deaths = self.deaths.iter().map(|(cause, count)| format!("{} {}", DeathCause::name_of_label(cause), count)).collect::<Vec<_>>().join(", ");
+        vec![
+            trf(Text::StatsGames, &[&self.games, &self.kills, &self.food_eaten]),
+            trf(Text::StatsDeaths, &[&self.total_deaths(), &if deaths.is_empty() { String::new() } else { format!(" ({})", deaths) }]),
+            trf(Text::StatsLength, &[&format!("{:.1}", self.average_length()), &self.max_length]),
+            trf(Text::StatsSurvival, &[&format!("{:.1}", self.average_survival())]),
+            trf(Text::StatsLatency, &[&self.average_latency().map(|x| format!("{:.1}ms", x)).unwrap_or("-".to_string()), &self.missed_inputs]),
+        ]
+    }
+}
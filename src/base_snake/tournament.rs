@@ -0,0 +1,310 @@
+use std::fmt::Write;
+
+use super::{bracket::{Bracket, BracketFormat, MAX_REPLAYS}, export::stats::{ScoreRow, StatsExport}, stats::BotStats, headless::{play_headless_match, MatchSettings}, match_result::{MatchResult, RosterKeys}, rating::RatingTable, snake::SnakeController};
+
+#[derive(Debug, Clone, Default)]
+pub struct TournamentRow {
+    pub name: String,
+    pub played: u32,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+    pub placement_sum: u32
+}
+
+impl TournamentRow {
+    pub fn average_placement(&self) -> f32 {
+        if self.played == 0 {
+            return 0.;
+        }
+        self.placement_sum as f32 / self.played as f32
+    }
+}
+
+pub struct Tournament {
+    entrants: Vec<Box<dyn SnakeController>>, // Used as templates, every match gets a fresh clone_weak
+    rows: Vec<TournamentRow>,
+    keys: RosterKeys, // Roster slots are the entrant indices
+    pub settings: MatchSettings,
+    pub ratings: Option<RatingTable>, // Updated and saved after every match
+    results: Vec<(Vec<usize>, MatchResult)> // Group and result of every match played
+}
+
+pub fn check_group_size(group_size: usize, entrants: usize) -> Result<(), String> {
+    // A match needs at least two snakes and can not have more snakes than there are entrants
+    if group_size < 2 {
+        return Err(format!("Group size has to be at least 2, got {}", group_size));
+    }
+    if group_size > entrants {
+        return Err(format!("Group size {} is larger than the {} players", group_size, entrants));
+    }
+    Ok(())
+}
+
+pub fn combinations(count: usize, group_size: usize) -> Result<Vec<Vec<usize>>, String> {
+    // All sets of `group_size` indices out of 0..count, in lexicographic order
+    if group_size == 0 || group_size > count {
+        return Err(format!("Unable to pick groups of {} out of {}", group_size, count));
+    }
+    let mut groups = Vec::new();
+    let mut current: Vec<usize> = (0..group_size).collect();
+    loop {
+        groups.push(current.clone());
+
+        let Some(i) = (0..group_size).rev().find(|&i| current[i] < count - group_size + i) else {
+            return Ok(groups);
+        };
+        current[i] += 1;
+        for j in (i + 1)..group_size {
+            current[j] = current[j - 1] + 1;
+        }
+    }
+}
+
+impl Tournament {
+    pub fn new(entrants: Vec<Box<dyn SnakeController>>, settings: MatchSettings) -> Self {
+        let rows = entrants.iter().map(|x| TournamentRow { name: x.get_name(), ..Default::default() }).collect();
+        Self { entrants, rows, keys: RosterKeys::default(), settings, ratings: None, results: Vec::new() }
+    }
+
+    pub fn entrant_count(&self) -> usize {
+        self.entrants.len()
+    }
+
+    pub fn get_rows(&self) -> &Vec<TournamentRow> {
+        &self.rows
+    }
+
+    pub fn play_group(&mut self, group: &[usize]) -> MatchResult {
+        // The snake ids of the match are the positions in the group
+        let mut controllers: Vec<Box<dyn SnakeController>> = group.iter().map(|i| self.entrants[*i].clone_weak()).collect();
+        let mut settings = self.settings.clone();
+        settings.seed = settings.seed.map(|x| x.wrapping_add(self.results.len() as u64)); // Every match gets its own seed
+        let result = play_headless_match(&mut controllers, &settings);
+        self.record_match(group, &result);
+        result
+    }
+
+    fn record_match(&mut self, group: &[usize], result: &MatchResult) {
+        // The keys are looked up once per entrant, so the rows and the ratings always agree
+        let keys = self.keys.match_keys(group, result);
+        self.record_result(group, &keys, result);
+        self.results.push((group.to_vec(), result.clone()));
+        if let Some(ratings) = self.ratings.as_mut() {
+            ratings.update(&result.standings, &keys);
+            if let Err(e) = ratings.save() {
+                println!("[ERROR] {}", e);
+            }
+        }
+    }
+
+    pub fn record_result(&mut self, group: &[usize], keys: &[String], result: &MatchResult) {
+        // Group and keys are indexed by snake id
+        for standing in &result.standings {
+            let row = &mut self.rows[group[standing.id as usize]];
+            row.name = keys[standing.id as usize].clone(); // Same key as in the ratings
+            row.played += 1;
+            row.placement_sum += standing.placement;
+
+            // Without a winner the snakes that died last share the draw
+            match result.winner {
+                Some(winner) if winner == standing.id => row.wins += 1,
+                None if standing.placement == 1 => row.draws += 1,
+                _ => row.losses += 1
+            }
+        }
+    }
+
+    pub fn run_round_robin(&mut self, group_size: usize, games_per_group: u32) -> Result<(), String> {
+        check_group_size(group_size, self.entrants.len())?;
+        let groups = combinations(self.entrants.len(), group_size)?;
+        let total = groups.len() as u32 * games_per_group;
+        let mut played = 0;
+        for group in &groups {
+            for _ in 0..games_per_group {
+                played += 1;
+                let names: Vec<String> = group.iter().map(|x| self.rows[*x].name.clone()).collect();
+                println!("Game {}/{}: {}", played, total, names.join(" vs "));
+
+                let result = self.play_group(group);
+                match result.winner {
+                    Some(winner) => println!("{} won after {} ticks", result.standings.iter().find(|x| x.id == winner).map(|x| x.name.clone()).unwrap_or_default(), result.ticks),
+                    None => println!("Tie after {} ticks", result.ticks),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn run_bracket(&mut self, format: BracketFormat) -> Bracket {
+        let names = self.rows.iter().map(|x| x.name.clone()).collect();
+        let mut bracket = Bracket::new(format, names, self.ratings.as_ref());
+        while let Some(index) = bracket.next_match() {
+            let group = bracket.get_matches()[index].entrants.clone();
+            let names: Vec<String> = group.iter().map(|x| self.rows[*x].name.clone()).collect();
+            println!("Round {}: {}", bracket.get_round(), names.join(" vs "));
+
+            let result = self.play_group(&group);
+            if !bracket.record(index, &result) {
+                println!("No winner, replay {} of {}", bracket.get_matches()[index].replays, MAX_REPLAYS);
+            }
+        }
+        bracket
+    }
+
+    pub fn standings(&self) -> Vec<TournamentRow> {
+        let mut rows = self.rows.clone();
+        rows.sort_by(|a, b| b.wins.cmp(&a.wins)
+            .then(b.draws.cmp(&a.draws))
+            .then(a.average_placement().total_cmp(&b.average_placement())));
+        rows
+    }
+
+    pub fn export_stats(&self) -> StatsExport {
+        let mut stats = vec![BotStats::default(); self.rows.len()];
+        for (group, result) in &self.results {
+            for standing in &result.standings {
+                stats[group[standing.id as usize]].add(&BotStats::from_standing(standing, result));
+            }
+        }
+        let scoreboard = self.rows.iter().zip(stats).map(|(row, stats)| ScoreRow {
+            name: row.name.clone(),
+            games: row.played,
+            wins: row.wins,
+            rating: self.ratings.as_ref().map(|x| x.get(&row.name).rating),
+            stats
+        }).collect();
+        StatsExport::new(scoreboard, self.results.iter().map(|x| x.1.clone()).collect())
+    }
+
+    pub fn format_standings(&self) -> String {
+        let rows = self.standings();
+        let name_width = rows.iter().map(|x| x.name.chars().count()).max().unwrap_or(0).max(4);
+
+        let mut table = String::new();
+        let _ = writeln!(table, "{:>3}  {:<name_width$}  {:>6}  {:>4}  {:>5}  {:>6}  {:>9}  {:>6}", "#", "Name", "Played", "Wins", "Draws", "Losses", "Avg Place", "Rating");
+        for (i, row) in rows.iter().enumerate() {
+            let rating = self.ratings.as_ref().map(|x| format!("{:.0}", x.get(&row.name).rating)).unwrap_or("-".to_string());
+            let _ = writeln!(table, "{:>3}  {:<name_width$}  {:>6}  {:>4}  {:>5}  {:>6}  {:>9.2}  {:>6}", i + 1, row.name, row.played, row.wins, row.draws, row.losses, row.average_placement(), rating);
+        }
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::base_snake::{match_result::Standing, snake::Direction};
+
+    use super::*;
+
+    #[derive(Debug)]
+    struct NamedController(String);
+
+    impl SnakeController for NamedController {
+        fn get_name(&self) -> String {
+            self.0.clone()
+        }
+
+        fn next_direction(&self) -> Direction {
+            Direction::UP
+        }
+
+        fn clone_weak(&self) -> Box<dyn SnakeController> {
+            Box::new(NamedController(self.0.clone()))
+        }
+    }
+
+    fn tournament(names: &[&str]) -> Tournament {
+        let entrants = names.iter().map(|x| Box::new(NamedController(x.to_string())) as Box<dyn SnakeController>).collect();
+        Tournament::new(entrants, MatchSettings { grid_size: (10, 10), rules: Default::default(), seed: None, replay_dir: None, colors: Vec::new() })
+    }
+
+    fn result(names: &[&str], placements: &[u32], winner: Option<i32>) -> MatchResult {
+        let standings = names.iter().zip(placements).enumerate().map(|(id, (name, placement))| Standing {
+            placement: *placement,
+            id: id as i32,
+            name: name.to_string(),
+            size: 1,
+            alive: winner == Some(id as i32),
+            death_tick: None,
+            death_cause: None,
+            missed_inputs: 0,
+            average_latency: None
+        }).collect();
+        MatchResult { seed: 0, ticks: 10, duration: 1., winner, standings }
+    }
+
+    #[test]
+    fn combinations_in_lexicographic_order() {
+        assert_eq!(combinations(4, 2), Ok(vec![vec![0, 1], vec![0, 2], vec![0, 3], vec![1, 2], vec![1, 3], vec![2, 3]]));
+        assert_eq!(combinations(3, 3), Ok(vec![vec![0, 1, 2]]));
+        assert_eq!(combinations(6, 3).map(|x| x.len()), Ok(20));
+    }
+
+    #[test]
+    fn combinations_reject_impossible_groups() {
+        assert!(combinations(3, 0).is_err());
+        assert!(combinations(3, 4).is_err());
+        assert!(combinations(0, 1).is_err());
+    }
+
+    #[test]
+    fn group_size_needs_two_snakes() {
+        assert!(check_group_size(0, 4).is_err());
+        assert!(check_group_size(1, 4).is_err());
+        assert!(check_group_size(5, 4).is_err());
+        assert!(check_group_size(2, 4).is_ok());
+        assert!(check_group_size(4, 4).is_ok());
+    }
+
+    #[test]
+    fn round_robin_rejects_invalid_group_sizes() {
+        let mut tournament = tournament(&["A", "B", "C"]);
+        assert!(tournament.run_round_robin(1, 1).is_err());
+        assert!(tournament.run_round_robin(4, 1).is_err());
+        assert!(tournament.get_rows().iter().all(|x| x.played == 0));
+    }
+
+    #[test]
+    fn results_are_counted_per_entrant() {
+        let mut tournament = tournament(&["A", "B", "C"]);
+        // The snake ids are the positions in the group
+        tournament.record_match(&[0, 2], &result(&["A", "C"], &[1, 2], Some(0)));
+        tournament.record_match(&[1, 2], &result(&["B", "C"], &[2, 1], Some(1)));
+        tournament.record_match(&[0, 1], &result(&["A", "B"], &[1, 1], None));
+
+        let rows = tournament.get_rows();
+        assert_eq!((rows[0].played, rows[0].wins, rows[0].draws, rows[0].losses), (2, 1, 1, 0));
+        assert_eq!((rows[1].played, rows[1].wins, rows[1].draws, rows[1].losses), (2, 0, 1, 1));
+        assert_eq!((rows[2].played, rows[2].wins, rows[2].draws, rows[2].losses), (2, 1, 0, 1));
+        assert_eq!(rows[2].average_placement(), 1.5);
+    }
+
+    #[test]
+    fn standings_sort_by_wins_draws_and_placement() {
+        let mut tournament = tournament(&["A", "B", "C"]);
+        tournament.record_match(&[0, 1, 2], &result(&["A", "B", "C"], &[2, 3, 1], Some(2)));
+        tournament.record_match(&[0, 1, 2], &result(&["A", "B", "C"], &[1, 1, 3], None));
+        tournament.record_match(&[0, 1], &result(&["A", "B"], &[1, 2], Some(0)));
+
+        let names: Vec<String> = tournament.standings().into_iter().map(|x| x.name).collect();
+        assert_eq!(names, vec!["A", "C", "B"]);
+    }
+
+    #[test]
+    fn same_names_keep_their_keys_across_groups() {
+        let mut tournament = tournament(&["Bot", "C", "Bot"]);
+        tournament.ratings = Some(RatingTable::default());
+        // The second "Bot" plays first and keeps the plain name in every later match
+        tournament.record_match(&[2, 1], &result(&["Bot", "C"], &[1, 2], Some(0)));
+        tournament.record_match(&[0, 1], &result(&["Bot", "C"], &[2, 1], Some(1)));
+        tournament.record_match(&[0, 2], &result(&["Bot", "Bot"], &[2, 1], Some(1)));
+
+        let rows = tournament.get_rows();
+        assert_eq!((rows[0].name.as_str(), rows[0].wins, rows[0].losses), ("Bot (2)", 0, 2));
+        assert_eq!((rows[2].name.as_str(), rows[2].wins, rows[2].losses), ("Bot", 2, 0));
+        let ratings = tournament.ratings.as_ref().unwrap();
+        assert_eq!((ratings.get("Bot").games, ratings.get("Bot (2)").games), (2, 2));
+        assert!(ratings.get("Bot").rating > ratings.get("Bot (2)").rating);
+    }
+}
@@ -1,126 +1,193 @@
-use std::{sync::Arc, thread::sleep, time::{Duration, Instant}};
-
-use multisnake::base_snake::{scenes::{connect::{add_players, connection_screen, GameConfig}, snake_draw::snake_draw}, scoreboard::{self, Scoreboard}};
-use macroquad::{audio::{load_sound, play_sound, PlaySoundParams}, prelude::*};
-use multisnake::base_snake::snakegrid::SnakeGrid;
-
-
-fn window_conf() -> Conf {
+use std::{path::{Path, PathBuf}, thread::sleep, time::Duration};
+
+use multisnake::base_snake::{scenes::{bracket::{bracket_view, BracketSettings}, connect::{add_players, connection_screen, GameConfig}, game::{create_grid, play_match, GameOptions, GameSounds}, replay_viewer::replay_viewer, snake_draw::snake_draw}, scoreboard::Scoreboard};
+use macroquad::prelude::*;
+use multisnake::base_snake::snake::SnakeController;
+use multisnake::base_snake::snapshot::GameState;
+use multisnake::base_snake::replay::Replay;
+use multisnake::base_snake::consts;
+use multisnake::base_snake::config::Config;
+use multisnake::base_snake::locale::set_language;
+use multisnake::base_snake::cli::{parse_args, CliOptions, Command, TournamentFormat, USAGE};
+use multisnake::base_snake::headless::MatchSettings;
+use multisnake::base_snake::tournament::Tournament;
+use multisnake::base_snake::rating::RatingTable;
+
+
+fn window_conf(config: &Config) -> Conf {
     Conf {
         window_title: "MultiSnake".to_owned(),
-        window_width: 1200,
-        window_height: 600,
+        window_width: config.window.width,
+        window_height: config.window.height,
+        fullscreen: config.window.fullscreen,
         ..Default::default()
     }
 }
 
 
-#[macroquad::main(window_conf)]
-async fn main() {
-    let sound_coin = Arc::new(load_sound("assets/pickupCoin.wav").await.unwrap());
-    let sound_explosion = Arc::new(load_sound("assets/explosion.wav").await.unwrap());
+fn main() {
+    let config = Config::load(Path::new(consts::CONFIG_FILE));
+    set_language(config.language);
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let command = match parse_args(&args, &config) {
+        Ok(command) => command,
+        Err(e) => {
+            println!("{}\n\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    match command {
+        Command::Help => println!("{}", USAGE),
+        Command::VerifyReplays(paths) => std::process::exit(verify_replays(&paths)),
+        Command::Headless(options) => std::process::exit(run_headless(&options, &config)),
+        command => macroquad::Window::from_config(window_conf(&config), run(command, config))
+    }
+}
+
+fn verify_replays(paths: &[String]) -> i32 {
+    // Headless, returns the exit code
+    let mut failed = 0;
+    for path in paths {
+        match Replay::load(Path::new(path)).and_then(|replay| replay.verify().map_err(|e| e.to_string())) {
+            Ok(ticks) => println!("{}: OK ({} ticks)", path, ticks),
+            Err(e) => {
+                println!("{}: FAILED {}", path, e);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 { 1 } else { 0 }
+}
+
+fn run_headless(options: &CliOptions, config: &Config) -> i32 {
+    // Plays all games without a window, a batch of games is a round robin with a single group
+    let settings = MatchSettings {
+        grid_size: options.grid_size,
+        rules: options.rules.clone(),
+        seed: options.seed,
+        replay_dir: options.replay_dir(),
+        colors: config.colors.clone()
+    };
+    let mut tournament = Tournament::new(options.create_controllers(config), settings);
+    tournament.ratings = Some(RatingTable::load(Path::new(consts::RATINGS_FILE)));
+    let (prefix, played) = match options.tournament {
+        Some(TournamentFormat::Bracket(format)) => {
+            println!("{}", tournament.run_bracket(format).format_results());
+            ("tournament", Ok(()))
+        },
+        Some(TournamentFormat::RoundRobin) => ("tournament", tournament.run_round_robin(options.group_size, options.games.unwrap_or(1))),
+        None => ("headless", tournament.run_round_robin(options.players.len(), options.games.unwrap_or(1)))
+    };
+    if let Err(e) = played {
+        println!("[ERROR] {}", e);
+        return 1;
+    }
+
+    println!("{}", tournament.format_standings());
+    tournament.export_stats().save_session(&options.export_dir(), prefix);
+    0
+}
+
+fn cli_config(options: &CliOptions, config: &Config) -> GameConfig {
+    // Skips the lobby
+    let mut scoreboard = Scoreboard::load(Path::new(consts::SCOREBOARD_FILE));
+    scoreboard.set_export_dir(&options.export_dir());
+    GameConfig {
+        snake_controller_list: options.create_controllers(config),
+        grid_size: options.grid_size,
+        sandbox: options.sandbox,
+        snake_draw_mode: false,
+        rules: options.rules.clone(),
+        position_file: None,
+        replay_dir: options.replay_dir().map(|x| x.to_string_lossy().to_string()),
+        replay_file: None,
+        live_export: None,
+        json_export: false,
+        bracket: match options.tournament {
+            Some(TournamentFormat::Bracket(format)) => Some(format),
+            _ => None
+        },
+        scoreboard,
+        seed: options.seed,
+        games: options.games,
+        export_dir: options.export_dir().to_string_lossy().to_string()
+    }
+}
+
+async fn run(command: Command, mut config: Config) {
+    let sounds = GameSounds::load(config.volume).await;
+
+    let game_config = match command {
+        Command::WatchReplay(path) => {
+            replay_viewer(Path::new(&path)).await;
+            return;
+        },
+        Command::Play(options) => cli_config(&options, &config),
+        _ => add_players(&mut config).await
+    };
+    let GameConfig { snake_controller_list, grid_size: (mut grid_x, mut grid_y), sandbox, snake_draw_mode, rules, position_file, replay_dir, replay_file, live_export, json_export, bracket, mut scoreboard, seed, games, export_dir } = game_config;
+
+    if let Some(path) = replay_file {
+        replay_viewer(Path::new(&path)).await;
+        return;
+    }
 
-    let GameConfig { snake_controller_list, grid_size: (grid_x, grid_y), sandbox, snake_draw_mode } = add_players().await;
+    let options = GameOptions { sandbox, replay_dir, live_export, json_export, export_dir: PathBuf::from(export_dir) };
+    let mut ratings = RatingTable::load(Path::new(consts::RATINGS_FILE));
 
-    let mut scoreboard: Scoreboard = Scoreboard::new(snake_controller_list.len() as i32);
+    if let Some(format) = bracket {
+        let settings = BracketSettings { format, grid_size: (grid_x, grid_y), rules, colors: config.colors.clone(), seed, options };
+        bracket_view(&snake_controller_list, &settings, &sounds, &mut scoreboard, &mut ratings).await;
+        return;
+    }
+
+    let position = position_file.and_then(|path| match GameState::load(Path::new(&path)) {
+        Ok(state) => Some(state),
+        Err(e) => { println!("[ERROR] {}", e); None }
+    });
+    if let Some(state) = &position {
+        (grid_x, grid_y) = (state.grid.width, state.grid.height);
+    }
+
+    scoreboard.set_ratings(&ratings);
     
-    loop {
+    for game in 0.. {
+        if games.is_some_and(|x| game >= x) {
+            break;
+        }
 
-        let mut snake_controllers = snake_controller_list.iter().map(|x| (**x).clone_weak()).collect();
-        connection_screen(&mut snake_controllers).await;
+        // Disqualified bots are left out, the slots keep the scoreboard keys of the others
+        let slots: Vec<usize> = (0..snake_controller_list.len()).filter(|x| !snake_controller_list[*x].is_disqualified()).collect();
+        let mut snake_controllers: Vec<Box<dyn SnakeController>> = slots.iter().map(|x| snake_controller_list[*x].clone_weak()).collect();
+        if snake_controllers.len() < 2 {
+            println!("Not enough players left");
+            break;
+        }
+        connection_screen(&mut snake_controllers, (grid_x, grid_y), &rules).await;
         sleep(Duration::from_secs_f32(0.5));
 
-        let mut game_grid: SnakeGrid = SnakeGrid::new(grid_x, grid_y);
-        game_grid.register_on_food_handler(Box::new({
-            let sound = Arc::clone(&sound_coin);
-            
-            move || play_sound(
-                &sound,
-                PlaySoundParams {
-                    looped: false,
-                    volume: 0.2,
-                },
-            )
-        }));
-        game_grid.register_on_death(Box::new({
-            let sound = Arc::clone(&sound_explosion);
-            
-            move || play_sound(
-                &sound,
-                PlaySoundParams {
-                    looped: false,
-                    volume: 0.2,
-                },
-            )
-        }));
-        
-        snake_controllers.iter_mut().for_each(|x| { game_grid.add_snake(x.as_mut()); } );
-
-        game_grid.do_place_food();
+        let mut game_grid = create_grid(&mut snake_controllers, (grid_x, grid_y), &rules, seed.map(|x| x.wrapping_add(game as u64)), &config.colors, &sounds);
 
         if snake_draw_mode {
+            if let Some(Err(e)) = position.as_ref().map(|x| game_grid.load_state(x)) {
+                println!("[ERROR] {}", e);
+            }
             snake_draw(game_grid).await;
             continue;
         }
 
-        game_grid.start_game();  // Initialize all the Snakes (Spawnpoints)
-        scoreboard.initalize(game_grid.get_all_snake_refs()); // Draw the initial scoreboard now because the names and ids are unknown beforehand
-        game_grid.draw();
-
-        sleep(Duration::from_secs_f32(0.2));
-
-        let mut winner = None;
-        let start_time = Instant::now();
-
-        loop {
-            clear_background(BLACK);
-            
-            game_grid.update_input();
-            game_grid.tick();
-
-            game_grid.draw();
-            scoreboard.draw_widget(game_grid.get_info_dict());
-
-            next_frame().await;
-
-            
-            match game_grid.check_end() {
-                Ok(snake_data) => {
-                    if !sandbox {
-                        game_grid.draw();
-                        draw_end_message(&format!("{} Won!", snake_data.name)).await;
-                        winner = Some(snake_data.clone());
-                        break
-                    }
-                },
-                Err(1) if (start_time.elapsed() < Duration::from_secs(600) || sandbox) => {} // Ongoing
-                _ => {  // Err 0 or timeout
-                    if let Some(best_snake) = game_grid.get_all_snake_refs().iter().filter(|x| x.alive).max_by_key(|item| item.size) {
-                        if !sandbox {
-                            game_grid.draw();
-                            draw_end_message(&format!("{} Won!", best_snake.name)).await;
-                            winner = Some(best_snake.clone());
-                        }
-                        break;
-                    }
-                    draw_end_message(&format!("Tie!")).await;
-                    break;
-                },
-            }
+        let result = play_match(&mut game_grid, &slots, &options, &mut scoreboard).await;
 
-            game_grid.send_gamestate();
-            let now = Instant::now();
-            while now.elapsed() < Duration::from_secs_f32(0.15) {
-                game_grid.draw();
-                scoreboard.draw_widget(game_grid.get_info_dict());
-                next_frame().await;
+        if !sandbox {
+            ratings.update(&result.standings, &scoreboard.current_keys());
+            if let Err(e) = ratings.save().and(scoreboard.save()) {
+                println!("[ERROR] {}", e);
             }
-        }
+            scoreboard.set_ratings(&ratings);
 
-        if !sandbox {
-            if winner.is_some() {
-                snake_controllers.iter_mut().for_each(|x| x.send_winner(winner.as_ref().expect("No winner? How did we get here??").id));
-                scoreboard.add_win(&winner.unwrap());
+            if let Some(winner) = result.winner {
+                snake_controllers.iter_mut().for_each(|x| x.send_winner(winner));
             }
         }
 
@@ -131,10 +198,3 @@ async fn main() {
         sleep(Duration::from_secs_f32(0.05));
     }
 }
-
-async fn draw_end_message(message: &str){
-    let (height, width) = (screen_height(), screen_width());
-    draw_text(message, width*0.2, height*0.4, 60.0, WHITE);
-    next_frame().await;
-    sleep(Duration::from_secs(2));
-}